@@ -19,6 +19,20 @@ pub struct Uris {
     pub uris: Vec<String>,
 }
 
+#[derive(Serialize)]
+pub struct ReorderTracks {
+    pub range_start: usize,
+    pub insert_before: usize,
+    pub range_length: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot_id: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PlaylistSnapshot {
+    pub snapshot_id: String,
+}
+
 #[derive(Serialize)]
 pub struct PlayOffset {
     pub position: u32,
@@ -30,13 +44,21 @@ pub enum PlayRequest {
     Contextual {
         context_uri: String,
         offset: PlayOffset,
+        position_ms: u32,
     },
     Uris {
         uris: Vec<String>,
         offset: PlayOffset,
+        position_ms: u32,
     },
 }
 
+#[derive(Serialize)]
+pub struct TransferRequest {
+    pub device_ids: Vec<String>,
+    pub play: bool,
+}
+
 #[derive(Serialize)]
 pub struct Ids {
     pub ids: Vec<String>,
@@ -47,9 +69,12 @@ pub struct Name<'a> {
     pub name: &'a str,
 }
 
+#[derive(Clone, Copy, Debug)]
 pub enum SearchType {
     Artist,
     Album,
+    Track,
+    Playlist,
 }
 
 impl SearchType {
@@ -57,6 +82,8 @@ impl SearchType {
         match self {
             Self::Artist => "artist",
             Self::Album => "album",
+            Self::Track => "track",
+            Self::Playlist => "playlist",
         }
     }
 }
@@ -189,6 +216,8 @@ pub struct Playlist {
     pub images: Vec<Image>,
     pub tracks: Page<PlaylistTrack>,
     pub owner: PlaylistOwner,
+    #[serde(default)]
+    pub snapshot_id: Option<String>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -325,6 +354,12 @@ pub struct PlayerState {
     pub shuffle_state: bool,
     pub item: FailibleTrackItem,
     pub context: Option<PlayerContext>,
+    pub device: Option<PlayerDevice>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PlayerDevice {
+    pub id: Option<String>,
 }
 
 impl From<PlayerState> for ConnectPlayerState {
@@ -336,6 +371,7 @@ impl From<PlayerState> for ConnectPlayerState {
             shuffle_state,
             item,
             context,
+            device,
         }: PlayerState,
     ) -> Self {
         let repeat = match &repeat_state[..] {
@@ -359,6 +395,7 @@ impl From<PlayerState> for ConnectPlayerState {
             shuffle,
             source,
             current_song_id,
+            device_id: device.and_then(|device| device.id),
         }
     }
 }
@@ -368,6 +405,11 @@ pub struct TopTracks {
     pub tracks: Vec<TrackItem>,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+pub struct Recommendations {
+    pub tracks: Vec<TrackItem>,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct AlbumTrackItem {
     pub id: String,
@@ -408,6 +450,44 @@ impl FailibleTrackItem {
 pub struct RawSearchResults {
     pub albums: Option<Page<Album>>,
     pub artists: Option<Page<Artist>>,
+    pub tracks: Option<Page<TrackItem>>,
+    // Spotify sometimes sends null entries in place of playlists
+    pub playlists: Option<Page<Option<Playlist>>>,
+}
+
+impl From<RawSearchResults> for SearchResults {
+    fn from(results: RawSearchResults) -> Self {
+        let albums = results
+            .albums
+            .unwrap_or_default()
+            .into_iter()
+            .map(|album| album.into())
+            .collect();
+
+        let artists = results
+            .artists
+            .unwrap_or_default()
+            .into_iter()
+            .map(|artist| artist.into())
+            .collect();
+
+        let tracks = results.tracks.unwrap_or_default().into();
+
+        let playlists = results
+            .playlists
+            .unwrap_or_default()
+            .into_iter()
+            .flatten()
+            .map(|playlist| playlist.into())
+            .collect();
+
+        Self {
+            albums,
+            artists,
+            tracks,
+            playlists,
+        }
+    }
 }
 
 impl From<Artist> for ArtistSummary {
@@ -462,6 +542,12 @@ impl From<TopTracks> for Vec<SongDescription> {
     }
 }
 
+impl From<Recommendations> for Vec<SongDescription> {
+    fn from(recommendations: Recommendations) -> Self {
+        Page::new(recommendations.tracks).into()
+    }
+}
+
 impl<T> From<Page<T>> for Vec<SongDescription>
 where
     T: TryInto<TrackItem>,
@@ -493,52 +579,55 @@ where
         };
         let songs = page
             .into_iter()
-            .filter_map(|t| {
-                let TrackItem { track, album } = t.try_into().ok()?;
-                let AlbumTrackItem {
-                    artists,
-                    id,
-                    uri,
-                    name,
-                    duration_ms,
-                    track_number,
-                } = track;
-                let artists = artists
-                    .into_iter()
-                    .map(|a| ArtistRef {
-                        id: a.id,
-                        name: a.name,
-                    })
-                    .collect::<Vec<ArtistRef>>();
-
-                let art = album.best_image_for_width(200).map(|i| &i.url).cloned();
-                let Album {
-                    id: album_id,
-                    name: album_name,
-                    ..
-                } = album;
-
-                let album_ref = AlbumRef {
-                    id: album_id,
-                    name: album_name,
-                };
-
-                Some(SongDescription {
-                    id,
-                    track_number: track_number.map(|u| u as u32),
-                    uri,
-                    title: name,
-                    artists,
-                    album: album_ref,
-                    duration: duration_ms as u32,
-                    art,
-                })
-            })
+            .filter_map(|t| Some(t.try_into().ok()?.into()))
             .collect();
         SongBatch { songs, batch }
     }
 }
 
+impl From<TrackItem> for SongDescription {
+    fn from(TrackItem { track, album }: TrackItem) -> Self {
+        let AlbumTrackItem {
+            artists,
+            id,
+            uri,
+            name,
+            duration_ms,
+            track_number,
+        } = track;
+        let artists = artists
+            .into_iter()
+            .map(|a| ArtistRef {
+                id: a.id,
+                name: a.name,
+            })
+            .collect::<Vec<ArtistRef>>();
+
+        let art = album.best_image_for_width(200).map(|i| &i.url).cloned();
+        let Album {
+            id: album_id,
+            name: album_name,
+            ..
+        } = album;
+
+        let album_ref = AlbumRef {
+            id: album_id,
+            name: album_name,
+        };
+
+        Self {
+            id,
+            track_number: track_number.map(|u| u as u32),
+            uri,
+            title: name,
+            artists,
+            album: album_ref,
+            duration: duration_ms as u32,
+            art,
+        }
+    }
+}
+
 impl TryFrom<Album> for SongBatch {
     type Error = ();
 
@@ -617,6 +706,7 @@ impl From<Playlist> for PlaylistDescription {
             name,
             tracks,
             owner,
+            snapshot_id,
             ..
         } = playlist;
         let PlaylistOwner {
@@ -633,6 +723,7 @@ impl From<Playlist> for PlaylistDescription {
                 id: owner_id,
                 display_name,
             },
+            snapshot_id,
         }
     }
 }
@@ -6,6 +6,7 @@ use async_std::prelude::*;
 use core::mem::size_of;
 use futures::join;
 use regex::Regex;
+use std::collections::HashMap;
 use std::convert::From;
 use std::future::Future;
 use std::time::{Duration, SystemTime};
@@ -13,6 +14,13 @@ use thiserror::Error;
 
 const EXPIRY_FILE_EXT: &str = ".expiry";
 
+pub const NETWORK_CACHE_DIR: &str = "spot/net";
+pub const IMAGE_CACHE_DIR: &str = "spot/img";
+
+lazy_static! {
+    static ref ANY_FILE: Regex = Regex::new(".*").unwrap();
+}
+
 #[derive(Error, Debug)]
 pub enum CacheError {
     #[error("No content available")]
@@ -77,6 +85,29 @@ impl CacheExpiry {
     }
 }
 
+// A cached resource, along with its expiry file
+#[derive(Debug)]
+struct CacheEntry {
+    resource: String,
+    size: u64,
+    last_access: SystemTime,
+}
+
+// Least recently used resources go first, until what's left fits in max_size
+fn select_evicted(mut entries: Vec<CacheEntry>, max_size: u64) -> Vec<String> {
+    let mut total: u64 = entries.iter().map(|e| e.size).sum();
+    entries.sort_by_key(|e| e.last_access);
+    entries
+        .into_iter()
+        .take_while(|e| {
+            let evict = total > max_size;
+            total = total.saturating_sub(e.size);
+            evict
+        })
+        .map(|e| e.resource)
+        .collect()
+}
+
 #[derive(Clone)]
 pub struct CacheManager {
     root: PathBuf,
@@ -193,9 +224,13 @@ impl CacheManager {
                 .unwrap_or(false);
             if matches {
                 info!("Removing {}...", entry.file_name().to_str().unwrap_or(""));
-                fs::remove_file(entry.path())
-                    .await
-                    .map_err(CacheError::RemoveError)?;
+                match fs::remove_file(entry.path()).await {
+                    // Expiry files are removed along with the file they belong to
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                        return Err(CacheError::RemoveError(e))
+                    }
+                    _ => {}
+                }
                 if let Some(expiry_file_path) = entry
                     .path()
                     .to_str()
@@ -209,6 +244,67 @@ impl CacheManager {
         Ok(())
     }
 
+    pub async fn clear_all(&self) -> Result<(), CacheError> {
+        self.clear_cache_pattern(&ANY_FILE).await
+    }
+
+    async fn entries(&self) -> Result<Vec<CacheEntry>, CacheError> {
+        let mut entries = fs::read_dir(&self.root)
+            .await
+            .map_err(CacheError::ReadError)?;
+
+        let mut by_resource: HashMap<String, CacheEntry> = HashMap::new();
+        while let Some(Ok(entry)) = entries.next().await {
+            let Ok(metadata) = entry.metadata().await else {
+                continue;
+            };
+            let Some(name) = entry.file_name().to_str().map(|s| s.to_string()) else {
+                continue;
+            };
+            // With relatime, the access time is only updated once a day, which is plenty for us
+            let last_access = metadata
+                .accessed()
+                .or_else(|_| metadata.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            let (resource, last_access) = match name.strip_suffix(EXPIRY_FILE_EXT) {
+                Some(resource) => (resource.to_string(), SystemTime::UNIX_EPOCH),
+                None => (name, last_access),
+            };
+            let cache_entry = by_resource
+                .entry(resource.clone())
+                .or_insert_with(|| CacheEntry {
+                    resource,
+                    size: 0,
+                    last_access,
+                });
+            cache_entry.size += metadata.len();
+            cache_entry.last_access = cache_entry.last_access.max(last_access);
+        }
+
+        Ok(by_resource.into_values().collect())
+    }
+
+    // Total size of the files in this cache, in bytes
+    pub async fn usage(&self) -> Result<u64, CacheError> {
+        Ok(self.entries().await?.iter().map(|e| e.size).sum())
+    }
+
+    // Evicts the least recently used files until the cache fits in max_size bytes, and returns how many were removed
+    pub async fn compact(&self, max_size: u64) -> Result<usize, CacheError> {
+        let evicted = select_evicted(self.entries().await?, max_size);
+        for resource in evicted.iter() {
+            debug!("Evicting {} from cache", resource);
+            let _ = fs::remove_file(self.cache_meta_path(resource)).await;
+            match fs::remove_file(self.cache_path(resource)).await {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    return Err(CacheError::RemoveError(e))
+                }
+                _ => {}
+            }
+        }
+        Ok(evicted.len())
+    }
+
     pub async fn set_expired_pattern(&self, regex: &Regex) -> Result<(), CacheError> {
         let mut entries = fs::read_dir(&self.root)
             .await
@@ -287,3 +383,25 @@ pub enum FetchResult {
     NotModified(CacheExpiry),
     Modified(Vec<u8>, CacheExpiry),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(resource: &str, size: u64, last_access_secs: u64) -> CacheEntry {
+        CacheEntry {
+            resource: resource.to_string(),
+            size,
+            last_access: SystemTime::UNIX_EPOCH + Duration::from_secs(last_access_secs),
+        }
+    }
+
+    #[test]
+    fn test_select_evicted() {
+        let entries = vec![entry("a", 10, 30), entry("b", 10, 10), entry("c", 10, 20)];
+        assert_eq!(select_evicted(entries, 15), vec!["b", "c"]);
+
+        let entries = vec![entry("a", 10, 30), entry("b", 10, 10)];
+        assert!(select_evicted(entries, 20).is_empty());
+    }
+}
@@ -1,13 +1,19 @@
+use futures::channel::oneshot;
 use futures::future::BoxFuture;
 use futures::{join, FutureExt};
 use regex::Regex;
 use serde::de::DeserializeOwned;
 use serde_json::from_slice;
+use std::collections::HashMap;
 use std::convert::Into;
 use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
 
-use super::cache::{CacheExpiry, CacheManager, CachePolicy, FetchResult};
+use super::cache::{CacheExpiry, CacheManager, CachePolicy, FetchResult, NETWORK_CACHE_DIR};
 use super::client::*;
+use super::outbox::{Outbox, PendingWrite};
 use crate::app::models::*;
 
 pub type SpotifyResult<T> = Result<T, SpotifyApiError>;
@@ -24,6 +30,8 @@ pub trait SpotifyApiClient {
         limit: usize,
     ) -> BoxFuture<SpotifyResult<SongBatch>>;
 
+    fn get_track(&self, id: &str) -> BoxFuture<SpotifyResult<SongDescription>>;
+
     fn get_playlist(&self, id: &str) -> BoxFuture<SpotifyResult<PlaylistDescription>>;
 
     fn get_playlist_tracks(
@@ -65,6 +73,15 @@ pub trait SpotifyApiClient {
 
     fn remove_from_playlist(&self, id: &str, uris: Vec<String>) -> BoxFuture<SpotifyResult<()>>;
 
+    // Moves the track at position `from` so that it ends up at position `to`, returns the new snapshot id
+    fn move_playlist_track(
+        &self,
+        id: &str,
+        from: usize,
+        to: usize,
+        snapshot_id: Option<String>,
+    ) -> BoxFuture<SpotifyResult<String>>;
+
     fn update_playlist_details(&self, id: &str, name: String) -> BoxFuture<SpotifyResult<()>>;
 
     fn search(
@@ -74,6 +91,34 @@ pub trait SpotifyApiClient {
         limit: usize,
     ) -> BoxFuture<SpotifyResult<SearchResults>>;
 
+    fn search_albums(
+        &self,
+        query: &str,
+        offset: usize,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<AlbumDescription>>>;
+
+    fn search_artists(
+        &self,
+        query: &str,
+        offset: usize,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<ArtistSummary>>>;
+
+    fn search_tracks(
+        &self,
+        query: &str,
+        offset: usize,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<SongBatch>>;
+
+    fn search_playlists(
+        &self,
+        query: &str,
+        offset: usize,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<PlaylistDescription>>>;
+
     fn get_artist_albums(
         &self,
         id: &str,
@@ -94,7 +139,33 @@ pub trait SpotifyApiClient {
 
     fn get_player_queue(&self) -> BoxFuture<SpotifyResult<Vec<SongDescription>>>;
 
-    fn update_token(&self, token: String);
+    // Songs similar to the seeds, Spotify accepts up to 5 seeds in total
+    fn get_recommendations(
+        &self,
+        seed_tracks: Vec<String>,
+        seed_artists: Vec<String>,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<SongDescription>>>;
+
+    fn update_token(&self, token: String, token_expiry_time: Option<SystemTime>);
+
+    // Called whenever a new token is needed, which should eventually lead to update_token being called
+    fn set_token_refresher(&self, refresher: Box<dyn Fn() + Send + Sync>);
+
+    // Called when a request fails because Spotify can't be reached, and the client goes offline by itself
+    fn set_offline_listener(&self, listener: Box<dyn Fn() + Send + Sync>);
+
+    fn is_offline(&self) -> bool;
+
+    fn set_offline(&self, offline: bool);
+
+    // Number of library changes that are waiting to be sent to Spotify
+    fn pending_writes(&self) -> usize;
+
+    // Sends the library changes that could not be sent earlier, returns how many went through
+    fn replay_pending_writes(&self) -> BoxFuture<SpotifyResult<usize>>;
+
+    fn clear_pending_writes(&self);
 
     fn player_pause(&self, device_id: String) -> BoxFuture<SpotifyResult<()>>;
 
@@ -115,6 +186,7 @@ pub trait SpotifyApiClient {
         device_id: String,
         context: String,
         offset: usize,
+        position_ms: u32,
     ) -> BoxFuture<SpotifyResult<()>>;
 
     fn player_play_no_context(
@@ -122,8 +194,11 @@ pub trait SpotifyApiClient {
         device_id: String,
         uris: Vec<String>,
         offset: usize,
+        position_ms: u32,
     ) -> BoxFuture<SpotifyResult<()>>;
 
+    fn player_transfer(&self, device_id: String, play: bool) -> BoxFuture<SpotifyResult<()>>;
+
     fn player_state(&self) -> BoxFuture<SpotifyResult<ConnectPlayerState>>;
 }
 
@@ -134,6 +209,7 @@ enum SpotCacheKey<'a> {
     Album(&'a str),
     AlbumLiked(&'a str),
     AlbumTracks(&'a str, usize, usize),
+    Track(&'a str),
     Playlist(&'a str),
     PlaylistTracks(&'a str, usize, usize),
     ArtistAlbums(&'a str, usize, usize),
@@ -141,6 +217,11 @@ enum SpotCacheKey<'a> {
     ArtistTopTracks(&'a str),
     User(&'a str),
     UserPlaylists(&'a str, usize, usize),
+    Search(&'a str, usize, usize),
+    SearchAlbums(&'a str, usize, usize),
+    SearchArtists(&'a str, usize, usize),
+    SearchTracks(&'a str, usize, usize),
+    SearchPlaylists(&'a str, usize, usize),
 }
 
 impl<'a> SpotCacheKey<'a> {
@@ -154,6 +235,7 @@ impl<'a> SpotCacheKey<'a> {
                 format!("album_item_{id}_{offset}_{limit}.json")
             }
             Self::AlbumLiked(id) => format!("album_liked_{id}.json"),
+            Self::Track(id) => format!("track_{id}.json"),
             Self::Playlist(id) => format!("playlist_{id}.json"),
             Self::PlaylistTracks(id, offset, limit) => {
                 format!("playlist_item_{id}_{offset}_{limit}.json")
@@ -167,10 +249,45 @@ impl<'a> SpotCacheKey<'a> {
             Self::UserPlaylists(id, offset, limit) => {
                 format!("user_playlists_{id}_{offset}_{limit}.json")
             }
+            Self::Search(query, offset, limit) => {
+                format!("search_{:x}_{offset}_{limit}.json", hash_query(query))
+            }
+            Self::SearchAlbums(query, offset, limit) => {
+                format!(
+                    "search_albums_{:x}_{offset}_{limit}.json",
+                    hash_query(query)
+                )
+            }
+            Self::SearchArtists(query, offset, limit) => {
+                format!(
+                    "search_artists_{:x}_{offset}_{limit}.json",
+                    hash_query(query)
+                )
+            }
+            Self::SearchTracks(query, offset, limit) => {
+                format!(
+                    "search_tracks_{:x}_{offset}_{limit}.json",
+                    hash_query(query)
+                )
+            }
+            Self::SearchPlaylists(query, offset, limit) => {
+                format!(
+                    "search_playlists_{:x}_{offset}_{limit}.json",
+                    hash_query(query)
+                )
+            }
         }
     }
 }
 
+// Search queries can contain anything, so they are hashed to get a usable file name.
+// The file names outlive the binary, hence FNV-1a rather than the std hasher, which may change.
+fn hash_query(query: &str) -> u64 {
+    query.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
 lazy_static! {
     pub static ref ME_TRACKS_CACHE: Regex = Regex::new(r"^me_tracks_\w+_\w+\.json$").unwrap();
     pub static ref ME_ALBUMS_CACHE: Regex = Regex::new(r"^me_albums_\w+_\w+\.json$").unwrap();
@@ -182,27 +299,97 @@ fn playlist_cache_key(id: &str) -> Regex {
     Regex::new(&format!(r"^playlist(_{id}|item_{id}_\w+_\w+)\.json$")).unwrap()
 }
 
+// Last move queued on a playlist, with a receiver for the snapshot it will return
+type QueuedMove = (usize, oneshot::Receiver<Option<String>>);
+
 pub struct CachedSpotifyClient {
     client: SpotifyClient,
     cache: CacheManager,
+    offline: AtomicBool,
+    outbox: Outbox,
+    replaying: AtomicBool,
+    playlist_moves: Mutex<HashMap<String, QueuedMove>>,
+    move_count: AtomicUsize,
+    offline_listener: Mutex<Option<Box<dyn Fn() + Send + Sync>>>,
 }
 
 impl CachedSpotifyClient {
     pub fn new() -> CachedSpotifyClient {
         CachedSpotifyClient {
             client: SpotifyClient::new(),
-            cache: CacheManager::for_dir("spot/net").unwrap(),
+            cache: CacheManager::for_dir(NETWORK_CACHE_DIR).unwrap(),
+            offline: AtomicBool::new(false),
+            outbox: Outbox::for_dir("spot"),
+            replaying: AtomicBool::new(false),
+            playlist_moves: Mutex::new(HashMap::new()),
+            move_count: AtomicUsize::new(0),
+            offline_listener: Mutex::new(None),
+        }
+    }
+
+    fn go_offline(&self) {
+        if !self.offline.swap(true, Ordering::SeqCst) {
+            if let Some(listener) = &*self.offline_listener.lock().unwrap() {
+                listener();
+            }
         }
     }
 
     fn default_cache_policy(&self) -> CachePolicy {
-        if self.client.has_token() {
+        if self.client.has_token() && !self.is_offline() {
             CachePolicy::Default
         } else {
             CachePolicy::IgnoreExpiry
         }
     }
 
+    async fn send_write(&self, write: &PendingWrite) -> SpotifyResult<()> {
+        match write.clone() {
+            PendingWrite::SaveAlbum(id) => self.client.save_album(&id).send_no_response().await,
+            PendingWrite::RemoveSavedAlbum(id) => {
+                self.client.remove_saved_album(&id).send_no_response().await
+            }
+            PendingWrite::SaveTracks(ids) => self.client.save_tracks(ids).send_no_response().await,
+            PendingWrite::RemoveSavedTracks(ids) => {
+                self.client
+                    .remove_saved_tracks(ids)
+                    .send_no_response()
+                    .await
+            }
+            PendingWrite::AddToPlaylist(id, uris) => {
+                self.client
+                    .add_to_playlist(&id, uris)
+                    .send_no_response()
+                    .await
+            }
+            PendingWrite::RemoveFromPlaylist(id, uris) => {
+                self.client
+                    .remove_from_playlist(&id, uris)
+                    .send_no_response()
+                    .await
+            }
+        }
+    }
+
+    // Writes are kept for later rather than failing when Spotify can't be reached
+    async fn write_or_queue(&self, write: PendingWrite) -> SpotifyResult<()> {
+        // Anything already waiting has to go first, so that changes are applied in order
+        if !self.is_offline() && self.outbox.is_empty() {
+            match self.send_write(&write).await {
+                Err(e) if e.is_retryable() => {
+                    warn!("Could not send change, will retry later: {}", e);
+                    if e.is_network_error() {
+                        self.go_offline();
+                    }
+                }
+                result => return result,
+            }
+        }
+        debug!("queuing {:?}", &write);
+        self.outbox.push(write);
+        Ok(())
+    }
+
     async fn wrap_write<T, O, F>(write: &F, etag: Option<String>) -> SpotifyResult<FetchResult>
     where
         O: Future<Output = SpotifyResult<SpotifyResponse<T>>>,
@@ -239,20 +426,42 @@ impl CachedSpotifyClient {
     {
         let write = &write;
         let cache_key = key.into_raw();
+        let offline = self.is_offline();
+        let cache_policy = if offline {
+            CachePolicy::IgnoreExpiry
+        } else {
+            cache_policy.unwrap_or_else(|| self.default_cache_policy())
+        };
         let raw = self
             .cache
-            .get_or_write(
-                &cache_key,
-                cache_policy.unwrap_or_else(|| self.default_cache_policy()),
-                |etag| Self::wrap_write(write, etag),
-            )
-            .await?;
+            .get_or_write(&cache_key, cache_policy, |etag| async move {
+                if offline {
+                    Err(SpotifyApiError::Offline)
+                } else {
+                    Self::wrap_write(write, etag).await
+                }
+            })
+            .await;
+
+        let raw = match raw {
+            // Spotify can't be reached: switch to offline mode, and serve whatever is in the cache
+            Err(e) if e.is_network_error() && !offline => {
+                warn!("Network unreachable, going offline: {}", e);
+                self.go_offline();
+                self.cache
+                    .get_or_write(&cache_key, CachePolicy::IgnoreExpiry, |_| async {
+                        Err(SpotifyApiError::Offline)
+                    })
+                    .await?
+            }
+            raw => raw?,
+        };
 
         let result = from_slice::<T>(&raw);
         match result {
             Ok(t) => Ok(t),
             // parsing failed: cache is likely invalid, request again, ignoring cache
-            Err(e) => {
+            Err(e) if !self.is_offline() => {
                 dbg!(&cache_key, e);
                 let new_raw = self
                     .cache
@@ -262,13 +471,87 @@ impl CachedSpotifyClient {
                     .await?;
                 Ok(from_slice::<T>(&new_raw)?)
             }
+            Err(e) => Err(e.into()),
         }
     }
+
+    async fn search_with_types(
+        &self,
+        key: SpotCacheKey<'_>,
+        query: &str,
+        types: &[SearchType],
+        offset: usize,
+        limit: usize,
+    ) -> SpotifyResult<SearchResults> {
+        let results = self
+            .cache_get_or_write(key, None, |etag| {
+                self.client
+                    .search(query.to_owned(), types.to_vec(), offset, limit)
+                    .etag(etag)
+                    .send()
+            })
+            .await?;
+        Ok(results.into())
+    }
 }
 
 impl SpotifyApiClient for CachedSpotifyClient {
-    fn update_token(&self, new_token: String) {
-        self.client.update_token(new_token)
+    fn update_token(&self, new_token: String, token_expiry_time: Option<SystemTime>) {
+        self.client.update_token(new_token, token_expiry_time)
+    }
+
+    fn set_token_refresher(&self, refresher: Box<dyn Fn() + Send + Sync>) {
+        self.client.set_token_refresher(refresher)
+    }
+
+    fn set_offline_listener(&self, listener: Box<dyn Fn() + Send + Sync>) {
+        *self.offline_listener.lock().unwrap() = Some(listener);
+    }
+
+    fn is_offline(&self) -> bool {
+        self.offline.load(Ordering::SeqCst)
+    }
+
+    fn set_offline(&self, offline: bool) {
+        self.offline.store(offline, Ordering::SeqCst);
+    }
+
+    fn pending_writes(&self) -> usize {
+        self.outbox.len()
+    }
+
+    fn replay_pending_writes(&self) -> BoxFuture<SpotifyResult<usize>> {
+        Box::pin(async move {
+            if self.replaying.swap(true, Ordering::SeqCst) {
+                return Ok(0);
+            }
+
+            let mut handled = vec![];
+            let mut result = Ok(0);
+            for write in self.outbox.take() {
+                match self.send_write(&write).await {
+                    Ok(_) => result = result.map(|sent| sent + 1),
+                    Err(e) if e.is_retryable() => {
+                        if e.is_network_error() {
+                            self.go_offline();
+                        }
+                        result = Err(e);
+                        break;
+                    }
+                    // Spotify won't ever accept it, no point in keeping it around
+                    Err(e) => warn!("Dropping {:?}: {}", write, e),
+                }
+                handled.push(write);
+            }
+
+            self.outbox.remove_sent(&handled);
+            self.replaying.store(false, Ordering::SeqCst);
+            result
+        })
+    }
+
+    fn clear_pending_writes(&self) {
+        self.outbox.clear();
     }
 
     fn get_saved_albums(
@@ -343,11 +626,8 @@ impl SpotifyApiClient for CachedSpotifyClient {
                 .await
                 .unwrap_or(());
 
-            self.client
-                .add_to_playlist(&id, uris)
-                .send_no_response()
-                .await?;
-            Ok(())
+            self.write_or_queue(PendingWrite::AddToPlaylist(id, uris))
+                .await
         })
     }
 
@@ -381,11 +661,60 @@ impl SpotifyApiClient for CachedSpotifyClient {
                 .await
                 .unwrap_or(());
 
-            self.client
-                .remove_from_playlist(&id, uris)
-                .send_no_response()
-                .await?;
-            Ok(())
+            self.write_or_queue(PendingWrite::RemoveFromPlaylist(id, uris))
+                .await
+        })
+    }
+
+    fn move_playlist_track(
+        &self,
+        id: &str,
+        from: usize,
+        to: usize,
+        snapshot_id: Option<String>,
+    ) -> BoxFuture<SpotifyResult<String>> {
+        let id = id.to_owned();
+        // Spotify inserts before the given position, which is counted before the track is taken out
+        let insert_before = if to > from { to + 1 } else { to };
+
+        // Moves on the same playlist are chained: the positions of this one already account for
+        // the ones queued before it, so it must wait for them and use the snapshot they return
+        let ticket = self.move_count.fetch_add(1, Ordering::SeqCst);
+        let (done, next) = oneshot::channel();
+        let previous = self
+            .playlist_moves
+            .lock()
+            .unwrap()
+            .insert(id.clone(), (ticket, next));
+
+        Box::pin(async move {
+            let snapshot_id = match previous {
+                Some((_, previous)) => previous.await.ok().flatten().or(snapshot_id),
+                None => snapshot_id,
+            };
+
+            self.cache
+                .set_expired_pattern(&playlist_cache_key(&id))
+                .await
+                .unwrap_or(());
+
+            let result = self
+                .client
+                .reorder_playlist_tracks(&id, from, insert_before, snapshot_id)
+                .send()
+                .await
+                .and_then(|r| r.deserialize().ok_or(SpotifyApiError::NoContent))
+                .map(|snapshot| snapshot.snapshot_id);
+
+            {
+                let mut moves = self.playlist_moves.lock().unwrap();
+                if matches!(moves.get(&id), Some((last, _)) if *last == ticket) {
+                    moves.remove(&id);
+                }
+            }
+            done.send(result.as_ref().ok().cloned()).unwrap_or(());
+
+            result
         })
     }
 
@@ -439,7 +768,8 @@ impl SpotifyApiClient for CachedSpotifyClient {
 
         Box::pin(async move {
             let _ = self.cache.set_expired_pattern(&ME_ALBUMS_CACHE).await;
-            self.client.save_album(&id).send_no_response().await?;
+            self.write_or_queue(PendingWrite::SaveAlbum(id.clone()))
+                .await?;
             self.get_album(&id[..]).await.map(|a| a.description)
         })
     }
@@ -447,8 +777,7 @@ impl SpotifyApiClient for CachedSpotifyClient {
     fn save_tracks(&self, ids: Vec<String>) -> BoxFuture<SpotifyResult<()>> {
         Box::pin(async move {
             let _ = self.cache.set_expired_pattern(&ME_TRACKS_CACHE).await;
-            self.client.save_tracks(ids).send_no_response().await?;
-            Ok(())
+            self.write_or_queue(PendingWrite::SaveTracks(ids)).await
         })
     }
 
@@ -457,16 +786,15 @@ impl SpotifyApiClient for CachedSpotifyClient {
 
         Box::pin(async move {
             let _ = self.cache.set_expired_pattern(&ME_ALBUMS_CACHE).await;
-            self.client.remove_saved_album(&id).send_no_response().await
+            self.write_or_queue(PendingWrite::RemoveSavedAlbum(id))
+                .await
         })
     }
 
     fn remove_saved_tracks(&self, ids: Vec<String>) -> BoxFuture<SpotifyResult<()>> {
         Box::pin(async move {
             let _ = self.cache.set_expired_pattern(&ME_TRACKS_CACHE).await;
-            self.client
-                .remove_saved_tracks(ids)
-                .send_no_response()
+            self.write_or_queue(PendingWrite::RemoveSavedTracks(ids))
                 .await
         })
     }
@@ -502,6 +830,20 @@ impl SpotifyApiClient for CachedSpotifyClient {
         })
     }
 
+    fn get_track(&self, id: &str) -> BoxFuture<SpotifyResult<SongDescription>> {
+        let id = id.to_owned();
+
+        Box::pin(async move {
+            let track = self
+                .cache_get_or_write(SpotCacheKey::Track(&id), None, |etag| {
+                    self.client.get_track(&id).etag(etag).send()
+                })
+                .await?;
+
+            Ok(track.into())
+        })
+    }
+
     fn get_playlist(&self, id: &str) -> BoxFuture<SpotifyResult<PlaylistDescription>> {
         let id = id.to_owned();
 
@@ -609,30 +951,109 @@ impl SpotifyApiClient for CachedSpotifyClient {
     ) -> BoxFuture<SpotifyResult<SearchResults>> {
         let query = query.to_owned();
 
+        Box::pin(async move {
+            let types = [
+                SearchType::Album,
+                SearchType::Artist,
+                SearchType::Track,
+                SearchType::Playlist,
+            ];
+            self.search_with_types(
+                SpotCacheKey::Search(&query, offset, limit),
+                &query,
+                &types,
+                offset,
+                limit,
+            )
+            .await
+        })
+    }
+
+    fn search_albums(
+        &self,
+        query: &str,
+        offset: usize,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<AlbumDescription>>> {
+        let query = query.to_owned();
+
         Box::pin(async move {
             let results = self
-                .client
-                .search(query, offset, limit)
-                .send()
-                .await?
-                .deserialize()
-                .ok_or(SpotifyApiError::NoContent)?;
+                .search_with_types(
+                    SpotCacheKey::SearchAlbums(&query, offset, limit),
+                    &query,
+                    &[SearchType::Album],
+                    offset,
+                    limit,
+                )
+                .await?;
+            Ok(results.albums)
+        })
+    }
 
-            let albums = results
-                .albums
-                .unwrap_or_default()
-                .into_iter()
-                .map(|saved| saved.into())
-                .collect::<Vec<AlbumDescription>>();
+    fn search_artists(
+        &self,
+        query: &str,
+        offset: usize,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<ArtistSummary>>> {
+        let query = query.to_owned();
 
-            let artists = results
-                .artists
-                .unwrap_or_default()
-                .into_iter()
-                .map(|saved| saved.into())
-                .collect::<Vec<ArtistSummary>>();
+        Box::pin(async move {
+            let results = self
+                .search_with_types(
+                    SpotCacheKey::SearchArtists(&query, offset, limit),
+                    &query,
+                    &[SearchType::Artist],
+                    offset,
+                    limit,
+                )
+                .await?;
+            Ok(results.artists)
+        })
+    }
+
+    fn search_tracks(
+        &self,
+        query: &str,
+        offset: usize,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<SongBatch>> {
+        let query = query.to_owned();
+
+        Box::pin(async move {
+            let results = self
+                .search_with_types(
+                    SpotCacheKey::SearchTracks(&query, offset, limit),
+                    &query,
+                    &[SearchType::Track],
+                    offset,
+                    limit,
+                )
+                .await?;
+            Ok(results.tracks)
+        })
+    }
+
+    fn search_playlists(
+        &self,
+        query: &str,
+        offset: usize,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<PlaylistDescription>>> {
+        let query = query.to_owned();
 
-            Ok(SearchResults { albums, artists })
+        Box::pin(async move {
+            let results = self
+                .search_with_types(
+                    SpotCacheKey::SearchPlaylists(&query, offset, limit),
+                    &query,
+                    &[SearchType::Playlist],
+                    offset,
+                    limit,
+                )
+                .await?;
+            Ok(results.playlists)
         })
     }
 
@@ -723,6 +1144,24 @@ impl SpotifyApiClient for CachedSpotifyClient {
         })
     }
 
+    fn get_recommendations(
+        &self,
+        seed_tracks: Vec<String>,
+        seed_artists: Vec<String>,
+        limit: usize,
+    ) -> BoxFuture<SpotifyResult<Vec<SongDescription>>> {
+        Box::pin(async move {
+            let recommendations = self
+                .client
+                .get_recommendations(&seed_tracks, &seed_artists, limit)
+                .send()
+                .await?
+                .deserialize()
+                .ok_or(SpotifyApiError::NoContent)?;
+            Ok(recommendations.into())
+        })
+    }
+
     fn player_pause(&self, device_id: String) -> BoxFuture<SpotifyResult<()>> {
         Box::pin(self.client.player_pause(&device_id).send_no_response())
     }
@@ -736,6 +1175,7 @@ impl SpotifyApiClient for CachedSpotifyClient {
         device_id: String,
         context_uri: String,
         offset: usize,
+        position_ms: u32,
     ) -> BoxFuture<SpotifyResult<()>> {
         Box::pin(
             self.client
@@ -746,6 +1186,7 @@ impl SpotifyApiClient for CachedSpotifyClient {
                         offset: PlayOffset {
                             position: offset as u32,
                         },
+                        position_ms,
                     },
                 )
                 .send_no_response(),
@@ -757,6 +1198,7 @@ impl SpotifyApiClient for CachedSpotifyClient {
         device_id: String,
         uris: Vec<String>,
         offset: usize,
+        position_ms: u32,
     ) -> BoxFuture<SpotifyResult<()>> {
         Box::pin(
             self.client
@@ -767,12 +1209,21 @@ impl SpotifyApiClient for CachedSpotifyClient {
                         offset: PlayOffset {
                             position: offset as u32,
                         },
+                        position_ms,
                     },
                 )
                 .send_no_response(),
         )
     }
 
+    fn player_transfer(&self, device_id: String, play: bool) -> BoxFuture<SpotifyResult<()>> {
+        Box::pin(
+            self.client
+                .player_transfer(&device_id, play)
+                .send_no_response(),
+        )
+    }
+
     fn player_next(&self, device_id: String) -> BoxFuture<SpotifyResult<()>> {
         Box::pin(self.client.player_next(&device_id).send_no_response())
     }
@@ -831,6 +1282,13 @@ pub mod tests {
 
     use crate::api::api_models::*;
 
+    #[test]
+    fn test_hash_query_is_stable() {
+        assert_eq!(super::hash_query(""), 0xcbf29ce484222325);
+        assert_eq!(super::hash_query("a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(super::hash_query("foobar"), 0x85944171f73967e8);
+    }
+
     #[test]
     fn test_search_query() {
         let query = SearchQuery {
@@ -1,14 +1,19 @@
+use async_std::channel::{bounded, Receiver, Sender};
 use form_urlencoded::Serializer;
+use futures::channel::oneshot;
 use isahc::config::Configurable;
-use isahc::http::{method::Method, request::Builder, StatusCode, Uri};
-use isahc::{AsyncReadResponseExt, HttpClient, Request};
+use isahc::http::{header::AUTHORIZATION, method::Method, request::Builder, StatusCode, Uri};
+use isahc::{AsyncBody, AsyncReadResponseExt, HttpClient, Request, Response};
 use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use rand::Rng;
 use serde::{de::Deserialize, Serialize};
 use serde_json::from_str;
 use std::convert::Into;
 use std::marker::PhantomData;
 use std::str::FromStr;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 use thiserror::Error;
 
 pub use super::api_models::*;
@@ -16,6 +21,18 @@ use super::cache::CacheError;
 
 const SPOTIFY_HOST: &str = "api.spotify.com";
 
+// Enough to keep the UI responsive, few enough for batch prefetching not to hit the rate limit
+const MAX_CONCURRENT_REQUESTS: usize = 4;
+const MAX_ATTEMPTS: u32 = 4;
+const BASE_RETRY_DELAY_MS: u64 = 500;
+const MAX_RETRY_DELAY_MS: u64 = 8000;
+// Past that, waiting would look like the app is stuck, so we'd rather report the failure
+const MAX_RETRY_AFTER_SECS: u64 = 30;
+
+// Tokens get refreshed that long before they expire
+const TOKEN_REFRESH_MARGIN_SECS: u64 = 60;
+const TOKEN_REFRESH_TIMEOUT_SECS: u64 = 20;
+
 // https://url.spec.whatwg.org/#path-percent-encode-set
 const PATH_ENCODE_SET: &AsciiSet = &CONTROLS
     .add(b' ')
@@ -36,18 +53,27 @@ pub(crate) struct SpotifyRequest<'a, Body, Response> {
     client: &'a SpotifyClient,
     request: Builder,
     body: Body,
+    // Whether sending it again after a server error is harmless
+    idempotent: bool,
     _type: PhantomData<Response>,
 }
 
 impl<'a, B, R> SpotifyRequest<'a, B, R>
 where
-    B: Into<isahc::AsyncBody>,
+    B: Into<AsyncBody> + Clone,
 {
     fn method(mut self, method: Method) -> Self {
+        self.idempotent = matches!(method, Method::GET | Method::PUT | Method::DELETE);
         self.request = self.request.method(method);
         self
     }
 
+    // For requests whose effect depends on the current state, despite their method
+    fn not_idempotent(mut self) -> Self {
+        self.idempotent = false;
+        self
+    }
+
     fn uri(mut self, path: String, query: Option<&str>) -> Self {
         let path_and_query = match query {
             None => path,
@@ -86,6 +112,7 @@ where
         let Self {
             client,
             request,
+            idempotent,
             _type,
             ..
         } = self;
@@ -93,6 +120,7 @@ where
             client,
             request: request.header("Content-Type", "application/json"),
             body: serde_json::to_vec(&body).unwrap(),
+            idempotent,
             _type,
         }
     }
@@ -102,9 +130,12 @@ where
             client,
             request,
             body,
+            idempotent,
             ..
         } = self.authenticated()?;
-        client.send_req(request.body(body).unwrap()).await
+        client
+            .send_req(request.body(body).unwrap(), idempotent)
+            .await
     }
 
     pub(crate) async fn send_no_response(self) -> Result<(), SpotifyApiError> {
@@ -112,10 +143,11 @@ where
             client,
             request,
             body,
+            idempotent,
             ..
         } = self.authenticated()?;
         client
-            .send_req_no_response(request.body(body).unwrap())
+            .send_req_no_response(request.body(body).unwrap(), idempotent)
             .await
     }
 }
@@ -156,6 +188,8 @@ pub enum SpotifyApiError {
     NoContent,
     #[error("Request rate exceeded")]
     TooManyRequests,
+    #[error("Not available while offline")]
+    Offline,
     #[error("Request failed ({0}): {1}")]
     BadStatus(u16, String),
     #[error(transparent)]
@@ -170,9 +204,147 @@ pub enum SpotifyApiError {
     ConversionError(#[from] std::string::FromUtf8Error),
 }
 
+impl SpotifyApiError {
+    // True when Spotify could not be reached at all, rather than answering with an error
+    pub fn is_network_error(&self) -> bool {
+        match self {
+            Self::Offline => true,
+            Self::ClientError(e) => e.is_network() || e.is_timeout(),
+            _ => false,
+        }
+    }
+
+    // Failures that might go away by trying again later, including once we're logged in again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::ClientError(_)
+            | Self::TooManyRequests
+            | Self::Offline
+            | Self::NoToken
+            | Self::InvalidToken => true,
+            Self::BadStatus(status, _) => *status >= 500,
+            _ => false,
+        }
+    }
+}
+
+// Limits how many requests can be in flight at once
+struct RequestLimiter {
+    permits: Receiver<()>,
+    release: Sender<()>,
+}
+
+struct RequestPermit<'a>(&'a Sender<()>);
+
+impl RequestLimiter {
+    fn new(max: usize) -> Self {
+        let (release, permits) = bounded(max);
+        for _ in 0..max {
+            let _ = release.try_send(());
+        }
+        Self { permits, release }
+    }
+
+    async fn acquire(&self) -> RequestPermit<'_> {
+        // The channel can't be closed while we hold the sender
+        let _ = self.permits.recv().await;
+        RequestPermit(&self.release)
+    }
+}
+
+impl Drop for RequestPermit<'_> {
+    fn drop(&mut self) {
+        let _ = self.0.try_send(());
+    }
+}
+
+pub(crate) type TokenRefresher = Box<dyn Fn() + Send + Sync>;
+
+// New tokens come from the player's session: we can only ask for one, then wait for it to be handed to us
+#[derive(Default)]
+struct TokenRefresh {
+    refresher: Mutex<Option<TokenRefresher>>,
+    waiters: Mutex<Vec<oneshot::Sender<()>>>,
+    requested_at: Mutex<Option<Instant>>,
+    // Bumped with every new token, so that timers scheduled for an older token do nothing
+    generation: AtomicUsize,
+}
+
+impl TokenRefresh {
+    async fn wait_for_token(&self) -> bool {
+        let (sender, receiver) = oneshot::channel();
+        let requested = {
+            let mut waiters = self.waiters.lock().unwrap();
+            // Requests that gave up waiting don't count as an ongoing refresh
+            waiters.retain(|waiter| !waiter.is_canceled());
+            waiters.push(sender);
+            waiters.len() > 1
+        };
+        if !requested && !self.request_refresh() {
+            return false;
+        }
+        let timeout = Duration::from_secs(TOKEN_REFRESH_TIMEOUT_SECS);
+        matches!(
+            async_std::future::timeout(timeout, receiver).await,
+            Ok(Ok(()))
+        )
+    }
+
+    // Asks for a new token without waiting for it, unless one was asked for recently
+    fn request_refresh(&self) -> bool {
+        let timeout = Duration::from_secs(TOKEN_REFRESH_TIMEOUT_SECS);
+        let mut requested_at = self.requested_at.lock().unwrap();
+        let pending = requested_at
+            .map(|requested_at| requested_at.elapsed() < timeout)
+            .unwrap_or(false);
+        match &*self.refresher.lock().unwrap() {
+            Some(_) if pending => true,
+            Some(refresher) => {
+                *requested_at = Some(Instant::now());
+                refresher();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn schedule(self: &Arc<Self>, expiry_time: SystemTime) {
+        let generation = self.generation.load(Ordering::SeqCst);
+        let delay = Self::refresh_delay(expiry_time, SystemTime::now());
+        let this = Arc::downgrade(self);
+        async_std::task::spawn(async move {
+            async_std::task::sleep(delay).await;
+            if let Some(this) = this.upgrade() {
+                if this.generation.load(Ordering::SeqCst) == generation {
+                    debug!("Token about to expire, refreshing");
+                    this.request_refresh();
+                }
+            }
+        });
+    }
+
+    fn refresh_delay(expiry_time: SystemTime, now: SystemTime) -> Duration {
+        let margin = Duration::from_secs(TOKEN_REFRESH_MARGIN_SECS);
+        expiry_time
+            .duration_since(now + margin)
+            .unwrap_or(Duration::ZERO)
+    }
+
+    fn token_updated(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        *self.requested_at.lock().unwrap() = None;
+        for waiter in self.waiters.lock().unwrap().drain(..) {
+            let _ = waiter.send(());
+        }
+    }
+}
+
 pub(crate) struct SpotifyClient {
     token: Mutex<Option<String>>,
+    token_expiry_time: Mutex<Option<SystemTime>>,
+    token_refresh: Arc<TokenRefresh>,
     client: HttpClient,
+    limiter: RequestLimiter,
 }
 
 impl SpotifyClient {
@@ -184,7 +356,10 @@ impl SpotifyClient {
         let client = builder.build().unwrap();
         Self {
             token: Mutex::new(None),
+            token_expiry_time: Mutex::new(None),
+            token_refresh: Default::default(),
             client,
+            limiter: RequestLimiter::new(MAX_CONCURRENT_REQUESTS),
         }
     }
 
@@ -193,6 +368,7 @@ impl SpotifyClient {
             client: self,
             request: Builder::new(),
             body: (),
+            idempotent: true,
             _type: PhantomData,
         }
     }
@@ -201,10 +377,43 @@ impl SpotifyClient {
         self.token.lock().unwrap().is_some()
     }
 
-    pub(crate) fn update_token(&self, new_token: String) {
+    pub(crate) fn update_token(&self, new_token: String, expiry_time: Option<SystemTime>) {
         if let Ok(mut token) = self.token.lock() {
             *token = Some(new_token)
         }
+        if let Ok(mut token_expiry_time) = self.token_expiry_time.lock() {
+            *token_expiry_time = expiry_time
+        }
+        self.token_refresh.token_updated();
+        if let Some(expiry_time) = expiry_time {
+            self.token_refresh.schedule(expiry_time);
+        }
+    }
+
+    pub(crate) fn set_token_refresher(&self, refresher: TokenRefresher) {
+        if let Ok(mut current) = self.token_refresh.refresher.lock() {
+            *current = Some(refresher)
+        }
+    }
+
+    fn token_expires_soon(&self) -> bool {
+        let margin = Duration::from_secs(TOKEN_REFRESH_MARGIN_SECS);
+        self.token_expiry_time
+            .lock()
+            .unwrap()
+            .map(|expiry_time| SystemTime::now() + margin >= expiry_time)
+            .unwrap_or(false)
+    }
+
+    // The request might have been built with a token that has been refreshed since
+    fn authorize<B>(&self, request: &mut Request<B>) {
+        let token = self.token.lock().unwrap();
+        let header = token
+            .as_ref()
+            .and_then(|token| format!("Bearer {token}").parse().ok());
+        if let Some(header) = header {
+            request.headers_mut().insert(AUTHORIZATION, header);
+        }
     }
 
     fn clear_token(&self) {
@@ -221,14 +430,106 @@ impl SpotifyClient {
             .and_then(|s| u64::from_str(s).ok())
     }
 
+    fn parse_retry_after(retry_after: &str) -> Option<Duration> {
+        u64::from_str(retry_after.trim())
+            .ok()
+            .map(Duration::from_secs)
+    }
+
+    // Exponential backoff, with some jitter so that concurrent requests don't all retry at once
+    fn retry_delay(attempt: u32) -> Duration {
+        let delay = BASE_RETRY_DELAY_MS
+            .saturating_mul(1 << attempt.min(16))
+            .min(MAX_RETRY_DELAY_MS);
+        let jitter = rand::thread_rng().gen_range(0..=delay / 2);
+        Duration::from_millis(delay / 2 + jitter)
+    }
+
+    // A request that failed with a server error might still have gone through, sending it again
+    // could add the same tracks twice. Being rate limited means it was rejected, though.
+    fn should_retry(idempotent: bool, status: StatusCode) -> bool {
+        status == StatusCode::TOO_MANY_REQUESTS || (status.is_server_error() && idempotent)
+    }
+
+    fn clone_request<B: Clone>(request: &Request<B>) -> Request<B> {
+        let mut builder = Request::builder()
+            .method(request.method().clone())
+            .uri(request.uri().clone())
+            .version(request.version());
+        if let Some(headers) = builder.headers_mut() {
+            headers.extend(request.headers().clone());
+        }
+        builder.body(request.body().clone()).unwrap()
+    }
+
+    // Sends the request, trying again when rate limited, when Spotify has a hiccup, or once the token has been refreshed
+    async fn send_with_retries<B>(
+        &self,
+        request: Request<B>,
+        idempotent: bool,
+    ) -> Result<Response<AsyncBody>, SpotifyApiError>
+    where
+        B: Into<AsyncBody> + Clone,
+    {
+        if self.token_expires_soon() {
+            // The current token is still good for a little while, no need to hold the request back
+            self.token_refresh.request_refresh();
+        }
+
+        let mut attempt = 0;
+        let mut token_refreshed = false;
+        loop {
+            let response = {
+                let mut request = Self::clone_request(&request);
+                self.authorize(&mut request);
+                let _permit = self.limiter.acquire().await;
+                self.client.send_async(request).await?
+            };
+
+            let status = response.status();
+            if status == StatusCode::UNAUTHORIZED && !token_refreshed {
+                token_refreshed = true;
+                debug!("Token rejected, refreshing");
+                if self.token_refresh.wait_for_token().await {
+                    continue;
+                }
+                return Ok(response);
+            }
+
+            attempt += 1;
+            if !Self::should_retry(idempotent, status) || attempt >= MAX_ATTEMPTS {
+                return Ok(response);
+            }
+
+            let delay = response
+                .headers()
+                .get("retry-after")
+                .and_then(|header| header.to_str().ok())
+                .and_then(Self::parse_retry_after)
+                .unwrap_or_else(|| Self::retry_delay(attempt));
+            if delay.as_secs() > MAX_RETRY_AFTER_SECS {
+                return Ok(response);
+            }
+
+            warn!(
+                "Request to {} failed ({}), retrying in {:?}",
+                request.uri(),
+                status,
+                delay
+            );
+            async_std::task::sleep(delay).await;
+        }
+    }
+
     async fn send_req<B, T>(
         &self,
         request: Request<B>,
+        idempotent: bool,
     ) -> Result<SpotifyResponse<T>, SpotifyApiError>
     where
-        B: Into<isahc::AsyncBody>,
+        B: Into<AsyncBody> + Clone,
     {
-        let mut result = self.client.send_async(request).await?;
+        let mut result = self.send_with_retries(request, idempotent).await?;
 
         let etag = result
             .headers()
@@ -269,11 +570,15 @@ impl SpotifyClient {
         }
     }
 
-    async fn send_req_no_response<B>(&self, request: Request<B>) -> Result<(), SpotifyApiError>
+    async fn send_req_no_response<B>(
+        &self,
+        request: Request<B>,
+        idempotent: bool,
+    ) -> Result<(), SpotifyApiError>
     where
-        B: Into<isahc::AsyncBody>,
+        B: Into<AsyncBody> + Clone,
     {
-        let mut result = self.client.send_async(request).await?;
+        let mut result = self.send_with_retries(request, idempotent).await?;
         match result.status() {
             StatusCode::UNAUTHORIZED => {
                 self.clear_token();
@@ -328,6 +633,24 @@ impl SpotifyClient {
             .uri(format!("/v1/artists/{id}/top-tracks"), Some(&query))
     }
 
+    pub(crate) fn get_recommendations(
+        &self,
+        seed_tracks: &[String],
+        seed_artists: &[String],
+        limit: usize,
+    ) -> SpotifyRequest<'_, (), Recommendations> {
+        let query = make_query_params()
+            .append_pair("seed_tracks", &seed_tracks.join(","))
+            .append_pair("seed_artists", &seed_artists.join(","))
+            .append_pair("market", "from_token")
+            .append_pair("limit", &limit.to_string()[..])
+            .finish();
+
+        self.request()
+            .method(Method::GET)
+            .uri("/v1/recommendations".to_string(), Some(&query))
+    }
+
     pub(crate) fn is_album_saved(&self, id: &str) -> SpotifyRequest<'_, (), Vec<bool>> {
         let query = make_query_params().append_pair("ids", id).finish();
         self.request()
@@ -385,6 +708,12 @@ impl SpotifyClient {
             .uri(format!("/v1/albums/{id}/tracks"), Some(&query))
     }
 
+    pub(crate) fn get_track(&self, id: &str) -> SpotifyRequest<'_, (), TrackItem> {
+        self.request()
+            .method(Method::GET)
+            .uri(format!("/v1/tracks/{id}"), None)
+    }
+
     pub(crate) fn get_playlist(&self, id: &str) -> SpotifyRequest<'_, (), Playlist> {
         let query = make_query_params()
             .append_pair(
@@ -446,6 +775,26 @@ impl SpotifyClient {
             .json_body(Uris { uris })
     }
 
+    pub(crate) fn reorder_playlist_tracks(
+        &self,
+        playlist: &str,
+        range_start: usize,
+        insert_before: usize,
+        snapshot_id: Option<String>,
+    ) -> SpotifyRequest<'_, Vec<u8>, PlaylistSnapshot> {
+        // Moving a range again would move other tracks, it's only safe to send it again when it was rejected
+        self.request()
+            .method(Method::PUT)
+            .not_idempotent()
+            .uri(format!("/v1/playlists/{playlist}/tracks"), None)
+            .json_body(ReorderTracks {
+                range_start,
+                insert_before,
+                range_length: 1,
+                snapshot_id,
+            })
+    }
+
     pub(crate) fn update_playlist_details(
         &self,
         playlist: &str,
@@ -505,12 +854,13 @@ impl SpotifyClient {
     pub(crate) fn search(
         &self,
         query: String,
+        types: Vec<SearchType>,
         offset: usize,
         limit: usize,
     ) -> SpotifyRequest<'_, (), RawSearchResults> {
         let query = SearchQuery {
             query,
-            types: vec![SearchType::Album, SearchType::Artist],
+            types,
             limit,
             offset,
         };
@@ -585,6 +935,20 @@ impl SpotifyClient {
             .json_body(request)
     }
 
+    pub(crate) fn player_transfer(
+        &self,
+        device_id: &str,
+        play: bool,
+    ) -> SpotifyRequest<'_, Vec<u8>, ()> {
+        self.request()
+            .method(Method::PUT)
+            .uri("/v1/me/player".to_string(), None)
+            .json_body(TransferRequest {
+                device_ids: vec![device_id.to_string()],
+                play,
+            })
+    }
+
     pub(crate) fn player_pause(&self, device_id: &str) -> SpotifyRequest<'_, (), ()> {
         let query = make_query_params()
             .append_pair("device_id", device_id)
@@ -657,6 +1021,85 @@ pub mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(
+            SpotifyClient::parse_retry_after(" 3"),
+            Some(Duration::from_secs(3))
+        );
+        assert_eq!(
+            SpotifyClient::parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        for attempt in 1..10 {
+            let delay = SpotifyClient::retry_delay(attempt);
+            assert!(delay <= Duration::from_millis(MAX_RETRY_DELAY_MS));
+            assert!(delay >= Duration::from_millis(BASE_RETRY_DELAY_MS));
+        }
+    }
+
+    #[test]
+    fn test_should_retry() {
+        let unavailable = StatusCode::SERVICE_UNAVAILABLE;
+        assert!(SpotifyClient::should_retry(true, unavailable));
+        assert!(!SpotifyClient::should_retry(false, unavailable));
+        assert!(SpotifyClient::should_retry(
+            false,
+            StatusCode::TOO_MANY_REQUESTS
+        ));
+        assert!(!SpotifyClient::should_retry(true, StatusCode::NOT_FOUND));
+    }
+
+    #[test]
+    fn test_reorder_is_not_idempotent() {
+        let client = SpotifyClient::new();
+        assert!(client.request::<()>().method(Method::PUT).idempotent);
+        assert!(!client.request::<()>().method(Method::POST).idempotent);
+        assert!(
+            !client
+                .reorder_playlist_tracks("playlist", 0, 2, None)
+                .idempotent
+        );
+    }
+
+    #[test]
+    fn test_token_expires_soon() {
+        let client = SpotifyClient::new();
+        let token = "token".to_string();
+
+        client.update_token(token.clone(), None);
+        assert!(!client.token_expires_soon());
+
+        let expiry_time = SystemTime::now() + Duration::from_secs(3600);
+        client.update_token(token.clone(), Some(expiry_time));
+        assert!(!client.token_expires_soon());
+
+        let expiry_time = SystemTime::now() + Duration::from_secs(30);
+        client.update_token(token, Some(expiry_time));
+        assert!(client.token_expires_soon());
+    }
+
+    #[test]
+    fn test_refresh_delay() {
+        let now = SystemTime::now();
+
+        let expiry_time = now + Duration::from_secs(3600);
+        assert_eq!(
+            TokenRefresh::refresh_delay(expiry_time, now),
+            Duration::from_secs(3600 - TOKEN_REFRESH_MARGIN_SECS)
+        );
+
+        let expiry_time = now + Duration::from_secs(30);
+        assert_eq!(
+            TokenRefresh::refresh_delay(expiry_time, now),
+            Duration::ZERO
+        );
+    }
+
     #[test]
     fn test_username_encoding() {
         let username = "anna.lafuente❤";
@@ -687,6 +1130,21 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_search_query_tracks_and_playlists() {
+        let query = SearchQuery {
+            query: "test".to_string(),
+            types: vec![SearchType::Track, SearchType::Playlist],
+            limit: 10,
+            offset: 20,
+        };
+
+        assert_eq!(
+            query.into_query_string(),
+            "type=track,playlist&q=test&offset=20&limit=10&market=from_token"
+        );
+    }
+
     #[test]
     fn test_search_query_spaces_and_stuff() {
         let query = SearchQuery {
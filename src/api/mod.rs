@@ -1,6 +1,7 @@
 mod api_models;
 mod cached_client;
 mod client;
+mod outbox;
 
 pub mod cache;
 
@@ -8,7 +9,7 @@ pub use cached_client::{CachedSpotifyClient, SpotifyApiClient, SpotifyResult};
 pub use client::SpotifyApiError;
 
 pub async fn clear_user_cache() -> Option<()> {
-    cache::CacheManager::for_dir("spot/net")?
+    cache::CacheManager::for_dir(cache::NETWORK_CACHE_DIR)?
         .clear_cache_pattern(&cached_client::USER_CACHE)
         .await
         .ok()
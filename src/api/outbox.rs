@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const OUTBOX_FILE: &str = "outbox.json";
+
+// A library change that hasn't reached Spotify yet
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PendingWrite {
+    SaveAlbum(String),
+    RemoveSavedAlbum(String),
+    SaveTracks(Vec<String>),
+    RemoveSavedTracks(Vec<String>),
+    AddToPlaylist(String, Vec<String>),
+    RemoveFromPlaylist(String, Vec<String>),
+}
+
+// Library changes waiting to be sent, in order, kept on disk so that they survive a restart
+pub struct Outbox {
+    path: Option<PathBuf>,
+    writes: Mutex<Vec<PendingWrite>>,
+}
+
+impl Outbox {
+    pub fn for_dir(dir: &str) -> Self {
+        let path = glib::user_cache_dir().join(dir).join(OUTBOX_FILE);
+        let writes = fs::read(&path)
+            .ok()
+            .and_then(|content| {
+                serde_json::from_slice(&content)
+                    .map_err(|e| warn!("Could not read pending changes: {}", e))
+                    .ok()
+            })
+            .unwrap_or_default();
+        Self {
+            path: Some(path),
+            writes: Mutex::new(writes),
+        }
+    }
+
+    #[cfg(test)]
+    fn in_memory() -> Self {
+        Self {
+            path: None,
+            writes: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.writes.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn push(&self, write: PendingWrite) {
+        let mut writes = self.writes.lock().unwrap();
+        writes.push(write);
+        self.save(&writes);
+    }
+
+    // Writes stay in the outbox until remove_sent is called, in case sending them gets interrupted
+    pub fn take(&self) -> Vec<PendingWrite> {
+        self.writes.lock().unwrap().clone()
+    }
+
+    pub fn remove_sent(&self, sent: &[PendingWrite]) {
+        let mut writes = self.writes.lock().unwrap();
+        let count = writes
+            .iter()
+            .zip(sent.iter())
+            .take_while(|(a, b)| a == b)
+            .count();
+        writes.drain(..count);
+        self.save(&writes);
+    }
+
+    pub fn clear(&self) {
+        let mut writes = self.writes.lock().unwrap();
+        writes.clear();
+        self.save(&writes);
+    }
+
+    fn save(&self, writes: &[PendingWrite]) {
+        if let Some(path) = self.path.as_ref() {
+            if let Err(e) = Self::write_file(path, writes) {
+                warn!("Could not save pending changes: {}", e);
+            }
+        }
+    }
+
+    fn write_file(path: &PathBuf, writes: &[PendingWrite]) -> io::Result<()> {
+        if writes.is_empty() {
+            return match fs::remove_file(path) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+                result => result,
+            };
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let content = serde_json::to_vec(writes)?;
+        // Same as the playback session, a crash mid-write must not lose everything
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, content)?;
+        fs::rename(tmp_path, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_remove_sent() {
+        let outbox = Outbox::in_memory();
+        outbox.push(PendingWrite::SaveAlbum("a".to_string()));
+        outbox.push(PendingWrite::SaveTracks(vec!["b".to_string()]));
+
+        let mut pending = outbox.take();
+        assert_eq!(pending.len(), 2);
+
+        // something gets queued while the first write is being sent
+        outbox.push(PendingWrite::RemoveSavedAlbum("a".to_string()));
+        pending.truncate(1);
+        outbox.remove_sent(&pending);
+
+        assert_eq!(
+            outbox.take(),
+            vec![
+                PendingWrite::SaveTracks(vec!["b".to_string()]),
+                PendingWrite::RemoveSavedAlbum("a".to_string())
+            ]
+        );
+    }
+}
@@ -1,7 +1,8 @@
 use gettextrs::gettext;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-use crate::api::{SpotifyApiClient, SpotifyApiError};
+use crate::api::{SpotifyApiClient, SpotifyApiError, SpotifyResult};
 use crate::app::models::*;
 use crate::app::AppAction;
 
@@ -10,11 +11,16 @@ pub struct BatchLoader {
     api: Arc<dyn SpotifyApiClient + Send + Sync>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum SongsSource {
     Playlist(String),
     Album(String),
     SavedTracks,
+    // Tracks similar to the seeds, appended when the queue ends
+    Recommendations {
+        seed_tracks: Vec<String>,
+        seed_artists: Vec<String>,
+    },
 }
 
 impl PartialEq for SongsSource {
@@ -23,6 +29,16 @@ impl PartialEq for SongsSource {
             (Self::Playlist(l), Self::Playlist(r)) => l == r,
             (Self::Album(l), Self::Album(r)) => l == r,
             (Self::SavedTracks, Self::SavedTracks) => true,
+            (
+                Self::Recommendations {
+                    seed_tracks: lt,
+                    seed_artists: la,
+                },
+                Self::Recommendations {
+                    seed_tracks: rt,
+                    seed_artists: ra,
+                },
+            ) => lt == rt && la == ra,
             _ => false,
         }
     }
@@ -65,6 +81,34 @@ impl BatchLoader {
         Self { api }
     }
 
+    pub async fn fetch(&self, query: &BatchQuery) -> SpotifyResult<SongBatch> {
+        let api = Arc::clone(&self.api);
+        let Batch {
+            offset, batch_size, ..
+        } = query.batch;
+
+        match &query.source {
+            SongsSource::Playlist(id) => api.get_playlist_tracks(id, offset, batch_size).await,
+            SongsSource::SavedTracks => api.get_saved_tracks(offset, batch_size).await,
+            SongsSource::Album(id) => api.get_album_tracks(id, offset, batch_size).await,
+            SongsSource::Recommendations {
+                seed_tracks,
+                seed_artists,
+            } => {
+                let songs = api
+                    .get_recommendations(seed_tracks.clone(), seed_artists.clone(), batch_size)
+                    .await?;
+                // There's no paging here, the batch just follows the songs already queued
+                let batch = Batch {
+                    offset,
+                    batch_size: songs.len(),
+                    total: offset + songs.len(),
+                };
+                Ok(SongBatch { songs, batch })
+            }
+        }
+    }
+
     pub async fn query<ActionCreator>(
         &self,
         query: BatchQuery,
@@ -73,32 +117,16 @@ impl BatchLoader {
     where
         ActionCreator: FnOnce(SongsSource, SongBatch) -> AppAction,
     {
-        let api = Arc::clone(&self.api);
-
-        let result = match &query.source {
-            SongsSource::Playlist(id) => {
-                let Batch {
-                    offset, batch_size, ..
-                } = query.batch;
-                api.get_playlist_tracks(id, offset, batch_size).await
-            }
-            SongsSource::SavedTracks => {
-                let Batch {
-                    offset, batch_size, ..
-                } = query.batch;
-                api.get_saved_tracks(offset, batch_size).await
-            }
-            SongsSource::Album(id) => {
-                let Batch {
-                    offset, batch_size, ..
-                } = query.batch;
-                api.get_album_tracks(id, offset, batch_size).await
-            }
-        };
-
-        match result {
+        match self.fetch(&query).await {
             Ok(batch) => Some(create_action(query.source, batch)),
             Err(SpotifyApiError::NoToken) => None,
+            Err(err) if err.is_network_error() => {
+                warn!("Spotify API unreachable: {}", err);
+                Some(AppAction::ShowNotification(gettext(
+                    // translators: This notification is shown when something can't be loaded from the cache while offline.
+                    "This isn't available offline",
+                )))
+            }
             Err(err) => {
                 error!("Spotify API error: {}", err);
                 Some(AppAction::ShowNotification(gettext(
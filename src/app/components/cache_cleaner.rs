@@ -0,0 +1,60 @@
+use std::rc::Rc;
+
+use crate::api::cache::{CacheManager, IMAGE_CACHE_DIR, NETWORK_CACHE_DIR};
+use crate::app::components::EventListener;
+use crate::app::state::SettingsEvent;
+use crate::app::{AppEvent, AppModel, Worker};
+
+const COMPACTION_INTERVAL_SECONDS: u32 = 30 * 60;
+const MEGABYTE: u64 = 1024 * 1024;
+
+// Keeps the caches under the size set in the preferences, evicting what hasn't been used for the longest time
+pub struct CacheCleaner {
+    app_model: Rc<AppModel>,
+    worker: Worker,
+}
+
+impl CacheCleaner {
+    pub fn new(app_model: Rc<AppModel>, worker: Worker) -> Self {
+        glib::timeout_add_seconds_local(
+            COMPACTION_INTERVAL_SECONDS,
+            clone!(@weak app_model, @strong worker => @default-return glib::Continue(false), move || {
+                Self::compact(&app_model, &worker);
+                glib::Continue(true)
+            }),
+        );
+
+        Self { app_model, worker }
+    }
+
+    fn compact(app_model: &AppModel, worker: &Worker) {
+        let settings = app_model.get_state().settings.settings.cache.clone();
+        let caches = [
+            (NETWORK_CACHE_DIR, settings.network_max_size),
+            (IMAGE_CACHE_DIR, settings.image_max_size),
+        ];
+        worker.send_task(async move {
+            for (dir, max_size) in caches {
+                let Some(cache) = CacheManager::for_dir(dir) else {
+                    continue;
+                };
+                match cache.compact(max_size as u64 * MEGABYTE).await {
+                    Ok(0) => {}
+                    Ok(n) => debug!("Evicted {} files from {}", n, dir),
+                    Err(e) => warn!("Could not compact {}: {}", dir, e),
+                }
+            }
+        });
+    }
+}
+
+impl EventListener for CacheCleaner {
+    fn on_event(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::Started | AppEvent::SettingsEvent(SettingsEvent::CacheSettingsChanged) => {
+                Self::compact(&self.app_model, &self.worker)
+            }
+            _ => {}
+        }
+    }
+}
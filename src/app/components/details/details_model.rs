@@ -197,6 +197,7 @@ impl PlaylistModel for DetailsModel {
             group.add_action(&view_artist);
         }
         group.add_action(&song.make_link_action(None));
+        group.add_action(&song.make_play_next_action(self.dispatcher.box_clone(), None));
         group.add_action(&song.make_queue_action(self.dispatcher.box_clone(), None));
 
         Some(group.upcast())
@@ -215,6 +216,7 @@ impl PlaylistModel for DetailsModel {
         }
 
         menu.append(Some(&*labels::COPY_LINK), Some("song.copy_link"));
+        menu.append(Some(&*labels::PLAY_NEXT), Some("song.play_next"));
         menu.append(Some(&*labels::ADD_TO_QUEUE), Some("song.queue"));
         Some(menu.upcast())
     }
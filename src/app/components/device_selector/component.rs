@@ -2,6 +2,7 @@ use std::ops::Deref;
 use std::rc::Rc;
 
 use glib::Cast;
+use gtk::prelude::WidgetExt;
 
 use crate::app::components::{Component, EventListener};
 use crate::app::models::ConnectDevice;
@@ -90,10 +91,15 @@ impl EventListener for DeviceSelector {
                 self.widget
                     .update_devices_list(&self.model.get_available_devices());
             }
-            AppEvent::PlaybackEvent(PlaybackEvent::SwitchedDevice(_)) => {
+            AppEvent::PlaybackEvent(PlaybackEvent::SwitchedDevice(_))
+            | AppEvent::PlaybackEvent(PlaybackEvent::DeviceSynced(_)) => {
                 self.widget
                     .set_current_device(&self.model.get_current_device());
             }
+            // Connect devices can only be reached through Spotify
+            AppEvent::NetworkStatusChanged(status) => {
+                self.widget.set_sensitive(!status.is_offline());
+            }
             _ => (),
         }
     }
@@ -10,6 +10,9 @@ lazy_static! {
     // translators: This is part of a contextual menu attached to a single track; this entry adds a track at the end of the play queue.
     pub static ref ADD_TO_QUEUE: String = gettext("Add to queue");
 
+    // translators: This is part of a contextual menu attached to a single track; this entry adds a track at the start of the play queue, so that it plays after the current one.
+    pub static ref PLAY_NEXT: String = gettext("Play next");
+
     // translators: This is part of a contextual menu attached to a single track; this entry removes a track from the play queue.
     pub static ref REMOVE_FROM_QUEUE: String = gettext("Remove from queue");
 }
@@ -32,6 +35,24 @@ pub fn n_songs_selected_label(n: usize) -> String {
     ngettext!("{} song selected", "{} songs selected", n as u32, n)
 }
 
+pub fn n_pending_changes_label(n: usize) -> String {
+    // this is just to fool xgettext, it doesn't like macros (or rust for that matter) :(
+    if cfg!(debug_assertions) {
+        // translators: This is shown in a banner while changes to the library could not be sent to Spotify yet.
+        ngettext(
+            "{} change is waiting to be synced…",
+            "{} changes are waiting to be synced…",
+            n as u32,
+        );
+    }
+    ngettext!(
+        "{} change is waiting to be synced…",
+        "{} changes are waiting to be synced…",
+        n as u32,
+        n
+    )
+}
+
 pub fn more_from_label(artist: &str) -> String {
     // this is just to fool xgettext, it doesn't like macros (or rust for that matter) :(
     if cfg!(debug_assertions) {
@@ -53,3 +74,12 @@ pub fn album_by_artist_label(album: &str, artist: &str) -> String {
         glib::markup_escape_text(artist)
     )
 }
+
+pub fn cache_usage_label(size: u64) -> String {
+    // this is just to fool xgettext, it doesn't like macros (or rust for that matter) :(
+    if cfg!(debug_assertions) {
+        // translators: This shows how much disk space a cache uses, in the preferences. The placeholder is a size, like "12.3 MB".
+        gettext("{} used");
+    }
+    gettext!("{} used", glib::format_size(size))
+}
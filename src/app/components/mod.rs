@@ -9,9 +9,10 @@ use gettextrs::*;
 use std::cell::RefCell;
 use std::collections::HashSet;
 use std::future::Future;
+use std::sync::Arc;
 
-use crate::api::SpotifyApiError;
-use crate::app::{state::LoginAction, ActionDispatcher, AppAction, AppEvent};
+use crate::api::{SpotifyApiClient, SpotifyApiError};
+use crate::app::{state::LoginAction, ActionDispatcher, AppAction, AppEvent, BrowserAction};
 
 mod navigation;
 pub use navigation::*;
@@ -31,6 +32,15 @@ pub use settings::*;
 mod player_notifier;
 pub use player_notifier::PlayerNotifier;
 
+mod session_manager;
+pub use session_manager::SessionManager;
+
+mod offline_manager;
+pub use offline_manager::OfflineManager;
+
+mod cache_cleaner;
+pub use cache_cleaner::CacheCleaner;
+
 mod library;
 pub use library::*;
 
@@ -67,6 +77,9 @@ pub use user_menu::*;
 mod notification;
 pub use notification::*;
 
+mod offline;
+pub use offline::*;
+
 mod saved_playlists;
 pub use saved_playlists::*;
 
@@ -126,6 +139,13 @@ impl dyn ActionDispatcher {
                     retried.insert(0, LoginAction::RefreshToken.into());
                     retried
                 }
+                Err(err) if err.is_network_error() => {
+                    warn!("Spotify API unreachable: {}", err);
+                    vec![AppAction::ShowNotification(gettext(
+                        // translators: This notification is shown when something can't be loaded from the cache while offline.
+                        "This isn't available offline",
+                    ))]
+                }
                 Err(err) => {
                     error!("Spotify API error: {}", err);
                     vec![AppAction::ShowNotification(gettext(
@@ -136,6 +156,48 @@ impl dyn ActionDispatcher {
             }
         }))
     }
+
+    // The move is shown right away so that the positions of the next one already account for it,
+    // and undone if Spotify refuses it
+    fn move_playlist_track(
+        &self,
+        api: Arc<dyn SpotifyApiClient + Send + Sync>,
+        id: String,
+        from: usize,
+        to: usize,
+        snapshot_id: Option<String>,
+    ) {
+        self.dispatch(
+            BrowserAction::MovePlaylistTrack {
+                id: id.clone(),
+                from,
+                to,
+            }
+            .into(),
+        );
+        self.dispatch_many_async(Box::pin(async move {
+            match api.move_playlist_track(&id, from, to, snapshot_id).await {
+                Ok(snapshot_id) => {
+                    vec![BrowserAction::SetPlaylistSnapshot { id, snapshot_id }.into()]
+                }
+                Err(err) => {
+                    error!("Could not move playlist track: {}", err);
+                    vec![
+                        BrowserAction::MovePlaylistTrack {
+                            id,
+                            from: to,
+                            to: from,
+                        }
+                        .into(),
+                        AppAction::ShowNotification(gettext(
+                            // translators: This notification is the default message for unhandled errors. Logs refer to console output.
+                            "An error occured. Check logs for details!",
+                        )),
+                    ]
+                }
+            }
+        }))
+    }
 }
 
 thread_local!(static CSS_ADDED: RefCell<HashSet<&'static str>> = RefCell::new(HashSet::new()));
@@ -29,6 +29,12 @@ mod imp {
 
         #[template_child]
         pub scrolled_window: TemplateChild<gtk::ScrolledWindow>,
+
+        #[template_child]
+        pub next_up_section: TemplateChild<gtk::Box>,
+
+        #[template_child]
+        pub next_up_list: TemplateChild<gtk::ListView>,
     }
 
     #[glib::object_subclass]
@@ -82,6 +88,14 @@ impl NowPlayingWidget {
         self.imp().song_list.as_ref()
     }
 
+    fn next_up_list_widget(&self) -> &gtk::ListView {
+        self.imp().next_up_list.as_ref()
+    }
+
+    fn set_next_up_visible(&self, visible: bool) {
+        self.imp().next_up_section.set_visible(visible);
+    }
+
     fn headerbar_widget(&self) -> &HeaderBarWidget {
         self.imp().headerbar.as_ref()
     }
@@ -105,6 +119,13 @@ impl NowPlaying {
             model.load_more();
         }));
 
+        let next_up = Box::new(Playlist::new(
+            widget.next_up_list_widget().clone(),
+            model.to_next_up_model(),
+            worker.clone(),
+        ));
+        widget.set_next_up_visible(model.has_next_up());
+
         let playlist = Box::new(Playlist::new(
             widget.song_list_widget().clone(),
             model.clone(),
@@ -126,7 +147,7 @@ impl NowPlaying {
         Self {
             widget,
             model,
-            children: vec![playlist, headerbar, device_selector],
+            children: vec![next_up, playlist, headerbar, device_selector],
         }
     }
 }
@@ -143,8 +164,15 @@ impl Component for NowPlaying {
 
 impl EventListener for NowPlaying {
     fn on_event(&mut self, event: &AppEvent) {
-        if let AppEvent::PlaybackEvent(PlaybackEvent::TrackChanged(_)) = event {
-            self.model.load_more();
+        match event {
+            AppEvent::PlaybackEvent(PlaybackEvent::TrackChanged(_)) => {
+                self.model.load_more();
+                self.widget.set_next_up_visible(self.model.has_next_up());
+            }
+            AppEvent::PlaybackEvent(PlaybackEvent::PlaylistChanged) => {
+                self.widget.set_next_up_visible(self.model.has_next_up());
+            }
+            _ => {}
         }
         self.broadcast_event(event);
     }
@@ -60,6 +60,14 @@ impl NowPlayingModel {
         DeviceSelectorModel::new(self.app_model.clone(), self.dispatcher.box_clone())
     }
 
+    pub fn to_next_up_model(self: &Rc<Self>) -> Rc<NextUpModel> {
+        Rc::new(NextUpModel(self.clone()))
+    }
+
+    pub fn has_next_up(&self) -> bool {
+        self.queue().next_up().len() > 0
+    }
+
     fn current_selection_context(&self) -> SelectionContext {
         let state = self.app_model.get_state();
         match state.playback.current_device() {
@@ -91,9 +99,13 @@ impl PlaylistModel for NowPlayingModel {
         false // too buggy for now
     }
 
+    fn is_autoplayed(&self, id: &str) -> bool {
+        self.queue().is_autoplayed(id)
+    }
+
     fn actions_for(&self, id: &str) -> Option<gio::ActionGroup> {
         let queue = self.queue();
-        let song = queue.songs().get(id)?;
+        let song = queue.get_song(id)?;
         let song = song.description();
         let group = SimpleActionGroup::new();
 
@@ -109,7 +121,7 @@ impl PlaylistModel for NowPlayingModel {
 
     fn menu_for(&self, id: &str) -> Option<gio::MenuModel> {
         let queue = self.queue();
-        let song = queue.songs().get(id)?;
+        let song = queue.get_song(id)?;
         let song = song.description();
 
         let menu = gio::Menu::new();
@@ -129,7 +141,7 @@ impl PlaylistModel for NowPlayingModel {
 
     fn select_song(&self, id: &str) {
         let queue = self.queue();
-        if let Some(song) = queue.songs().get(id) {
+        if let Some(song) = queue.get_song(id) {
             let song = song.description().clone();
             self.dispatcher
                 .dispatch(SelectionAction::Select(vec![song]).into());
@@ -172,3 +184,52 @@ impl SimpleHeaderBarModel for NowPlayingModel {
             .dispatch(SelectionAction::Select(songs).into());
     }
 }
+
+// The songs queued by the user, shown in their own section above the rest of the queue
+pub struct NextUpModel(Rc<NowPlayingModel>);
+
+impl PlaylistModel for NextUpModel {
+    fn song_list_model(&self) -> SongListModel {
+        self.0.queue().next_up().clone()
+    }
+
+    fn is_paused(&self) -> bool {
+        self.0.is_paused()
+    }
+
+    fn current_song_id(&self) -> Option<String> {
+        None
+    }
+
+    fn play_song_at(&self, pos: usize, id: &str) {
+        self.0.play_song_at(pos, id)
+    }
+
+    fn autoscroll_to_playing(&self) -> bool {
+        false
+    }
+
+    fn actions_for(&self, id: &str) -> Option<gio::ActionGroup> {
+        self.0.actions_for(id)
+    }
+
+    fn menu_for(&self, id: &str) -> Option<gio::MenuModel> {
+        self.0.menu_for(id)
+    }
+
+    fn select_song(&self, id: &str) {
+        self.0.select_song(id)
+    }
+
+    fn deselect_song(&self, id: &str) {
+        self.0.deselect_song(id)
+    }
+
+    fn enable_selection(&self) -> bool {
+        self.0.enable_selection()
+    }
+
+    fn selection(&self) -> Option<Box<dyn Deref<Target = SelectionState> + '_>> {
+        self.0.selection()
+    }
+}
@@ -0,0 +1,80 @@
+use gettextrs::*;
+use gtk::prelude::*;
+use std::rc::Rc;
+
+use crate::app::components::{labels, EventListener};
+use crate::app::state::NetworkStatus;
+use crate::app::{ActionDispatcher, AppAction, AppEvent, AppModel};
+
+pub struct OfflineBanner {
+    app_model: Rc<AppModel>,
+    revealer: gtk::Revealer,
+    label: gtk::Label,
+    button: gtk::Button,
+}
+
+impl OfflineBanner {
+    pub fn new(
+        app_model: Rc<AppModel>,
+        dispatcher: Box<dyn ActionDispatcher>,
+        revealer: gtk::Revealer,
+        label: gtk::Label,
+        button: gtk::Button,
+    ) -> Self {
+        button.connect_clicked(clone!(@weak app_model => move |_| {
+            let action = match app_model.get_state().network {
+                NetworkStatus::WorkingOffline => AppAction::SetWorkOffline(false),
+                _ => AppAction::SetNetworkAvailable(true),
+            };
+            dispatcher.dispatch(action);
+        }));
+
+        Self {
+            app_model,
+            revealer,
+            label,
+            button,
+        }
+    }
+
+    fn update(&self) {
+        let state = self.app_model.get_state();
+        match state.network {
+            NetworkStatus::Online if state.pending_writes > 0 => {
+                self.label
+                    .set_label(&labels::n_pending_changes_label(state.pending_writes));
+                self.button.set_visible(false);
+                self.revealer.set_reveal_child(true);
+            }
+            NetworkStatus::Online => self.revealer.set_reveal_child(false),
+            NetworkStatus::Offline => {
+                // translators: This is shown in a banner when Spotify can't be reached, saved content is still browsable.
+                self.label
+                    .set_label(&gettext("Spotify can't be reached. Showing saved content."));
+                // translators: This button in the offline banner tries to reach Spotify again.
+                self.button.set_label(&gettext("Retry"));
+                self.button.set_visible(true);
+                self.revealer.set_reveal_child(true);
+            }
+            NetworkStatus::WorkingOffline => {
+                // translators: This is shown in a banner when the user chose to work offline.
+                self.label.set_label(&gettext("You are working offline."));
+                // translators: This button in the offline banner turns off offline mode.
+                self.button.set_label(&gettext("Go Online"));
+                self.button.set_visible(true);
+                self.revealer.set_reveal_child(true);
+            }
+        }
+    }
+}
+
+impl EventListener for OfflineBanner {
+    fn on_event(&mut self, event: &AppEvent) {
+        if let AppEvent::NetworkStatusChanged(_)
+        | AppEvent::PendingWritesChanged(_)
+        | AppEvent::Started = event
+        {
+            self.update();
+        }
+    }
+}
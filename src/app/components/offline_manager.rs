@@ -0,0 +1,118 @@
+use gettextrs::*;
+use gio::prelude::*;
+use std::cell::Cell;
+use std::rc::Rc;
+
+use crate::app::components::EventListener;
+use crate::app::state::{LoginEvent, NetworkStatus};
+use crate::app::{ActionDispatcher, AppAction, AppEvent, AppModel};
+
+const RETRY_INTERVAL_SECONDS: u32 = 60;
+const FIRST_REPLAY_DELAY_SECONDS: u32 = 5;
+const MAX_REPLAY_DELAY_SECONDS: u32 = 300;
+
+// Follows network connectivity, and sends the changes that couldn't be sent right away
+pub struct OfflineManager {
+    app_model: Rc<AppModel>,
+    dispatcher: Box<dyn ActionDispatcher>,
+    replay_attempts: Rc<Cell<u32>>,
+    replay_scheduled: Rc<Cell<bool>>,
+}
+
+impl OfflineManager {
+    pub fn new(app_model: Rc<AppModel>, dispatcher: Box<dyn ActionDispatcher>) -> Self {
+        let monitor = gio::NetworkMonitor::default();
+        let network_dispatcher = dispatcher.box_clone();
+        monitor.connect_network_changed(move |_, available| {
+            network_dispatcher.dispatch(AppAction::SetNetworkAvailable(available));
+        });
+
+        // Spotify might have been unreachable while the network itself was fine, so retry once in a while
+        let retry_dispatcher = dispatcher.box_clone();
+        glib::timeout_add_seconds_local(
+            RETRY_INTERVAL_SECONDS,
+            clone!(@weak app_model => @default-return glib::Continue(false), move || {
+                let network = app_model.get_state().network;
+                if network == NetworkStatus::Offline && monitor.is_network_available() {
+                    retry_dispatcher.dispatch(AppAction::SetNetworkAvailable(true));
+                }
+                glib::Continue(true)
+            }),
+        );
+
+        Self {
+            app_model,
+            dispatcher,
+            replay_attempts: Rc::new(Cell::new(0)),
+            replay_scheduled: Rc::new(Cell::new(false)),
+        }
+    }
+
+    fn replay_delay(attempts: u32) -> u32 {
+        FIRST_REPLAY_DELAY_SECONDS
+            .saturating_mul(2u32.saturating_pow(attempts))
+            .min(MAX_REPLAY_DELAY_SECONDS)
+    }
+
+    // Writes are kept while logged out, they can't be sent without a token anyway
+    fn can_replay(&self) -> bool {
+        let state = self.app_model.get_state();
+        state.pending_writes > 0 && !state.network.is_offline() && state.logged_user.user.is_some()
+    }
+
+    fn replay_pending_writes(app_model: &AppModel, dispatcher: &dyn ActionDispatcher) {
+        let api = app_model.get_spotify();
+        dispatcher.dispatch_many_async(Box::pin(async move {
+            let result = api.replay_pending_writes().await;
+            // Always sent, even if nothing changed, so that another attempt gets scheduled
+            let mut actions = vec![AppAction::SetPendingWrites(api.pending_writes())];
+            match result {
+                Ok(sent) if sent > 0 => actions.push(AppAction::ShowNotification(gettext(
+                    // translators: This notification is shown when changes that could not be sent earlier were sent to Spotify.
+                    "Pending changes have been synced",
+                ))),
+                Ok(_) => {}
+                Err(e) => warn!("Could not send pending changes: {}", e),
+            }
+            actions
+        }));
+    }
+
+    fn schedule_replay(&self) {
+        if !self.can_replay() || self.replay_scheduled.get() {
+            return;
+        }
+
+        let attempts = self.replay_attempts.get();
+        self.replay_attempts.set(attempts + 1);
+        self.replay_scheduled.set(true);
+
+        let dispatcher = self.dispatcher.box_clone();
+        let replay_scheduled = Rc::clone(&self.replay_scheduled);
+        glib::timeout_add_seconds_local(
+            Self::replay_delay(attempts),
+            clone!(@weak self.app_model as app_model => @default-return glib::Continue(false), move || {
+                replay_scheduled.set(false);
+                Self::replay_pending_writes(&app_model, dispatcher.as_ref());
+                glib::Continue(false)
+            }),
+        );
+    }
+}
+
+impl EventListener for OfflineManager {
+    fn on_event(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::NetworkStatusChanged(NetworkStatus::Online)
+            | AppEvent::LoginEvent(LoginEvent::LoginCompleted(_)) => {
+                self.replay_attempts.set(0);
+                if self.can_replay() {
+                    Self::replay_pending_writes(&self.app_model, self.dispatcher.as_ref());
+                }
+            }
+            AppEvent::PendingWritesChanged(0) => self.replay_attempts.set(0),
+            AppEvent::PendingWritesChanged(_) => self.schedule_replay(),
+            _ => {}
+        }
+    }
+}
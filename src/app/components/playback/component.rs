@@ -1,9 +1,12 @@
 use std::ops::Deref;
 use std::rc::Rc;
 
+use crate::app::components::utils::Clock;
 use crate::app::components::EventListener;
 use crate::app::models::*;
-use crate::app::state::{PlaybackAction, PlaybackEvent, ScreenName, SelectionEvent};
+use crate::app::state::{
+    Device, PlaybackAction, PlaybackEvent, ScreenName, SelectionEvent, SleepTimer,
+};
 use crate::app::{
     ActionDispatcher, AppAction, AppEvent, AppModel, AppState, BrowserAction, Worker,
 };
@@ -37,6 +40,10 @@ impl PlaybackModel {
         self.state().playback.is_playing()
     }
 
+    fn is_buffering(&self) -> bool {
+        self.state().playback.is_buffering()
+    }
+
     fn is_shuffled(&self) -> bool {
         self.state().playback.is_shuffled()
     }
@@ -71,12 +78,32 @@ impl PlaybackModel {
         self.dispatcher
             .dispatch(PlaybackAction::Seek(position).into());
     }
+
+    fn set_sleep_timer(&self, timer: Option<SleepTimer>) {
+        self.dispatcher
+            .dispatch(PlaybackAction::SetSleepTimer(timer).into());
+    }
+
+    fn set_rate(&self, rate: f32) {
+        self.dispatcher
+            .dispatch(PlaybackAction::SetRate(rate).into());
+    }
+
+    fn is_local_device(&self) -> bool {
+        matches!(self.state().playback.current_device(), Device::Local)
+    }
+
+    fn sleep_timer_remaining(&self) -> Option<f64> {
+        let remaining = self.state().playback.sleep_timer_remaining()?;
+        Some(remaining.as_millis() as f64)
+    }
 }
 
 pub struct PlaybackControl {
     model: Rc<PlaybackModel>,
     widget: PlaybackWidget,
     worker: Worker,
+    sleep_timer_clock: Clock,
 }
 
 impl PlaybackControl {
@@ -90,11 +117,29 @@ impl PlaybackControl {
         widget.connect_repeat(clone!(@weak model => move || model.toggle_repeat()));
         widget.connect_seek(clone!(@weak model => move |position| model.seek_to(position)));
         widget.connect_now_playing_clicked(clone!(@weak model => move || model.go_home()));
+        widget
+            .connect_sleep_timer(clone!(@weak model => move |timer| model.set_sleep_timer(timer)));
+        widget.connect_playback_rate(clone!(@weak model => move |rate| model.set_rate(rate)));
 
         Self {
             model,
             widget,
             worker,
+            sleep_timer_clock: Clock::default(),
+        }
+    }
+
+    fn update_sleep_timer(&self) {
+        let remaining = self.model.sleep_timer_remaining();
+        self.widget.set_sleep_timer_remaining(remaining);
+        if remaining.is_some() {
+            self.sleep_timer_clock.start(
+                clone!(@weak self.model as model, @weak self.widget as widget => move || {
+                    widget.set_sleep_timer_remaining(model.sleep_timer_remaining());
+                }),
+            );
+        } else {
+            self.sleep_timer_clock.stop();
         }
     }
 
@@ -111,6 +156,10 @@ impl PlaybackControl {
         self.widget.set_playing(is_playing);
     }
 
+    fn update_buffering(&self) {
+        self.widget.set_buffering(self.model.is_buffering());
+    }
+
     fn update_current_info(&self) {
         if let Some(song) = self.model.current_song() {
             self.widget
@@ -133,9 +182,13 @@ impl EventListener for PlaybackControl {
     fn on_event(&mut self, event: &AppEvent) {
         match event {
             AppEvent::PlaybackEvent(PlaybackEvent::PlaybackPaused)
-            | AppEvent::PlaybackEvent(PlaybackEvent::PlaybackResumed) => {
+            | AppEvent::PlaybackEvent(PlaybackEvent::PlaybackResumed)
+            | AppEvent::PlaybackEvent(PlaybackEvent::PlayingSynced(_)) => {
                 self.update_playing();
             }
+            AppEvent::PlaybackEvent(PlaybackEvent::BufferingChanged(buffering)) => {
+                self.widget.set_buffering(*buffering);
+            }
             AppEvent::PlaybackEvent(PlaybackEvent::RepeatModeChanged(mode)) => {
                 self.update_repeat(mode);
             }
@@ -147,6 +200,7 @@ impl EventListener for PlaybackControl {
             }
             AppEvent::PlaybackEvent(PlaybackEvent::PlaybackStopped) => {
                 self.update_playing();
+                self.update_buffering();
                 self.update_current_info();
             }
             AppEvent::PlaybackEvent(PlaybackEvent::SeekSynced(pos))
@@ -156,6 +210,18 @@ impl EventListener for PlaybackControl {
             AppEvent::SelectionEvent(SelectionEvent::SelectionModeChanged(active)) => {
                 self.widget.set_seekbar_visible(!active);
             }
+            AppEvent::PlaybackEvent(PlaybackEvent::SleepTimerChanged) => {
+                self.update_sleep_timer();
+            }
+            AppEvent::PlaybackEvent(PlaybackEvent::RateChanged(rate)) => {
+                self.widget.set_playback_rate(*rate);
+            }
+            AppEvent::PlaybackEvent(PlaybackEvent::SwitchedDevice(_))
+            | AppEvent::PlaybackEvent(PlaybackEvent::DeviceSynced(_)) => {
+                self.widget
+                    .set_playback_rate_available(self.model.is_local_device());
+                self.update_buffering();
+            }
             _ => {}
         }
     }
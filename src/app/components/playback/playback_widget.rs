@@ -1,16 +1,27 @@
+use gio::{SimpleAction, SimpleActionGroup};
 use gtk::prelude::*;
 use gtk::subclass::prelude::*;
 use gtk::{glib, CompositeTemplate};
+use std::cell::Cell;
 
 use crate::app::components::display_add_css_provider;
 use crate::app::components::utils::{format_duration, Clock, Debouncer};
 use crate::app::loader::ImageLoader;
 use crate::app::models::RepeatMode;
+use crate::app::state::SleepTimer;
 use crate::app::Worker;
 
 use super::playback_controls::PlaybackControlsWidget;
 use super::playback_info::PlaybackInfoWidget;
 
+const SLEEP_TIMER_ACTIONS: &str = "sleep-timer";
+const PLAYBACK_RATE_ACTIONS: &str = "playback-rate";
+
+// Matches the targets of the menu, so that the current rate shows up as selected
+fn rate_target(rate: f32) -> String {
+    format!("{:?}", rate)
+}
+
 mod imp {
 
     use super::*;
@@ -39,7 +50,23 @@ mod imp {
         #[template_child]
         pub track_duration: TemplateChild<gtk::Label>,
 
+        #[template_child]
+        pub buffering: TemplateChild<gtk::Spinner>,
+
+        #[template_child]
+        pub sleep_timer_countdown: TemplateChild<gtk::Label>,
+
+        #[template_child]
+        pub playback_rate: TemplateChild<gtk::MenuButton>,
+
         pub clock: Clock,
+
+        // Normal speed if unset
+        pub rate: Cell<Option<f32>>,
+
+        pub sleep_timer_actions: SimpleActionGroup,
+
+        pub playback_rate_actions: SimpleActionGroup,
     }
 
     #[glib::object_subclass]
@@ -62,6 +89,16 @@ mod imp {
             self.parent_constructed();
             self.now_playing_mobile.set_info_visible(false);
             self.now_playing.set_info_visible(true);
+            self.obj()
+                .insert_action_group(SLEEP_TIMER_ACTIONS, Some(&self.sleep_timer_actions));
+            self.obj()
+                .insert_action_group(PLAYBACK_RATE_ACTIONS, Some(&self.playback_rate_actions));
+            self.playback_rate_actions
+                .add_action(&SimpleAction::new_stateful(
+                    "set",
+                    Some(glib::VariantTy::STRING),
+                    rate_target(1.0).to_variant(),
+                ));
             display_add_css_provider(resource!("/components/playback.css"));
         }
     }
@@ -133,7 +170,9 @@ impl PlaybackWidget {
     }
 
     pub fn increment_seek_position(&self) {
-        let value = self.imp().seek_bar.value() + 1_000.0;
+        let widget = self.imp();
+        let rate = widget.rate.get().unwrap_or(1.0);
+        let value = widget.seek_bar.value() + 1_000.0 * f64::from(rate);
         self.set_seek_position(value);
     }
 
@@ -179,6 +218,87 @@ impl PlaybackWidget {
         }
     }
 
+    pub fn set_buffering(&self, buffering: bool) {
+        let spinner = &self.imp().buffering;
+        spinner.set_spinning(buffering);
+        spinner.set_visible(buffering);
+    }
+
+    pub fn set_sleep_timer_remaining(&self, remaining: Option<f64>) {
+        let label = &self.imp().sleep_timer_countdown;
+        if let Some(remaining) = remaining {
+            label.set_text(&format_duration(remaining));
+            label.show();
+        } else {
+            label.hide();
+        }
+    }
+
+    pub fn connect_sleep_timer<F>(&self, f: F)
+    where
+        F: Fn(Option<SleepTimer>) + Clone + 'static,
+    {
+        let actions = &self.imp().sleep_timer_actions;
+        actions.add_action(&{
+            let start = SimpleAction::new("start", Some(glib::VariantTy::STRING));
+            let f = f.clone();
+            start.connect_activate(move |_, minutes| {
+                let minutes = minutes
+                    .and_then(|m| m.get::<String>())
+                    .and_then(|m| m.parse::<u32>().ok());
+                if let Some(minutes) = minutes {
+                    f(Some(SleepTimer::Minutes(minutes)));
+                }
+            });
+            start
+        });
+        actions.add_action(&{
+            let end_of_track = SimpleAction::new("end-of-track", None);
+            let f = f.clone();
+            end_of_track.connect_activate(move |_, _| f(Some(SleepTimer::EndOfTrack)));
+            end_of_track
+        });
+        actions.add_action(&{
+            let cancel = SimpleAction::new("cancel", None);
+            cancel.connect_activate(move |_, _| f(None));
+            cancel
+        });
+    }
+
+    pub fn set_playback_rate(&self, rate: f32) {
+        let widget = self.imp();
+        widget.rate.set(Some(rate));
+        widget.playback_rate.set_label(&format!("{}×", rate));
+        widget
+            .playback_rate_actions
+            .change_action_state("set", &rate_target(rate).to_variant());
+    }
+
+    // Only the local player can change its speed
+    pub fn set_playback_rate_available(&self, available: bool) {
+        self.imp().playback_rate.set_sensitive(available);
+    }
+
+    pub fn connect_playback_rate<F>(&self, f: F)
+    where
+        F: Fn(f32) + 'static,
+    {
+        let action = self
+            .imp()
+            .playback_rate_actions
+            .lookup_action("set")
+            .and_downcast::<SimpleAction>()
+            .unwrap();
+        action.connect_activate(move |_, rate| {
+            let rate = rate
+                .and_then(|r| r.get::<String>())
+                .and_then(|r| r.parse::<f32>().ok());
+            if let Some(rate) = rate {
+                f(rate);
+            }
+        });
+    }
+
     pub fn set_repeat_mode(&self, mode: RepeatMode) {
         let widget = self.imp();
         widget.controls.set_repeat_mode(mode);
@@ -1,16 +1,25 @@
+use std::cell::Cell;
 use std::ops::Deref;
 use std::rc::Rc;
+use std::time::Duration;
 
 use futures::channel::mpsc::UnboundedSender;
+use gettextrs::gettext;
 use librespot::core::spotify_id::SpotifyId;
 
+use crate::app::components::utils::Clock;
 use crate::app::components::EventListener;
 use crate::app::state::{
-    Device, LoginAction, LoginEvent, LoginStartedEvent, PlaybackEvent, SettingsEvent,
+    Device, LoginAction, LoginEvent, LoginStartedEvent, PlaybackAction, PlaybackEvent,
+    SettingsEvent,
 };
 use crate::app::{ActionDispatcher, AppAction, AppEvent, AppModel, SongsSource};
-use crate::connect::ConnectCommand;
+use crate::connect::{ConnectCommand, TransferredPlayback, TransferredSongs};
 use crate::player::Command;
+use crate::settings::saved_volume;
+
+// The volume goes down progressively over the last seconds of the sleep timer
+const SLEEP_TIMER_FADE_OUT: Duration = Duration::from_secs(10);
 
 enum CurrentlyPlaying {
     WithSource {
@@ -38,6 +47,7 @@ pub struct PlayerNotifier {
     dispatcher: Box<dyn ActionDispatcher>,
     command_sender: UnboundedSender<Command>,
     connect_command_sender: UnboundedSender<ConnectCommand>,
+    sleep_timer: Clock,
 }
 
 impl PlayerNotifier {
@@ -52,6 +62,7 @@ impl PlayerNotifier {
             dispatcher,
             command_sender,
             connect_command_sender,
+            sleep_timer: Clock::new(250),
         }
     }
 
@@ -59,17 +70,30 @@ impl PlayerNotifier {
         self.app_model.get_state().playback.is_playing()
     }
 
+    fn sleeps_at_end_of_track(&self) -> bool {
+        self.app_model.get_state().playback.sleeps_at_end_of_track()
+    }
+
     fn currently_playing(&self) -> Option<CurrentlyPlaying> {
         let state = self.app_model.get_state();
         let song = state.playback.current_song_id()?;
-        let offset = state.playback.current_song_index()?;
+        // A queued song is played on its own, outside of its context
+        let Some(offset) = state.playback.current_song_index() else {
+            return Some(CurrentlyPlaying::Songs {
+                songs: vec![song],
+                offset: 0,
+            });
+        };
         let source = state.playback.current_source().cloned();
         let result = match source {
-            Some(source) if source.has_spotify_uri() => CurrentlyPlaying::WithSource {
-                source,
-                offset,
-                song,
-            },
+            // Recommendations aren't part of the context Spotify knows about
+            Some(source) if source.has_spotify_uri() && !state.playback.is_autoplayed(&song) => {
+                CurrentlyPlaying::WithSource {
+                    source,
+                    offset,
+                    song,
+                }
+            }
             _ => CurrentlyPlaying::Songs {
                 songs: state.playback.songs().map_collect(|s| s.id),
                 offset,
@@ -78,6 +102,39 @@ impl PlayerNotifier {
         Some(result)
     }
 
+    // Recommendations are appended once the last song of the queue is playing
+    fn autoplay(&self) {
+        let query = {
+            let state = self.app_model.get_state();
+            if !state.settings.settings.autoplay {
+                return;
+            }
+            state.playback.autoplay_query()
+        };
+        let Some(query) = query else {
+            return;
+        };
+        let loader = self.app_model.get_batch_loader();
+        self.dispatcher.dispatch_async(Box::pin(async move {
+            loader
+                .query(query, |source, song_batch| {
+                    PlaybackAction::LoadPagedSongs(source, song_batch).into()
+                })
+                .await
+        }));
+    }
+
+    // Albums are often meant to be heard without a break between tracks, so we don't crossfade them
+    fn is_next_in_same_album(&self, next_id: &str) -> bool {
+        let state = self.app_model.get_state();
+        let current_album = state.playback.current_song().map(|song| song.album.id);
+        let next_album = state
+            .playback
+            .get_song(next_id)
+            .map(|song| song.description().album.id.clone());
+        current_album.is_some() && current_album == next_album
+    }
+
     fn device(&self) -> impl Deref<Target = Device> + '_ {
         self.app_model.map_state(|s| s.playback.current_device())
     }
@@ -152,24 +209,30 @@ impl PlayerNotifier {
             PlaybackEvent::PlaybackPaused => Some(Command::PlayerPause),
             PlaybackEvent::PlaybackResumed => Some(Command::PlayerResume),
             PlaybackEvent::PlaybackStopped => Some(Command::PlayerStop),
-            PlaybackEvent::VolumeSet(volume) => Some(Command::PlayerSetVolume(*volume)),
+            PlaybackEvent::VolumeSet(volume) => {
+                crate::settings::save_volume(*volume);
+                Some(Command::PlayerSetVolume(*volume))
+            }
             PlaybackEvent::TrackChanged(id) => {
                 SpotifyId::from_base62(id)
                     .ok()
                     .map(|track| Command::PlayerLoad {
                         track,
-                        resume: true,
+                        resume: self.is_playing(),
+                        position_ms: 0,
                     })
             }
-            PlaybackEvent::SourceChanged => {
-                let resume = self.is_playing();
-                self.currently_playing()
-                    .and_then(|c| SpotifyId::from_base62(c.song_id()).ok())
-                    .map(|track| Command::PlayerLoad { track, resume })
-            }
+            PlaybackEvent::SourceChanged => self.load_current_song(0),
             PlaybackEvent::TrackSeeked(position) => Some(Command::PlayerSeek(*position)),
             PlaybackEvent::Preload(id) => {
-                SpotifyId::from_base62(id).ok().map(Command::PlayerPreload)
+                SpotifyId::from_base62(id)
+                    .ok()
+                    .map(|track| Command::PlayerPreload {
+                        track,
+                        // Fading in would start the next track before we get to pause at the end of this one
+                        crossfade: !self.sleeps_at_end_of_track()
+                            && !self.is_next_in_same_album(id),
+                    })
             }
             _ => None,
         };
@@ -179,6 +242,92 @@ impl PlayerNotifier {
         }
     }
 
+    fn load_current_song(&self, position_ms: u32) -> Option<Command> {
+        let resume = self.is_playing();
+        self.currently_playing()
+            .and_then(|c| SpotifyId::from_base62(c.song_id()).ok())
+            .map(|track| Command::PlayerLoad {
+                track,
+                resume,
+                position_ms,
+            })
+    }
+
+    fn transfer_to_local_player(&self) {
+        let position_ms = self.app_model.get_state().playback.position_ms();
+        if let Some(command) = self.load_current_song(position_ms) {
+            self.send_command_to_local_player(command);
+        }
+    }
+
+    fn transferred_playback(&self) -> Option<TransferredPlayback> {
+        let songs = match self.currently_playing()? {
+            CurrentlyPlaying::WithSource { source, offset, .. } => {
+                TransferredSongs::InContext { source, offset }
+            }
+            CurrentlyPlaying::Songs { songs, offset } => {
+                TransferredSongs::NoContext { songs, offset }
+            }
+        };
+        let state = self.app_model.get_state();
+        Some(TransferredPlayback {
+            songs,
+            position_ms: state.playback.position_ms(),
+            is_playing: state.playback.is_playing(),
+            shuffle: state.playback.is_shuffled(),
+            repeat: state.playback.repeat_mode(),
+        })
+    }
+
+    fn update_sleep_timer(&self) {
+        if self
+            .app_model
+            .get_state()
+            .playback
+            .sleep_timer_remaining()
+            .is_none()
+        {
+            self.sleep_timer.stop();
+            // In case the volume was being faded out
+            if let Device::Local = *self.device() {
+                self.send_command_to_local_player(Command::PlayerSetVolume(saved_volume()));
+            }
+            return;
+        }
+
+        let app_model = Rc::clone(&self.app_model);
+        let dispatcher = self.dispatcher.box_clone();
+        let command_sender = self.command_sender.clone();
+        let faded = Cell::new(false);
+        self.sleep_timer.start(move || {
+            let (remaining, can_fade, at_end_of_track) = {
+                let state = app_model.get_state();
+                let Some(remaining) = state.playback.sleep_timer_remaining() else {
+                    return;
+                };
+                let is_local = matches!(state.playback.current_device(), Device::Local);
+                (
+                    remaining,
+                    is_local && state.playback.is_playing(),
+                    state.playback.sleeps_at_end_of_track(),
+                )
+            };
+            // Pausing at the end of the track is left to the track change, we only fade out
+            if remaining.is_zero() && !at_end_of_track {
+                dispatcher.dispatch(PlaybackAction::Pause.into());
+                dispatcher.dispatch(PlaybackAction::SetSleepTimer(None).into());
+            } else if remaining < SLEEP_TIMER_FADE_OUT && can_fade {
+                let ratio = remaining.as_secs_f64() / SLEEP_TIMER_FADE_OUT.as_secs_f64();
+                let _ =
+                    command_sender.unbounded_send(Command::PlayerSetVolume(saved_volume() * ratio));
+                faded.set(true);
+            } else if remaining >= SLEEP_TIMER_FADE_OUT && faded.replace(false) {
+                // We seeked back for instance
+                let _ = command_sender.unbounded_send(Command::PlayerSetVolume(saved_volume()));
+            }
+        });
+    }
+
     fn send_command_to_connect_player(&self, command: ConnectCommand) {
         self.connect_command_sender.unbounded_send(command).unwrap();
     }
@@ -192,27 +341,78 @@ impl PlayerNotifier {
             });
     }
 
+    // The playback carries on where it was on the new device
     fn switch_device(&mut self, device: &Device) {
         match device {
             Device::Connect(device) => {
                 self.send_command_to_local_player(Command::PlayerStop);
-                self.send_command_to_connect_player(ConnectCommand::SetDevice(device.id.clone()));
-                self.notify_connect_player(&PlaybackEvent::SourceChanged);
+                self.send_command_to_connect_player(ConnectCommand::TransferPlayback(
+                    device.id.clone(),
+                    self.transferred_playback(),
+                ));
             }
             Device::Local => {
                 self.send_command_to_connect_player(ConnectCommand::PlayerStop);
-                self.notify_local_player(&PlaybackEvent::SourceChanged);
+                self.transfer_to_local_player();
+            }
+        }
+    }
+
+    // Unlike switch_device, the remote device keeps playing what it was told to by someone else
+    fn sync_device(&mut self, device: &Device) {
+        match device {
+            Device::Connect(device) => {
+                self.send_command_to_local_player(Command::PlayerStop);
+                self.send_command_to_connect_player(ConnectCommand::SetDevice(device.id.clone()));
+            }
+            Device::Local => {
+                self.send_command_to_connect_player(ConnectCommand::UnsetDevice);
+                self.transfer_to_local_player();
             }
         }
     }
+
+    // Queued rather than loaded on its own, so that the current context resumes after it
+    fn play_track(&self, id: &str) {
+        let api = self.app_model.get_spotify();
+        let id = id.to_string();
+        self.dispatcher.dispatch_many_async(Box::pin(async move {
+            match api.get_track(&id).await {
+                Ok(song) => vec![
+                    PlaybackAction::Queue(vec![song]).into(),
+                    PlaybackAction::Load(id).into(),
+                ],
+                Err(err) => {
+                    warn!("Could not open track {}: {}", id, err);
+                    vec![AppAction::ShowNotification(gettext("Failed to open link!"))]
+                }
+            }
+        }));
+    }
 }
 
 impl EventListener for PlayerNotifier {
     fn on_event(&mut self, event: &AppEvent) {
+        if matches!(
+            event,
+            AppEvent::PlaybackEvent(PlaybackEvent::TrackChanged(_))
+                | AppEvent::SettingsEvent(SettingsEvent::AutoplayToggled)
+        ) {
+            self.autoplay();
+        }
         let device = self.device().clone();
         match (device, event) {
             (_, AppEvent::LoginEvent(event)) => self.notify_login(event),
+            (_, AppEvent::TrackOpened(id)) => self.play_track(id),
             (_, AppEvent::PlaybackEvent(PlaybackEvent::SwitchedDevice(d))) => self.switch_device(d),
+            (_, AppEvent::PlaybackEvent(PlaybackEvent::DeviceSynced(d))) => self.sync_device(d),
+            (_, AppEvent::PlaybackEvent(PlaybackEvent::SleepTimerChanged)) => {
+                self.update_sleep_timer()
+            }
+            // Also reset when switching to another device, for when we get back to this one
+            (_, AppEvent::PlaybackEvent(PlaybackEvent::RateChanged(rate))) => {
+                self.send_command_to_local_player(Command::PlayerSetRate((*rate).into()))
+            }
             (Device::Local, AppEvent::PlaybackEvent(event)) => self.notify_local_player(event),
             (Device::Local, AppEvent::SettingsEvent(SettingsEvent::PlayerSettingsChanged)) => {
                 self.send_command_to_local_player(Command::ReloadSettings)
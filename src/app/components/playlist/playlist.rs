@@ -26,6 +26,10 @@ pub trait PlaylistModel {
         true
     }
 
+    fn is_autoplayed(&self, _id: &str) -> bool {
+        false
+    }
+
     fn actions_for(&self, _id: &str) -> Option<gio::ActionGroup> {
         None
     }
@@ -61,6 +65,12 @@ pub trait PlaylistModel {
         }
     }
 
+    fn can_reorder(&self) -> bool {
+        false
+    }
+
+    fn move_song(&self, _from: usize, _to: usize) {}
+
     fn toggle_select(&self, id: &str) {
         if let Some(selection) = self.selection() {
             if selection.is_song_selected(id) {
@@ -98,10 +108,12 @@ where
         Self::set_paused(&listview, model.is_paused());
         Self::set_selection_active(&listview, model.is_selection_enabled());
 
-        factory.connect_setup(|_, item| {
+        factory.connect_setup(clone!(@weak model => move |_, item| {
             let item = item.downcast_ref::<gtk::ListItem>().unwrap();
-            item.set_child(Some(&SongWidget::new()));
-        });
+            let widget = SongWidget::new();
+            Self::setup_reordering(&widget, item, &model);
+            item.set_child(Some(&widget));
+        }));
 
         factory.connect_bind(clone!(@weak model => move |_, item| {
             let item = item.downcast_ref::<gtk::ListItem>().unwrap();
@@ -112,6 +124,7 @@ where
             widget.bind(&song_model, worker.clone(), model.show_song_covers());
 
             let id = &song_model.get_id();
+            widget.set_autoplayed(model.is_autoplayed(id));
             widget.set_actions(model.actions_for(id).as_ref());
             widget.set_menu(model.menu_for(id).as_ref());
         }));
@@ -148,6 +161,39 @@ where
         }
     }
 
+    // Rows can be dragged onto one another, the model decides whether that is allowed at all
+    fn setup_reordering(widget: &SongWidget, item: &gtk::ListItem, model: &Rc<Model>) {
+        // Rows are identified by their position, as a playlist can contain the same track twice
+        let drag_source = gtk::DragSource::new();
+        drag_source.set_actions(gdk::DragAction::MOVE);
+        drag_source.connect_prepare(
+            clone!(@weak model, @weak item => @default-return None, move |_, _, _| {
+                let position = item.position();
+                if !model.can_reorder() || position == gtk::INVALID_LIST_POSITION {
+                    return None;
+                }
+                Some(gdk::ContentProvider::for_value(&position.to_value()))
+            }),
+        );
+        widget.add_controller(drag_source);
+
+        let drop_target = gtk::DropTarget::new(u32::static_type(), gdk::DragAction::MOVE);
+        drop_target.connect_drop(
+            clone!(@weak model, @weak item => @default-return false, move |_, value, _, _| {
+                let from = value.get::<u32>().ok();
+                let to = Some(item.position()).filter(|p| *p != gtk::INVALID_LIST_POSITION);
+                match (from, to) {
+                    (Some(from), Some(to)) if from != to => {
+                        model.move_song(from as usize, to as usize);
+                        true
+                    }
+                    _ => false,
+                }
+            }),
+        );
+        widget.add_controller(drop_target);
+    }
+
     fn autoscroll_to_playing(&self, index: usize) {
         let len = self.model.song_list_model().partial_len() as f64;
         let scrolled_window: Option<gtk::ScrolledWindow> = ancestor(&self.listview);
@@ -231,7 +277,9 @@ where
                 self.update_list();
             }
             AppEvent::PlaybackEvent(
-                PlaybackEvent::PlaybackResumed | PlaybackEvent::PlaybackPaused,
+                PlaybackEvent::PlaybackResumed
+                | PlaybackEvent::PlaybackPaused
+                | PlaybackEvent::PlayingSynced(_),
             ) => {
                 Self::set_paused(&self.listview, self.model.is_paused());
             }
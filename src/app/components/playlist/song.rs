@@ -152,6 +152,17 @@ impl SongWidget {
         }
     }
 
+    // Songs added by autoplay rather than by the user
+    pub fn set_autoplayed(&self, autoplayed: bool) {
+        let song_class = "song--autoplayed";
+        let context = self.style_context();
+        if autoplayed {
+            context.add_class(song_class);
+        } else {
+            context.remove_class(song_class);
+        }
+    }
+
     fn set_image(&self, pixbuf: Option<&gdk_pixbuf::Pixbuf>) {
         self.imp().song_cover.set_from_pixbuf(pixbuf);
     }
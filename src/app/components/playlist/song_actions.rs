@@ -19,6 +19,19 @@ impl SongDescription {
         queue
     }
 
+    pub fn make_play_next_action(
+        &self,
+        dispatcher: Box<dyn ActionDispatcher>,
+        name: Option<&str>,
+    ) -> SimpleAction {
+        let play_next = SimpleAction::new(name.unwrap_or("play_next"), None);
+        let song = self.clone();
+        play_next.connect_activate(move |_, _| {
+            dispatcher.dispatch(PlaybackAction::QueueNext(vec![song.clone()]).into());
+        });
+        play_next
+    }
+
     pub fn make_dequeue_action(
         &self,
         dispatcher: Box<dyn ActionDispatcher>,
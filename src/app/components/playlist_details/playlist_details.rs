@@ -204,7 +204,7 @@ impl PlaylistDetails {
             worker.clone(),
         ));
 
-        widget.set_editable(model.is_playlist_editable());
+        widget.set_editable(model.is_playlist_editable() && !model.is_offline());
 
         widget.connect_header();
 
@@ -288,6 +288,11 @@ impl EventListener for PlaylistDetails {
             AppEvent::SelectionEvent(SelectionEvent::SelectionModeChanged(editing)) => {
                 self.set_editing(*editing);
             }
+            // Playlists can't be renamed offline
+            AppEvent::NetworkStatusChanged(status) => {
+                self.widget
+                    .set_editable(self.model.is_playlist_editable() && !status.is_offline());
+            }
             _ => {}
         }
         self.broadcast_event(event);
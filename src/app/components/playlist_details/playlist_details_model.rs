@@ -30,6 +30,10 @@ impl PlaylistDetailsModel {
         state.logged_user.playlists.iter().any(|p| p.id == self.id)
     }
 
+    pub fn is_offline(&self) -> bool {
+        self.app_model.get_state().network.is_offline()
+    }
+
     pub fn get_playlist_info(&self) -> Option<impl Deref<Target = PlaylistDescription> + '_> {
         self.app_model.map_state_opt(|s| {
             s.browser
@@ -153,6 +157,7 @@ impl PlaylistModel for PlaylistDetailsModel {
         }
         group.add_action(&song.make_album_action(self.dispatcher.box_clone(), None));
         group.add_action(&song.make_link_action(None));
+        group.add_action(&song.make_play_next_action(self.dispatcher.box_clone(), None));
         group.add_action(&song.make_queue_action(self.dispatcher.box_clone(), None));
 
         Some(group.upcast())
@@ -172,6 +177,7 @@ impl PlaylistModel for PlaylistDetailsModel {
         }
 
         menu.append(Some(&*labels::COPY_LINK), Some("song.copy_link"));
+        menu.append(Some(&*labels::PLAY_NEXT), Some("song.play_next"));
         menu.append(Some(&*labels::ADD_TO_QUEUE), Some("song.queue"));
 
         Some(menu.upcast())
@@ -203,4 +209,15 @@ impl PlaylistModel for PlaylistDetailsModel {
     fn selection(&self) -> Option<Box<dyn Deref<Target = SelectionState> + '_>> {
         Some(Box::new(self.app_model.map_state(|s| &s.selection)))
     }
+
+    fn can_reorder(&self) -> bool {
+        self.is_playlist_editable() && !self.is_offline()
+    }
+
+    fn move_song(&self, from: usize, to: usize) {
+        let api = self.app_model.get_spotify();
+        let snapshot_id = self.get_playlist_info().and_then(|p| p.snapshot_id.clone());
+        self.dispatcher
+            .move_playlist_track(api, self.id.clone(), from, to, snapshot_id);
+    }
 }
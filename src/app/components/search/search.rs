@@ -4,12 +4,12 @@ use gtk::CompositeTemplate;
 use std::rc::Rc;
 
 use crate::app::components::utils::{wrap_flowbox_item, Debouncer};
-use crate::app::components::{AlbumWidget, ArtistWidget, Component, EventListener};
+use crate::app::components::{AlbumWidget, ArtistWidget, Component, EventListener, Playlist};
 use crate::app::dispatch::Worker;
 use crate::app::models::{AlbumModel, ArtistModel};
 use crate::app::state::{AppEvent, BrowserEvent};
 
-use super::SearchResultsModel;
+use super::{SearchResultsModel, SearchSection};
 mod imp {
 
     use super::*;
@@ -35,8 +35,26 @@ mod imp {
         #[template_child]
         pub albums_results: TemplateChild<gtk::FlowBox>,
 
+        #[template_child]
+        pub albums_more: TemplateChild<gtk::Button>,
+
         #[template_child]
         pub artist_results: TemplateChild<gtk::FlowBox>,
+
+        #[template_child]
+        pub artists_more: TemplateChild<gtk::Button>,
+
+        #[template_child]
+        pub track_results: TemplateChild<gtk::ListView>,
+
+        #[template_child]
+        pub tracks_more: TemplateChild<gtk::Button>,
+
+        #[template_child]
+        pub playlist_results: TemplateChild<gtk::FlowBox>,
+
+        #[template_child]
+        pub playlists_more: TemplateChild<gtk::Button>,
     }
 
     #[glib::object_subclass]
@@ -108,22 +126,82 @@ impl SearchResultsWidget {
             }));
     }
 
+    fn load_more_button(&self, section: SearchSection) -> &gtk::Button {
+        let imp = self.imp();
+        match section {
+            SearchSection::Albums => &imp.albums_more,
+            SearchSection::Artists => &imp.artists_more,
+            SearchSection::Tracks => &imp.tracks_more,
+            SearchSection::Playlists => &imp.playlists_more,
+        }
+    }
+
+    fn connect_load_more<F>(&self, f: F)
+    where
+        F: Fn(SearchSection) + Clone + 'static,
+    {
+        for section in [
+            SearchSection::Albums,
+            SearchSection::Artists,
+            SearchSection::Tracks,
+            SearchSection::Playlists,
+        ] {
+            let f = f.clone();
+            self.load_more_button(section)
+                .connect_clicked(move |_| f(section));
+        }
+    }
+
+    fn set_has_more(&self, section: SearchSection, has_more: bool) {
+        self.load_more_button(section).set_visible(has_more);
+    }
+
+    fn track_results_widget(&self) -> &gtk::ListView {
+        self.imp().track_results.as_ref()
+    }
+
     fn bind_albums_results<F>(&self, worker: Worker, store: &gio::ListStore, on_album_pressed: F)
     where
         F: Fn(String) + Clone + 'static,
     {
-        self.imp()
-            .albums_results
-            .bind_model(Some(store), move |item| {
-                wrap_flowbox_item(item, |album_model| {
-                    let f = on_album_pressed.clone();
-                    let album = AlbumWidget::for_model(album_model, worker.clone());
-                    album.connect_album_pressed(clone!(@weak album_model => move |_| {
-                        f(album_model.uri());
-                    }));
-                    album
-                })
-            });
+        Self::bind_album_flowbox(&self.imp().albums_results, worker, store, on_album_pressed);
+    }
+
+    // Playlists are displayed just like albums
+    fn bind_playlists_results<F>(
+        &self,
+        worker: Worker,
+        store: &gio::ListStore,
+        on_playlist_pressed: F,
+    ) where
+        F: Fn(String) + Clone + 'static,
+    {
+        Self::bind_album_flowbox(
+            &self.imp().playlist_results,
+            worker,
+            store,
+            on_playlist_pressed,
+        );
+    }
+
+    fn bind_album_flowbox<F>(
+        flowbox: &gtk::FlowBox,
+        worker: Worker,
+        store: &gio::ListStore,
+        on_album_pressed: F,
+    ) where
+        F: Fn(String) + Clone + 'static,
+    {
+        flowbox.bind_model(Some(store), move |item| {
+            wrap_flowbox_item(item, |album_model| {
+                let f = on_album_pressed.clone();
+                let album = AlbumWidget::for_model(album_model, worker.clone());
+                album.connect_album_pressed(clone!(@weak album_model => move |_| {
+                    f(album_model.uri());
+                }));
+                album
+            })
+        });
     }
 
     fn bind_artists_results<F>(&self, worker: Worker, store: &gio::ListStore, on_artist_pressed: F)
@@ -150,7 +228,9 @@ pub struct SearchResults {
     model: Rc<SearchResultsModel>,
     album_results_model: gio::ListStore,
     artist_results_model: gio::ListStore,
+    playlist_results_model: gio::ListStore,
     debouncer: Debouncer,
+    children: Vec<Box<dyn EventListener>>,
 }
 
 impl SearchResults {
@@ -160,6 +240,7 @@ impl SearchResults {
 
         let album_results_model = gio::ListStore::new(AlbumModel::static_type());
         let artist_results_model = gio::ListStore::new(ArtistModel::static_type());
+        let playlist_results_model = gio::ListStore::new(AlbumModel::static_type());
 
         widget.bind_to_leaflet(leaflet);
 
@@ -180,19 +261,39 @@ impl SearchResults {
         );
 
         widget.bind_artists_results(
-            worker,
+            worker.clone(),
             &artist_results_model,
             clone!(@weak model => move |id| {
                 model.open_artist(id);
             }),
         );
 
+        widget.bind_playlists_results(
+            worker.clone(),
+            &playlist_results_model,
+            clone!(@weak model => move |id| {
+                model.open_playlist(id);
+            }),
+        );
+
+        widget.connect_load_more(clone!(@weak model => move |section| {
+            model.load_more(section);
+        }));
+
+        let playlist = Playlist::new(
+            widget.track_results_widget().clone(),
+            Rc::clone(&model),
+            worker,
+        );
+
         Self {
             widget,
             model,
             album_results_model,
             artist_results_model,
+            playlist_results_model,
             debouncer: Debouncer::new(),
+            children: vec![Box::new(playlist)],
         }
     }
 
@@ -219,6 +320,22 @@ impl SearchResults {
                 ));
             }
         }
+        if let Some(results) = self.model.get_playlist_results() {
+            self.playlist_results_model.remove_all();
+            for playlist in results.iter() {
+                self.playlist_results_model
+                    .append(&AlbumModel::from(playlist));
+            }
+        }
+        for section in [
+            SearchSection::Albums,
+            SearchSection::Artists,
+            SearchSection::Tracks,
+            SearchSection::Playlists,
+        ] {
+            self.widget
+                .set_has_more(section, self.model.has_more(section));
+        }
     }
 
     fn update_search_query(&self) {
@@ -233,6 +350,10 @@ impl Component for SearchResults {
     fn get_root_widget(&self) -> &gtk::Widget {
         self.widget.as_ref()
     }
+
+    fn get_children(&mut self) -> Option<&mut Vec<Box<dyn EventListener>>> {
+        Some(&mut self.children)
+    }
 }
 
 impl EventListener for SearchResults {
@@ -247,5 +368,6 @@ impl EventListener for SearchResults {
             }
             _ => {}
         }
+        self.broadcast_event(app_event);
     }
 }
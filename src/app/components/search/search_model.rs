@@ -1,9 +1,23 @@
+use gio::prelude::*;
+use gio::SimpleActionGroup;
 use std::ops::Deref;
 use std::rc::Rc;
 
+use crate::app::components::{labels, PlaylistModel};
 use crate::app::dispatch::ActionDispatcher;
 use crate::app::models::*;
-use crate::app::state::{AppAction, AppModel, BrowserAction};
+use crate::app::state::{
+    AppAction, AppModel, BrowserAction, PlaybackAction, SearchState, SelectionAction,
+    SelectionContext, SelectionState,
+};
+
+#[derive(Clone, Copy, Debug)]
+pub enum SearchSection {
+    Albums,
+    Artists,
+    Tracks,
+    Playlists,
+}
 
 pub struct SearchResultsModel {
     app_model: Rc<AppModel>,
@@ -19,8 +33,12 @@ impl SearchResultsModel {
     }
 
     pub fn go_back(&self) {
-        self.dispatcher
-            .dispatch(BrowserAction::NavigationPop.into());
+        if self.is_selection_enabled() {
+            self.dispatcher.dispatch(AppAction::CancelSelection);
+        } else {
+            self.dispatcher
+                .dispatch(BrowserAction::NavigationPop.into());
+        }
     }
 
     pub fn search(&self, query: String) {
@@ -28,6 +46,10 @@ impl SearchResultsModel {
             .dispatch(BrowserAction::Search(query).into());
     }
 
+    fn search_state(&self) -> Option<impl Deref<Target = SearchState> + '_> {
+        self.app_model.map_state_opt(|s| s.browser.search_state())
+    }
+
     fn get_query(&self) -> Option<impl Deref<Target = String> + '_> {
         self.app_model
             .map_state_opt(|s| Some(&s.browser.search_state()?.query).filter(|s| !s.is_empty()))
@@ -35,17 +57,75 @@ impl SearchResultsModel {
 
     pub fn fetch_results(&self) {
         let api = self.app_model.get_spotify();
-        if let Some(query) = self.get_query() {
+        let limit = self.search_state().map(|s| s.batch_size());
+        if let (Some(query), Some(limit)) = (self.get_query(), limit) {
             let query = query.to_owned();
             self.dispatcher
                 .call_spotify_and_dispatch(move || async move {
-                    api.search(&query, 0, 5)
+                    api.search(&query, 0, limit)
                         .await
                         .map(|results| BrowserAction::SetSearchResults(Box::new(results)).into())
                 });
         }
     }
 
+    pub fn has_more(&self, section: SearchSection) -> bool {
+        self.search_state()
+            .map(|state| {
+                let page = match section {
+                    SearchSection::Albums => &state.next_albums_page,
+                    SearchSection::Artists => &state.next_artists_page,
+                    SearchSection::Tracks => &state.next_tracks_page,
+                    SearchSection::Playlists => &state.next_playlists_page,
+                };
+                page.next_offset.is_some()
+            })
+            .unwrap_or(false)
+    }
+
+    pub fn load_more(&self, section: SearchSection) -> Option<()> {
+        let api = self.app_model.get_spotify();
+        let state = self.search_state()?;
+        let next_page = match section {
+            SearchSection::Albums => &state.next_albums_page,
+            SearchSection::Artists => &state.next_artists_page,
+            SearchSection::Tracks => &state.next_tracks_page,
+            SearchSection::Playlists => &state.next_playlists_page,
+        };
+
+        let query = next_page.data.clone();
+        let batch_size = next_page.batch_size;
+        let offset = next_page.next_offset?;
+
+        self.dispatcher
+            .call_spotify_and_dispatch(move || async move {
+                match section {
+                    SearchSection::Albums => api
+                        .search_albums(&query, offset, batch_size)
+                        .await
+                        .map(|albums| BrowserAction::AppendSearchAlbums(query, albums).into()),
+                    SearchSection::Artists => api
+                        .search_artists(&query, offset, batch_size)
+                        .await
+                        .map(|artists| BrowserAction::AppendSearchArtists(query, artists).into()),
+                    SearchSection::Tracks => api
+                        .search_tracks(&query, offset, batch_size)
+                        .await
+                        .map(|song_batch| {
+                            BrowserAction::AppendSearchTracks(query, Box::new(song_batch)).into()
+                        }),
+                    SearchSection::Playlists => api
+                        .search_playlists(&query, offset, batch_size)
+                        .await
+                        .map(|playlists| {
+                            BrowserAction::AppendSearchPlaylists(query, playlists).into()
+                        }),
+                }
+            });
+
+        Some(())
+    }
+
     pub fn get_album_results(&self) -> Option<impl Deref<Target = Vec<AlbumDescription>> + '_> {
         self.app_model
             .map_state_opt(|s| Some(&s.browser.search_state()?.album_results))
@@ -56,6 +136,13 @@ impl SearchResultsModel {
             .map_state_opt(|s| Some(&s.browser.search_state()?.artist_results))
     }
 
+    pub fn get_playlist_results(
+        &self,
+    ) -> Option<impl Deref<Target = Vec<PlaylistDescription>> + '_> {
+        self.app_model
+            .map_state_opt(|s| Some(&s.browser.search_state()?.playlist_results))
+    }
+
     pub fn open_album(&self, id: String) {
         self.dispatcher.dispatch(AppAction::ViewAlbum(id));
     }
@@ -63,4 +150,96 @@ impl SearchResultsModel {
     pub fn open_artist(&self, id: String) {
         self.dispatcher.dispatch(AppAction::ViewArtist(id));
     }
+
+    pub fn open_playlist(&self, id: String) {
+        self.dispatcher.dispatch(AppAction::ViewPlaylist(id));
+    }
+}
+
+impl PlaylistModel for SearchResultsModel {
+    fn song_list_model(&self) -> SongListModel {
+        self.search_state()
+            .expect("illegal attempt to read search_state")
+            .track_results
+            .clone()
+    }
+
+    fn is_paused(&self) -> bool {
+        !self.app_model.get_state().playback.is_playing()
+    }
+
+    fn current_song_id(&self) -> Option<String> {
+        self.app_model.get_state().playback.current_song_id()
+    }
+
+    fn play_song_at(&self, _pos: usize, id: &str) {
+        let tracks: Vec<SongDescription> = self.song_list_model().collect();
+        self.dispatcher
+            .dispatch(PlaybackAction::LoadSongs(tracks).into());
+        self.dispatcher
+            .dispatch(PlaybackAction::Load(id.to_string()).into());
+    }
+
+    fn autoscroll_to_playing(&self) -> bool {
+        false
+    }
+
+    fn actions_for(&self, id: &str) -> Option<gio::ActionGroup> {
+        let song = self.song_list_model().get(id)?;
+        let song = song.description();
+
+        let group = SimpleActionGroup::new();
+
+        for view_artist in song.make_artist_actions(self.dispatcher.box_clone(), None) {
+            group.add_action(&view_artist);
+        }
+        group.add_action(&song.make_album_action(self.dispatcher.box_clone(), None));
+        group.add_action(&song.make_link_action(None));
+        group.add_action(&song.make_play_next_action(self.dispatcher.box_clone(), None));
+        group.add_action(&song.make_queue_action(self.dispatcher.box_clone(), None));
+
+        Some(group.upcast())
+    }
+
+    fn menu_for(&self, id: &str) -> Option<gio::MenuModel> {
+        let song = self.song_list_model().get(id)?;
+        let song = song.description();
+
+        let menu = gio::Menu::new();
+        menu.append(Some(&*labels::VIEW_ALBUM), Some("song.view_album"));
+        for artist in song.artists.iter() {
+            menu.append(
+                Some(&labels::more_from_label(&artist.name)),
+                Some(&format!("song.view_artist_{}", artist.id)),
+            );
+        }
+
+        menu.append(Some(&*labels::COPY_LINK), Some("song.copy_link"));
+        menu.append(Some(&*labels::PLAY_NEXT), Some("song.play_next"));
+        menu.append(Some(&*labels::ADD_TO_QUEUE), Some("song.queue"));
+        Some(menu.upcast())
+    }
+
+    fn select_song(&self, id: &str) {
+        let song = self.song_list_model().get(id);
+        if let Some(song) = song {
+            self.dispatcher
+                .dispatch(SelectionAction::Select(vec![song.into_description()]).into());
+        }
+    }
+
+    fn deselect_song(&self, id: &str) {
+        self.dispatcher
+            .dispatch(SelectionAction::Deselect(vec![id.to_string()]).into());
+    }
+
+    fn enable_selection(&self) -> bool {
+        self.dispatcher
+            .dispatch(AppAction::EnableSelection(SelectionContext::Default));
+        true
+    }
+
+    fn selection(&self) -> Option<Box<dyn Deref<Target = SelectionState> + '_>> {
+        Some(Box::new(self.app_model.map_state(|s| &s.selection)))
+    }
 }
@@ -26,11 +26,21 @@ impl SelectionToolbarModel {
     }
 
     pub fn move_up_selection(&self) {
-        self.dispatcher.dispatch(AppAction::MoveUpSelection);
+        match &self.selection().context {
+            SelectionContext::EditablePlaylist(id) => {
+                self.move_in_playlist(id, -1);
+            }
+            _ => self.dispatcher.dispatch(AppAction::MoveUpSelection),
+        }
     }
 
     pub fn move_down_selection(&self) {
-        self.dispatcher.dispatch(AppAction::MoveDownSelection);
+        match &self.selection().context {
+            SelectionContext::EditablePlaylist(id) => {
+                self.move_in_playlist(id, 1);
+            }
+            _ => self.dispatcher.dispatch(AppAction::MoveDownSelection),
+        }
     }
 
     pub fn queue_selection(&self) {
@@ -87,6 +97,10 @@ impl SelectionToolbarModel {
         self.app_model.map_state(|s| &s.selection)
     }
 
+    fn is_offline(&self) -> bool {
+        self.app_model.get_state().network.is_offline()
+    }
+
     fn selected_count(&self) -> usize {
         self.selection().count()
     }
@@ -111,6 +125,29 @@ impl SelectionToolbarModel {
             })
     }
 
+    fn move_in_playlist(&self, id: &str, offset: isize) -> Option<()> {
+        let api = self.app_model.get_spotify();
+        let (from, to, snapshot_id) = {
+            let state = self.app_model.get_state();
+            let playlist = state.browser.playlist_details_state(id)?;
+            let song = self.selection().peek_selection().next()?.id.clone();
+            // The selection doesn't tell which copy of a duplicated track to move
+            if playlist.songs.count(&song) != 1 {
+                return None;
+            }
+            let from = playlist.songs.find_index(&song)?;
+            let to = from.checked_add_signed(offset)?;
+            if to >= playlist.songs.len() {
+                return None;
+            }
+            let snapshot_id = playlist.playlist.as_ref()?.snapshot_id.clone();
+            (from, to, snapshot_id)
+        };
+        self.dispatcher
+            .move_playlist_track(api, id.to_string(), from, to, snapshot_id);
+        Some(())
+    }
+
     fn remove_from_playlist(&self, id: &str) {
         let api = self.app_model.get_spotify();
         let id = id.to_string();
@@ -192,7 +229,9 @@ impl SelectionToolbar {
                 self.widget.set_save(SelectionToolState::Hidden);
             }
             SelectionContext::EditablePlaylist(_) => {
-                self.widget.set_move(SelectionToolState::Hidden);
+                // Reordering is not queued like other changes while offline
+                let can_move = count == 1 && !self.model.is_offline();
+                self.widget.set_move(SelectionToolState::Visible(can_move));
                 self.widget
                     .set_queue(SelectionToolState::Visible(count > 0));
                 self.widget.set_add(SelectionToolState::Hidden);
@@ -217,7 +256,8 @@ impl EventListener for SelectionToolbar {
                 self.widget.set_visible(*active);
                 self.update_active_tools();
             }
-            AppEvent::SelectionEvent(SelectionEvent::SelectionChanged) => {
+            AppEvent::SelectionEvent(SelectionEvent::SelectionChanged)
+            | AppEvent::NetworkStatusChanged(_) => {
                 self.update_active_tools();
             }
             AppEvent::LoginEvent(LoginEvent::UserPlaylistsLoaded) => {
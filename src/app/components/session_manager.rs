@@ -0,0 +1,95 @@
+use gtk::prelude::*;
+use std::cell::Cell;
+use std::rc::Rc;
+
+use crate::app::components::EventListener;
+use crate::app::state::{LoginEvent, PlaybackAction};
+use crate::app::{ActionDispatcher, AppEvent, AppModel, PlaybackSession};
+
+const SAVE_INTERVAL_SECONDS: u32 = 30;
+
+// Saves the playback session to disk periodically and on shutdown, and brings it back after login
+pub struct SessionManager {
+    app_model: Rc<AppModel>,
+    dispatcher: Box<dyn ActionDispatcher>,
+    // Nothing gets saved until the previous session had a chance to be restored
+    active: Rc<Cell<bool>>,
+    dirty: Rc<Cell<bool>>,
+}
+
+impl SessionManager {
+    pub fn new(
+        app_model: Rc<AppModel>,
+        dispatcher: Box<dyn ActionDispatcher>,
+        window: libadwaita::ApplicationWindow,
+    ) -> Self {
+        let active = Rc::new(Cell::new(false));
+        let dirty = Rc::new(Cell::new(false));
+
+        glib::timeout_add_seconds_local(
+            SAVE_INTERVAL_SECONDS,
+            clone!(@weak app_model, @strong active, @strong dirty => @default-return glib::Continue(false), move || {
+                let playing = app_model.get_state().playback.is_playing();
+                if dirty.get() || playing {
+                    Self::save(&app_model, &active, &dirty);
+                }
+                glib::Continue(true)
+            }),
+        );
+
+        window.connect_unrealize(
+            clone!(@weak app_model, @strong active, @strong dirty => move |_| {
+                debug!("saving playback session");
+                Self::save(&app_model, &active, &dirty);
+            }),
+        );
+
+        Self {
+            app_model,
+            dispatcher,
+            active,
+            dirty,
+        }
+    }
+
+    fn save(app_model: &AppModel, active: &Cell<bool>, dirty: &Cell<bool>) {
+        if !active.get() {
+            return;
+        }
+        let session = app_model.get_state().playback.session();
+        match session.save() {
+            Ok(_) => dirty.set(false),
+            Err(e) => warn!("Could not save playback session: {}", e),
+        }
+    }
+
+    fn restore(&self) {
+        if self.active.replace(true) {
+            return;
+        }
+        let has_songs = self.app_model.get_state().playback.songs().len() > 0;
+        if let Some(session) = PlaybackSession::load().filter(|_| !has_songs) {
+            self.dispatcher
+                .dispatch(PlaybackAction::RestoreSession(Box::new(session)).into());
+        }
+    }
+
+    fn forget(&self) {
+        self.active.set(false);
+        self.dirty.set(false);
+        if let Err(e) = PlaybackSession::clear() {
+            warn!("Could not remove playback session: {}", e);
+        }
+    }
+}
+
+impl EventListener for SessionManager {
+    fn on_event(&mut self, event: &AppEvent) {
+        match event {
+            AppEvent::LoginEvent(LoginEvent::LoginCompleted(_)) => self.restore(),
+            AppEvent::LoginEvent(LoginEvent::LogoutCompleted) => self.forget(),
+            AppEvent::PlaybackEvent(_) => self.dirty.set(true),
+            _ => {}
+        }
+    }
+}
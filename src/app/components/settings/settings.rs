@@ -1,7 +1,12 @@
-use crate::app::components::EventListener;
-use crate::app::AppEvent;
+use crate::api::cache::{CacheManager, IMAGE_CACHE_DIR, NETWORK_CACHE_DIR};
+use crate::app::components::{labels, EventListener};
+use crate::app::{AppEvent, Worker};
+use crate::player::{list_alsa_devices, list_pulseaudio_sinks, AudioDevice};
 use crate::settings::SpotSettings;
 
+use gettextrs::gettext;
+use std::cell::{Cell, RefCell};
+
 use gtk::prelude::*;
 use gtk::subclass::prelude::*;
 use gtk::CompositeTemplate;
@@ -11,6 +16,13 @@ use super::SettingsModel;
 
 const SETTINGS: &str = "dev.alextren.Spot";
 
+// Positions in the audio backend dropdown
+const PULSEAUDIO_BACKEND: u32 = 0;
+const ALSA_BACKEND: u32 = 1;
+const GSTREAMER_BACKEND: u32 = 2;
+const PIPE_BACKEND: u32 = 3;
+const SUBPROCESS_BACKEND: u32 = 4;
+
 mod imp {
 
     use super::*;
@@ -23,22 +35,104 @@ mod imp {
         pub player_bitrate: TemplateChild<libadwaita::ComboRow>,
 
         #[template_child]
-        pub alsa_device: TemplateChild<gtk::Entry>,
+        pub output_device: TemplateChild<libadwaita::ComboRow>,
+
+        #[template_child]
+        pub gstreamer_pipeline_row: TemplateChild<libadwaita::ActionRow>,
 
         #[template_child]
-        pub alsa_device_row: TemplateChild<libadwaita::ActionRow>,
+        pub gstreamer_pipeline: TemplateChild<gtk::Entry>,
+
+        #[template_child]
+        pub pipe_path_row: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub pipe_path: TemplateChild<gtk::Entry>,
+
+        #[template_child]
+        pub subprocess_command_row: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub subprocess_command: TemplateChild<gtk::Entry>,
 
         #[template_child]
         pub audio_backend: TemplateChild<libadwaita::ComboRow>,
 
+        #[template_child]
+        pub alsa_hardware_mixer: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub alsa_mixer_control_row: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub alsa_mixer_control: TemplateChild<gtk::Entry>,
+
+        #[template_child]
+        pub volume_curve: TemplateChild<libadwaita::ComboRow>,
+
+        #[template_child]
+        pub volume_db_range_row: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub volume_db_range: TemplateChild<gtk::SpinButton>,
+
         #[template_child]
         pub gapless_playback: TemplateChild<libadwaita::ActionRow>,
 
+        #[template_child]
+        pub crossfade_duration: TemplateChild<gtk::SpinButton>,
+
+        #[template_child]
+        pub autoplay: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub volume_normalisation: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub normalisation_type: TemplateChild<libadwaita::ComboRow>,
+
+        #[template_child]
+        pub normalisation_pregain_row: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub normalisation_pregain: TemplateChild<gtk::SpinButton>,
+
+        #[template_child]
+        pub normalisation_threshold_row: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub normalisation_threshold: TemplateChild<gtk::SpinButton>,
+
         #[template_child]
         pub ap_port: TemplateChild<gtk::Entry>,
 
+        #[template_child]
+        pub connect_receiver: TemplateChild<libadwaita::ActionRow>,
+
         #[template_child]
         pub theme: TemplateChild<libadwaita::ComboRow>,
+
+        #[template_child]
+        pub network_cache_row: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub network_cache_max_size: TemplateChild<gtk::SpinButton>,
+
+        #[template_child]
+        pub network_cache_clear: TemplateChild<gtk::Button>,
+
+        #[template_child]
+        pub image_cache_row: TemplateChild<libadwaita::ActionRow>,
+
+        #[template_child]
+        pub image_cache_max_size: TemplateChild<gtk::SpinButton>,
+
+        #[template_child]
+        pub image_cache_clear: TemplateChild<gtk::Button>,
+
+        // What's listed in the output device dropdown
+        pub output_devices: RefCell<Vec<AudioDevice>>,
+        pub updating_output_devices: Cell<bool>,
     }
 
     #[glib::object_subclass]
@@ -72,6 +166,7 @@ impl SettingsWindow {
         let window: Self = glib::Object::new();
 
         window.bind_backend_and_device();
+        window.bind_normalisation();
         window.bind_settings();
         window.connect_theme_select();
         window
@@ -84,18 +179,72 @@ impl SettingsWindow {
             .audio_backend
             .downcast_ref::<libadwaita::ComboRow>()
             .unwrap();
-        let alsa_device_row = widget
-            .alsa_device_row
-            .downcast_ref::<libadwaita::ActionRow>()
-            .unwrap();
+        audio_backend
+            .bind_property("selected", &*widget.output_device, "visible")
+            .transform_to(|_, value: u32| Some(Self::output_device_key(value).is_some()))
+            .flags(glib::BindingFlags::SYNC_CREATE)
+            .build();
+
+        audio_backend
+            .bind_property("selected", &*widget.gstreamer_pipeline_row, "visible")
+            .transform_to(|_, value: u32| Some(value == GSTREAMER_BACKEND))
+            .flags(glib::BindingFlags::SYNC_CREATE)
+            .build();
+
+        audio_backend
+            .bind_property("selected", &*widget.pipe_path_row, "visible")
+            .transform_to(|_, value: u32| Some(value == PIPE_BACKEND))
+            .flags(glib::BindingFlags::SYNC_CREATE)
+            .build();
 
         audio_backend
-            .bind_property("selected", alsa_device_row, "visible")
+            .bind_property("selected", &*widget.subprocess_command_row, "visible")
+            .transform_to(|_, value: u32| Some(value == SUBPROCESS_BACKEND))
+            .flags(glib::BindingFlags::SYNC_CREATE)
+            .build();
+
+        let alsa_hardware_mixer = &*widget.alsa_hardware_mixer;
+        audio_backend
+            .bind_property("selected", alsa_hardware_mixer, "visible")
+            .transform_to(|_, value: u32| Some(value == ALSA_BACKEND))
+            .flags(glib::BindingFlags::SYNC_CREATE)
+            .build();
+
+        // The control only matters when the hardware mixer is used
+        let alsa_mixer_control_row = widget.alsa_mixer_control_row.get();
+        let hardware_mixer_enabled = alsa_hardware_mixer.activatable_widget().unwrap();
+        let update_control_row = clone!(@weak audio_backend, @weak hardware_mixer_enabled, @weak alsa_mixer_control_row => move || {
+            let enabled = hardware_mixer_enabled.property::<bool>("active");
+            alsa_mixer_control_row.set_visible(audio_backend.selected() == ALSA_BACKEND && enabled);
+        });
+        update_control_row();
+        audio_backend.connect_selected_notify(
+            clone!(@strong update_control_row => move |_| update_control_row()),
+        );
+        hardware_mixer_enabled
+            .connect_notify_local(Some("active"), move |_, _| update_control_row());
+
+        widget
+            .volume_curve
+            .bind_property("selected", &*widget.volume_db_range_row, "visible")
             .transform_to(|_, value: u32| Some(value == 1))
+            .flags(glib::BindingFlags::SYNC_CREATE)
             .build();
+    }
 
-        if audio_backend.selected() == 0 {
-            alsa_device_row.set_visible(false);
+    fn bind_normalisation(&self) {
+        let widget = self.imp();
+        let enabled = widget.volume_normalisation.activatable_widget().unwrap();
+        let rows: [&gtk::Widget; 3] = [
+            widget.normalisation_type.upcast_ref(),
+            widget.normalisation_pregain_row.upcast_ref(),
+            widget.normalisation_threshold_row.upcast_ref(),
+        ];
+        for row in rows {
+            enabled
+                .bind_property("active", row, "sensitive")
+                .flags(glib::BindingFlags::SYNC_CREATE)
+                .build();
         }
     }
 
@@ -133,8 +282,17 @@ impl SettingsWindow {
             })
             .build();
 
-        let alsa_device = widget.alsa_device.downcast_ref::<gtk::Entry>().unwrap();
-        settings.bind("alsa-device", alsa_device, "text").build();
+        settings
+            .bind("gstreamer-pipeline", &*widget.gstreamer_pipeline, "text")
+            .build();
+
+        settings
+            .bind("pipe-path", &*widget.pipe_path, "text")
+            .build();
+
+        settings
+            .bind("subprocess-command", &*widget.subprocess_command, "text")
+            .build();
 
         let audio_backend = widget
             .audio_backend
@@ -148,6 +306,8 @@ impl SettingsWindow {
                         "pulseaudio" => 0,
                         "alsa" => 1,
                         "gstreamer" => 2,
+                        "pipe" => 3,
+                        "subprocess" => 4,
                         _ => unreachable!(),
                     }
                     .to_value()
@@ -159,6 +319,8 @@ impl SettingsWindow {
                         0 => "pulseaudio",
                         1 => "alsa",
                         2 => "gstreamer",
+                        3 => "pipe",
+                        4 => "subprocess",
                         _ => unreachable!(),
                     }
                     .to_variant()
@@ -166,6 +328,48 @@ impl SettingsWindow {
             })
             .build();
 
+        settings
+            .bind(
+                "alsa-hardware-mixer",
+                &widget.alsa_hardware_mixer.activatable_widget().unwrap(),
+                "active",
+            )
+            .build();
+
+        settings
+            .bind("alsa-mixer-control", &*widget.alsa_mixer_control, "text")
+            .build();
+
+        settings
+            .bind("volume-curve", &*widget.volume_curve, "selected")
+            .mapping(|variant, _| {
+                variant.str().map(|s| {
+                    match s {
+                        "linear" => 0,
+                        "log" => 1,
+                        "fixed" => 2,
+                        _ => unreachable!(),
+                    }
+                    .to_value()
+                })
+            })
+            .set_mapping(|value, _| {
+                value.get::<u32>().ok().map(|u| {
+                    match u {
+                        0 => "linear",
+                        1 => "log",
+                        2 => "fixed",
+                        _ => unreachable!(),
+                    }
+                    .to_variant()
+                })
+            })
+            .build();
+
+        settings
+            .bind("volume-db-range", &*widget.volume_db_range, "value")
+            .build();
+
         let gapless_playback = widget
             .gapless_playback
             .downcast_ref::<libadwaita::ActionRow>()
@@ -178,6 +382,74 @@ impl SettingsWindow {
             )
             .build();
 
+        settings
+            .bind("crossfade-duration", &*widget.crossfade_duration, "value")
+            .mapping(|variant, _| variant.get::<u32>().map(|u| (u as f64).to_value()))
+            .set_mapping(|value, _| value.get::<f64>().ok().map(|f| (f as u32).to_variant()))
+            .build();
+
+        settings
+            .bind(
+                "autoplay",
+                &widget.autoplay.activatable_widget().unwrap(),
+                "active",
+            )
+            .build();
+
+        settings
+            .bind(
+                "volume-normalisation",
+                &widget.volume_normalisation.activatable_widget().unwrap(),
+                "active",
+            )
+            .build();
+
+        settings
+            .bind(
+                "normalisation-type",
+                &*widget.normalisation_type,
+                "selected",
+            )
+            .mapping(|variant, _| {
+                variant.str().map(|s| {
+                    match s {
+                        "auto" => 0,
+                        "track" => 1,
+                        "album" => 2,
+                        _ => unreachable!(),
+                    }
+                    .to_value()
+                })
+            })
+            .set_mapping(|value, _| {
+                value.get::<u32>().ok().map(|u| {
+                    match u {
+                        0 => "auto",
+                        1 => "track",
+                        2 => "album",
+                        _ => unreachable!(),
+                    }
+                    .to_variant()
+                })
+            })
+            .build();
+
+        settings
+            .bind(
+                "normalisation-pregain",
+                &*widget.normalisation_pregain,
+                "value",
+            )
+            .build();
+
+        settings
+            .bind(
+                "normalisation-threshold",
+                &*widget.normalisation_threshold,
+                "value",
+            )
+            .build();
+
         let ap_port = widget.ap_port.downcast_ref::<gtk::Entry>().unwrap();
         settings
             .bind("ap-port", ap_port, "text")
@@ -185,6 +457,34 @@ impl SettingsWindow {
             .set_mapping(|value, _| value.get::<u32>().ok().map(|u| u.to_variant()))
             .build();
 
+        settings
+            .bind(
+                "connect-receiver",
+                &widget.connect_receiver.activatable_widget().unwrap(),
+                "active",
+            )
+            .build();
+
+        settings
+            .bind(
+                "network-cache-max-size",
+                &*widget.network_cache_max_size,
+                "value",
+            )
+            .mapping(|variant, _| variant.get::<u32>().map(|u| (u as f64).to_value()))
+            .set_mapping(|value, _| value.get::<f64>().ok().map(|f| (f as u32).to_variant()))
+            .build();
+
+        settings
+            .bind(
+                "image-cache-max-size",
+                &*widget.image_cache_max_size,
+                "value",
+            )
+            .mapping(|variant, _| variant.get::<u32>().map(|u| (u as f64).to_value()))
+            .set_mapping(|value, _| value.get::<f64>().ok().map(|f| (f as u32).to_variant()))
+            .build();
+
         let theme = widget.theme.downcast_ref::<libadwaita::ComboRow>().unwrap();
         settings
             .bind("theme-preference", theme, "selected")
@@ -230,6 +530,141 @@ impl SettingsWindow {
         });
     }
 
+    // The settings key the output device dropdown stands for, given the audio backend
+    fn output_device_key(backend: u32) -> Option<&'static str> {
+        match backend {
+            PULSEAUDIO_BACKEND => Some("pulseaudio-sink"),
+            ALSA_BACKEND => Some("alsa-device"),
+            _ => None,
+        }
+    }
+
+    fn set_output_devices(&self, backend: u32, mut devices: Vec<AudioDevice>) {
+        let widget = self.imp();
+        let Some(key) = Self::output_device_key(backend) else {
+            return;
+        };
+        let current = gio::Settings::new(SETTINGS).string(key).to_string();
+
+        if backend == PULSEAUDIO_BACKEND {
+            devices.insert(
+                0,
+                AudioDevice {
+                    id: String::new(),
+                    // translators: This is the first choice in the list of output devices, to play to whatever the system uses by default.
+                    description: gettext("Default"),
+                },
+            );
+        }
+        // Devices that aren't around right now shouldn't get lost by just opening the preferences
+        if !devices.iter().any(|device| device.id == current) {
+            devices.push(AudioDevice {
+                id: current.clone(),
+                description: current.clone(),
+            });
+        }
+
+        let descriptions: Vec<&str> = devices.iter().map(|d| d.description.as_str()).collect();
+        let selected = devices.iter().position(|d| d.id == current).unwrap_or(0);
+
+        widget.updating_output_devices.set(true);
+        widget
+            .output_device
+            .set_model(Some(&gtk::StringList::new(&descriptions[..])));
+        widget.output_device.set_selected(selected as u32);
+        widget.output_devices.replace(devices);
+        widget.updating_output_devices.set(false);
+    }
+
+    fn update_output_devices(&self, worker: &Worker) {
+        let backend = self.imp().audio_backend.selected();
+        if Self::output_device_key(backend).is_none() {
+            return;
+        }
+        worker.send_local_task(clone!(@weak self as window => async move {
+            // Listing devices means talking to the sound server, don't block the UI on that
+            let devices = async_std::task::spawn_blocking(move || match backend {
+                PULSEAUDIO_BACKEND => list_pulseaudio_sinks(),
+                _ => list_alsa_devices(),
+            })
+            .await;
+            // The backend might have changed in the meantime
+            if window.imp().audio_backend.selected() == backend {
+                window.set_output_devices(backend, devices);
+            }
+        }));
+    }
+
+    fn connect_output_devices(&self, worker: Worker) {
+        let widget = self.imp();
+        widget
+            .output_device
+            .connect_selected_notify(clone!(@weak self as window => move |row| {
+                let widget = window.imp();
+                if widget.updating_output_devices.get() {
+                    return;
+                }
+                let backend = widget.audio_backend.selected();
+                let devices = widget.output_devices.borrow();
+                let device = devices.get(row.selected() as usize);
+                if let (Some(key), Some(device)) = (Self::output_device_key(backend), device) {
+                    let _ = gio::Settings::new(SETTINGS).set_string(key, &device.id);
+                }
+            }));
+
+        widget
+            .audio_backend
+            .connect_selected_notify(clone!(@weak self as window => move |_| {
+                window.update_output_devices(&worker);
+            }));
+    }
+
+    fn cache_rows(&self) -> [(&'static str, libadwaita::ActionRow, gtk::Button); 2] {
+        let widget = self.imp();
+        [
+            (
+                NETWORK_CACHE_DIR,
+                widget.network_cache_row.get(),
+                widget.network_cache_clear.get(),
+            ),
+            (
+                IMAGE_CACHE_DIR,
+                widget.image_cache_row.get(),
+                widget.image_cache_clear.get(),
+            ),
+        ]
+    }
+
+    fn update_cache_usage(&self, worker: &Worker) {
+        for (dir, row, _) in self.cache_rows() {
+            worker.send_local_task(async move {
+                let usage = match CacheManager::for_dir(dir) {
+                    Some(cache) => cache.usage().await.unwrap_or(0),
+                    None => 0,
+                };
+                row.set_subtitle(&labels::cache_usage_label(usage));
+            });
+        }
+    }
+
+    fn connect_clear_cache(&self, worker: Worker) {
+        for (dir, row, button) in self.cache_rows() {
+            button.connect_clicked(clone!(@strong worker => move |button| {
+                button.set_sensitive(false);
+                worker.send_local_task(clone!(@weak button, @weak row => async move {
+                    if let Some(cache) = CacheManager::for_dir(dir) {
+                        if let Err(e) = cache.clear_all().await {
+                            warn!("Could not clear {}: {}", dir, e);
+                        }
+                        let usage = cache.usage().await.unwrap_or(0);
+                        row.set_subtitle(&labels::cache_usage_label(usage));
+                    }
+                    button.set_sensitive(true);
+                }));
+            }));
+        }
+    }
+
     fn connect_close<F>(&self, on_close: F)
     where
         F: Fn() + 'static,
@@ -248,11 +683,14 @@ impl SettingsWindow {
 pub struct Settings {
     parent: gtk::Window,
     settings_window: SettingsWindow,
+    worker: Worker,
 }
 
 impl Settings {
-    pub fn new(parent: gtk::Window, model: SettingsModel) -> Self {
+    pub fn new(parent: gtk::Window, model: SettingsModel, worker: Worker) -> Self {
         let settings_window = SettingsWindow::new();
+        settings_window.connect_clear_cache(worker.clone());
+        settings_window.connect_output_devices(worker.clone());
 
         settings_window.connect_close(move || {
             let new_settings = SpotSettings::new_from_gsettings().unwrap_or_default();
@@ -265,6 +703,7 @@ impl Settings {
         Self {
             parent,
             settings_window,
+            worker,
         }
     }
 
@@ -276,6 +715,8 @@ impl Settings {
         self.window().set_transient_for(Some(&self.parent));
         self.window().set_modal(true);
         self.window().show();
+        self.settings_window.update_cache_usage(&self.worker);
+        self.settings_window.update_output_devices(&self.worker);
     }
 }
 
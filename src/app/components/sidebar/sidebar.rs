@@ -51,6 +51,10 @@ impl SidebarModel {
         SidebarDestination::Playlist(PlaylistSummary { id, title })
     }
 
+    fn is_offline(&self) -> bool {
+        self.app_model.get_state().network.is_offline()
+    }
+
     fn create_new_playlist(&self, name: String) {
         let user_id = self.app_model.get_state().logged_user.user.clone().unwrap();
         let api = self.app_model.get_spotify();
@@ -183,6 +187,18 @@ impl Sidebar {
             playlists.as_slice(),
         );
     }
+
+    // Playlists can't be created offline
+    fn update_create_playlist(&self) {
+        let offline = self.model.is_offline();
+        let row = (0..NUM_FIXED_ENTRIES as i32)
+            .filter_map(|i| self.listbox.row_at_index(i))
+            .filter_map(|row| row.downcast::<SidebarRow>().ok())
+            .find(|row| row.item().id() == CREATE_PLAYLIST_ITEM);
+        if let Some(row) = row {
+            row.set_sensitive(!offline);
+        }
+    }
 }
 
 impl Component for Sidebar {
@@ -193,8 +209,12 @@ impl Component for Sidebar {
 
 impl EventListener for Sidebar {
     fn on_event(&mut self, event: &AppEvent) {
-        if let AppEvent::BrowserEvent(BrowserEvent::SavedPlaylistsUpdated) = event {
-            self.update_playlists_in_sidebar();
+        match event {
+            AppEvent::BrowserEvent(BrowserEvent::SavedPlaylistsUpdated) => {
+                self.update_playlists_in_sidebar();
+            }
+            AppEvent::NetworkStatusChanged(_) => self.update_create_playlist(),
+            _ => {}
         }
     }
 }
@@ -1,14 +1,17 @@
 use gettextrs::*;
 use gio::{prelude::ActionMapExt, SimpleAction, SimpleActionGroup};
+use glib::ToVariant;
 use gtk::prelude::*;
 use std::rc::Rc;
 
 use super::UserMenuModel;
 use crate::app::components::{EventListener, Settings};
-use crate::app::{state::LoginEvent, AppEvent};
+use crate::app::state::{LoginEvent, NetworkStatus};
+use crate::app::AppEvent;
 
 pub struct UserMenu {
     user_button: gtk::MenuButton,
+    work_offline: SimpleAction,
     model: Rc<UserMenuModel>,
 }
 
@@ -38,6 +41,13 @@ impl UserMenu {
             logout
         });
 
+        let work_offline = SimpleAction::new_stateful("work_offline", None, false.to_variant());
+        work_offline.connect_activate(clone!(@weak model => move |action, _| {
+            let offline = action.state().and_then(|s| s.get::<bool>()).unwrap_or(false);
+            model.set_work_offline(!offline);
+        }));
+        action_group.add_action(&work_offline);
+
         action_group.add_action(&{
             let settings_action = SimpleAction::new("settings", None);
             settings_action.connect_activate(clone!(@weak model => move |_, _| {
@@ -56,12 +66,18 @@ impl UserMenu {
 
         user_button.insert_action_group("menu", Some(&action_group));
 
-        Self { user_button, model }
+        Self {
+            user_button,
+            work_offline,
+            model,
+        }
     }
 
     fn update_menu(&self) {
         let menu = gio::Menu::new();
         // translators: This is a menu entry.
+        menu.append(Some(&gettext("Work Offline")), Some("menu.work_offline"));
+        // translators: This is a menu entry.
         menu.append(Some(&gettext("Preferences")), Some("menu.settings"));
         // translators: This is a menu entry.
         menu.append(Some(&gettext("About")), Some("menu.about"));
@@ -86,6 +102,10 @@ impl EventListener for UserMenu {
                 self.update_menu();
                 self.model.fetch_user_playlists();
             }
+            AppEvent::NetworkStatusChanged(status) => {
+                let working_offline = *status == NetworkStatus::WorkingOffline;
+                self.work_offline.set_state(working_offline.to_variant());
+            }
             _ => {}
         }
     }
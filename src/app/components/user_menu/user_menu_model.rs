@@ -1,7 +1,7 @@
 use crate::api::clear_user_cache;
 use crate::app::credentials::Credentials;
 use crate::app::state::{LoginAction, PlaybackAction};
-use crate::app::{ActionDispatcher, AppModel};
+use crate::app::{ActionDispatcher, AppAction, AppModel};
 use std::ops::Deref;
 use std::rc::Rc;
 
@@ -25,6 +25,8 @@ impl UserMenuModel {
 
     pub fn logout(&self) {
         self.dispatcher.dispatch(PlaybackAction::Stop.into());
+        // Whatever wasn't sent belongs to the user logging out
+        self.app_model.get_spotify().clear_pending_writes();
         self.dispatcher.dispatch_async(Box::pin(async {
             let _ = Credentials::logout().await;
             let _ = clear_user_cache().await;
@@ -32,6 +34,10 @@ impl UserMenuModel {
         }));
     }
 
+    pub fn set_work_offline(&self, offline: bool) {
+        self.dispatcher.dispatch(AppAction::SetWorkOffline(offline));
+    }
+
     pub fn fetch_user_playlists(&self) {
         let api = self.app_model.get_spotify();
         if let Some(current_user) = self.username() {
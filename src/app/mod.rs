@@ -1,4 +1,4 @@
-use crate::api::CachedSpotifyClient;
+use crate::api::{CachedSpotifyClient, SpotifyApiClient};
 use crate::settings::SpotSettings;
 use futures::channel::mpsc::UnboundedSender;
 use std::rc::Rc;
@@ -16,6 +16,7 @@ mod list_store;
 pub use list_store::*;
 
 pub mod state;
+use state::LoginAction;
 pub use state::{AppAction, AppEvent, AppModel, AppState, BrowserAction, BrowserEvent};
 
 mod batch_loader;
@@ -27,6 +28,9 @@ pub mod loader;
 pub mod rng;
 pub use rng::LazyRandomIndex;
 
+mod session;
+pub use session::PlaybackSession;
+
 pub struct App {
     settings: SpotSettings,
     builder: gtk::Builder,
@@ -45,6 +49,14 @@ impl App {
     ) -> Self {
         let state = AppState::new();
         let spotify_client = Arc::new(CachedSpotifyClient::new());
+        let refresh_sender = sender.clone();
+        spotify_client.set_token_refresher(Box::new(move || {
+            let _ = refresh_sender.unbounded_send(LoginAction::RefreshToken.into());
+        }));
+        let offline_sender = sender.clone();
+        spotify_client.set_offline_listener(Box::new(move || {
+            let _ = offline_sender.unbounded_send(AppAction::SetNetworkAvailable(false));
+        }));
         let model = Rc::new(AppModel::new(state, spotify_client));
 
         let components: Vec<Box<dyn EventListener>> = vec![
@@ -55,6 +67,16 @@ impl App {
                 sender.clone(),
             ),
             App::make_dbus(Rc::clone(&model), sender.clone()),
+            App::make_session_manager(
+                &builder,
+                Rc::clone(&model),
+                Box::new(ActionDispatcherImpl::new(sender.clone(), worker.clone())),
+            ),
+            App::make_offline_manager(
+                Rc::clone(&model),
+                Box::new(ActionDispatcherImpl::new(sender.clone(), worker.clone())),
+            ),
+            App::make_cache_cleaner(Rc::clone(&model), worker.clone()),
         ];
 
         Self {
@@ -91,7 +113,8 @@ impl App {
                 worker.clone(),
             ),
             App::make_search_button(builder, dispatcher.box_clone()),
-            App::make_user_menu(builder, Rc::clone(model), dispatcher),
+            App::make_offline_banner(builder, Rc::clone(model), dispatcher.box_clone()),
+            App::make_user_menu(builder, Rc::clone(model), dispatcher, worker.clone()),
             App::make_notification(builder),
         ];
 
@@ -105,11 +128,16 @@ impl App {
         sender: UnboundedSender<AppAction>,
     ) -> Box<impl EventListener> {
         let api = app_model.get_spotify();
+        let connect_sender = crate::connect::start_connect_server(api, sender.clone());
         Box::new(PlayerNotifier::new(
             app_model,
             dispatcher,
-            crate::player::start_player_service(settings.player_settings.clone(), sender.clone()),
-            crate::connect::start_connect_server(api, sender),
+            crate::player::start_player_service(
+                settings.player_settings.clone(),
+                sender,
+                connect_sender.clone(),
+            ),
+            connect_sender,
         ))
     }
 
@@ -120,6 +148,26 @@ impl App {
         Box::new(crate::dbus::start_dbus_server(app_model, sender))
     }
 
+    fn make_session_manager(
+        builder: &gtk::Builder,
+        app_model: Rc<AppModel>,
+        dispatcher: Box<dyn ActionDispatcher>,
+    ) -> Box<SessionManager> {
+        let window: libadwaita::ApplicationWindow = builder.object("window").unwrap();
+        Box::new(SessionManager::new(app_model, dispatcher, window))
+    }
+
+    fn make_offline_manager(
+        app_model: Rc<AppModel>,
+        dispatcher: Box<dyn ActionDispatcher>,
+    ) -> Box<OfflineManager> {
+        Box::new(OfflineManager::new(app_model, dispatcher))
+    }
+
+    fn make_cache_cleaner(app_model: Rc<AppModel>, worker: Worker) -> Box<CacheCleaner> {
+        Box::new(CacheCleaner::new(app_model, worker))
+    }
+
     fn make_window(
         settings: &SpotSettings,
         builder: &gtk::Builder,
@@ -202,10 +250,11 @@ impl App {
         builder: &gtk::Builder,
         app_model: Rc<AppModel>,
         dispatcher: Box<dyn ActionDispatcher>,
+        worker: Worker,
     ) -> Box<UserMenu> {
         let parent: gtk::Window = builder.object("window").unwrap();
         let settings_model = SettingsModel::new(app_model.clone(), dispatcher.box_clone());
-        let settings = Settings::new(parent, settings_model);
+        let settings = Settings::new(parent, settings_model, worker);
 
         let button: gtk::MenuButton = builder.object("user").unwrap();
         let about: gtk::AboutDialog = builder.object("about").unwrap();
@@ -214,6 +263,19 @@ impl App {
         Box::new(user_menu)
     }
 
+    fn make_offline_banner(
+        builder: &gtk::Builder,
+        app_model: Rc<AppModel>,
+        dispatcher: Box<dyn ActionDispatcher>,
+    ) -> Box<OfflineBanner> {
+        let revealer: gtk::Revealer = builder.object("offline_banner").unwrap();
+        let label: gtk::Label = builder.object("offline_label").unwrap();
+        let button: gtk::Button = builder.object("offline_button").unwrap();
+        Box::new(OfflineBanner::new(
+            app_model, dispatcher, revealer, label, button,
+        ))
+    }
+
     fn make_notification(builder: &gtk::Builder) -> Box<Notification> {
         let toast_overlay: libadwaita::ToastOverlay = builder.object("main").unwrap();
         Box::new(Notification::new(toast_overlay))
@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use std::{
     hash::{Hash, Hasher},
     str::FromStr,
@@ -5,7 +6,7 @@ use std::{
 
 use crate::app::SongsSource;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Batch {
     pub offset: usize,
     pub batch_size: usize,
@@ -43,13 +44,13 @@ pub struct UserRef {
     pub display_name: String,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ArtistRef {
     pub id: String,
     pub name: String,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AlbumRef {
     pub id: String,
     pub name: String,
@@ -59,6 +60,8 @@ pub struct AlbumRef {
 pub struct SearchResults {
     pub albums: Vec<AlbumDescription>,
     pub artists: Vec<ArtistSummary>,
+    pub tracks: SongBatch,
+    pub playlists: Vec<PlaylistDescription>,
 }
 
 #[derive(Clone, Debug)]
@@ -109,6 +112,7 @@ pub struct PlaylistDescription {
     pub art: Option<String>,
     pub songs: SongBatch,
     pub owner: UserRef,
+    pub snapshot_id: Option<String>,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -132,7 +136,7 @@ pub struct PlaylistSummary {
     pub title: String,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SongDescription {
     pub id: String,
     pub track_number: Option<u32>,
@@ -166,7 +170,7 @@ pub struct SongState {
     pub is_selected: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SongBatch {
     pub songs: Vec<SongDescription>,
     pub batch: Batch,
@@ -236,7 +240,7 @@ pub struct UserDescription {
     pub playlists: Vec<PlaylistDescription>,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum RepeatMode {
     Song,
     Playlist,
@@ -251,6 +255,8 @@ pub struct ConnectPlayerState {
     pub progress_ms: u32,
     pub repeat: RepeatMode,
     pub shuffle: bool,
+    // The device that is currently playing
+    pub device_id: Option<String>,
 }
 
 impl Default for ConnectPlayerState {
@@ -262,6 +268,7 @@ impl Default for ConnectPlayerState {
             progress_ms: 0,
             repeat: RepeatMode::None,
             shuffle: false,
+            device_id: None,
         }
     }
 }
@@ -126,6 +126,10 @@ impl SongListModel {
         self.inner().song_batch_for(i)
     }
 
+    pub fn song_batches(&self) -> Vec<SongBatch> {
+        self.inner().song_batches()
+    }
+
     pub fn last_batch(&self) -> Option<Batch> {
         self.inner().last_batch()
     }
@@ -156,6 +160,10 @@ impl SongListModel {
         self.inner().find_index(song_id)
     }
 
+    pub fn count(&self, song_id: &str) -> usize {
+        self.inner().count(song_id)
+    }
+
     pub fn remove(&mut self, ids: &[String]) -> SongListModelPending {
         let change = self.inner_mut().remove(ids);
         SongListModelPending::new(Some(change), self)
@@ -171,6 +179,11 @@ impl SongListModel {
         SongListModelPending::new(swap, self)
     }
 
+    pub fn move_item(&mut self, from: usize, to: usize) -> SongListModelPending {
+        let change = self.inner_mut().move_item(from, to);
+        SongListModelPending::new(change, self)
+    }
+
     pub fn clear(&mut self) -> SongListModelPending {
         let removed = self.inner_mut().clear();
         SongListModelPending::new(Some(removed), self)
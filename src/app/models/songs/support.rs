@@ -173,6 +173,12 @@ impl SongList {
             .map(|(pos, _)| pos)
     }
 
+    pub fn count(&self, song_id: &str) -> usize {
+        self.iter_ids_from(0)
+            .filter(|(_, id)| &id[..] == song_id)
+            .count()
+    }
+
     fn iter_range(&self, a: usize, b: usize) -> impl Iterator<Item = (usize, &'_ String)> {
         let batch_size = self.batch_size;
         let batches = &self.batches;
@@ -330,6 +336,25 @@ impl SongList {
         Some(ListRangeUpdate::updated(a).merge(ListRangeUpdate::updated(b)))
     }
 
+    // Moves the song at `from` to `to`, shifting everything in between by one
+    pub fn move_item(&mut self, from: usize, to: usize) -> Option<ListRangeUpdate> {
+        if from == to || self.index_mut(from).is_none() || self.index_mut(to).is_none() {
+            return None;
+        }
+        if from < to {
+            (from..to).for_each(|i| {
+                self.swap(i, i + 1);
+            });
+        } else {
+            (to..from).rev().for_each(|i| {
+                self.swap(i, i + 1);
+            });
+        }
+        let start = usize::min(from, to);
+        let len = from.abs_diff(to) + 1;
+        Some(ListRangeUpdate(start as i32, len as i32, len as i32))
+    }
+
     pub fn index(&self, i: usize) -> Option<&SongModel> {
         let batch_size = self.batch_size;
         let batch_id = i / batch_size;
@@ -384,6 +409,12 @@ impl SongList {
         })
     }
 
+    pub fn song_batches(&self) -> Vec<SongBatch> {
+        (0..=self.last_batch_key)
+            .filter_map(|key| self.song_batch_for(key * self.batch_size))
+            .collect()
+    }
+
     pub fn last_batch(&self) -> Option<Batch> {
         if self.total_loaded == 0 {
             None
@@ -609,6 +640,25 @@ mod tests {
         assert_eq!(batch.unwrap().batch.offset, 2);
     }
 
+    #[test]
+    fn test_song_batches() {
+        let mut list = SongList::new_from_initial_batch(batch(0));
+        list.add(batch(3));
+
+        let batches = list.song_batches();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[1].batch.offset, 6);
+        assert_eq!(batches[1].songs[0].id, "song6");
+
+        let mut restored = SongList::new_sized(2);
+        batches.into_iter().for_each(|b| {
+            restored.add(b);
+        });
+        assert_eq!(restored.len(), 10);
+        assert_eq!(restored.partial_len(), 4);
+        assert_eq!(restored.index(7).unwrap().description().id, "song7");
+    }
+
     #[test]
     fn test_append() {
         let mut list = SongList::new_from_initial_batch(batch(0));
@@ -643,4 +693,33 @@ mod tests {
         assert_eq!(list_iter.next().unwrap().description().id, "song0");
         assert!(list_iter.next().is_none());
     }
+
+    #[test]
+    fn test_move_item() {
+        let mut list = SongList::new_sized(2);
+        list.append(vec![
+            song("song0"),
+            song("song1"),
+            song("song2"),
+            song("song3"),
+        ]);
+
+        assert_eq!(list.move_item(0, 2), Some(ListRangeUpdate(0, 3, 3)));
+        assert_eq!(list.move_item(3, 1), Some(ListRangeUpdate(1, 3, 3)));
+        assert_eq!(list.move_item(1, 4), None);
+        assert_eq!(list.move_item(2, 2), None);
+
+        let ids: Vec<String> = list.iter().map(|s| s.description().id.clone()).collect();
+        assert_eq!(ids, vec!["song1", "song3", "song2", "song0"]);
+    }
+
+    #[test]
+    fn test_count() {
+        let mut list = SongList::new_sized(2);
+        list.append(vec![song("song0"), song("song1"), song("song0")]);
+
+        assert_eq!(list.count("song0"), 2);
+        assert_eq!(list.count("song1"), 1);
+        assert_eq!(list.count("song2"), 0);
+    }
 }
@@ -47,6 +47,23 @@ impl LazyRandomIndex {
         self.indices.truncate(size);
     }
 
+    pub fn permutation(&self) -> (&[usize], usize) {
+        (&self.indices, self.generated)
+    }
+
+    // Restores a permutation previously obtained through permutation(), rejecting anything that
+    // isn't a permutation of 0..indices.len()
+    pub fn restore(&mut self, indices: Vec<usize>, generated: usize) -> bool {
+        let mut sorted = indices.clone();
+        sorted.sort_unstable();
+        let valid = generated <= indices.len() && sorted.into_iter().eq(0..indices.len());
+        if valid {
+            self.indices = indices;
+            self.generated = generated;
+        }
+        valid
+    }
+
     pub fn get(&self, i: usize) -> Option<usize> {
         if i >= self.generated || i >= self.indices.len() {
             None
@@ -145,6 +162,25 @@ mod tests {
         assert_eq!(values, same_values);
     }
 
+    #[test]
+    fn test_restore() {
+        let mut index = LazyRandomIndex::from(rng_for_test());
+        index.grow(5);
+        index.next_until(2);
+        let (indices, generated) = index.permutation();
+        let (indices, generated) = (indices.to_vec(), generated);
+
+        let mut restored = LazyRandomIndex::default();
+        assert!(restored.restore(indices.clone(), generated));
+        assert_eq!(restored.permutation(), (&indices[..], generated));
+        assert_eq!(restored.get(2), index.get(2));
+        assert_eq!(restored.get(3), None);
+
+        assert!(!restored.restore(vec![0, 0, 1], 1));
+        assert!(!restored.restore(vec![0, 1], 3));
+        assert_eq!(restored.permutation(), (&indices[..], generated));
+    }
+
     #[test]
     fn test_reset() {
         let mut index = LazyRandomIndex::from(rng_for_test());
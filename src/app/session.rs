@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::app::models::{RepeatMode, SongBatch, SongDescription};
+use crate::app::SongsSource;
+
+const SESSION_FILE: &str = "session.json";
+
+// Everything needed to bring the playback queue back after a restart
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlaybackSession {
+    pub source: Option<SongsSource>,
+    pub batches: Vec<SongBatch>,
+    pub list_position: Option<usize>,
+    pub shuffle_indices: Vec<usize>,
+    pub shuffle_generated: usize,
+    pub seek_position: u64,
+    pub repeat: RepeatMode,
+    pub is_shuffled: bool,
+    // Missing from sessions saved by older versions
+    #[serde(default)]
+    pub next_up: Vec<SongDescription>,
+    #[serde(default)]
+    pub from_next_up: Option<SongDescription>,
+}
+
+impl PlaybackSession {
+    fn session_dir() -> PathBuf {
+        glib::user_data_dir().join("spot")
+    }
+
+    pub fn load() -> Option<Self> {
+        let content = fs::read(Self::session_dir().join(SESSION_FILE)).ok()?;
+        serde_json::from_slice(&content)
+            .map_err(|e| warn!("Could not read playback session: {}", e))
+            .ok()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let dir = Self::session_dir();
+        fs::create_dir_all(&dir)?;
+        let content = serde_json::to_vec(self)?;
+
+        // Write to a temporary file first so that a crash mid-write can't leave a truncated session
+        let tmp_path = dir.join(SESSION_FILE.to_string() + ".tmp");
+        fs::write(&tmp_path, content)?;
+        fs::rename(tmp_path, dir.join(SESSION_FILE))
+    }
+
+    pub fn clear() -> io::Result<()> {
+        match fs::remove_file(Self::session_dir().join(SESSION_FILE)) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
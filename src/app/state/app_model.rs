@@ -52,20 +52,49 @@ impl AppModel {
             AppAction::LoginAction(LoginAction::SetLoginSuccess(
                 SetLoginSuccessAction::Password(creds),
             )) => {
-                self.services.spotify_api.update_token(creds.token.clone());
+                self.services
+                    .spotify_api
+                    .update_token(creds.token.clone(), creds.token_expiry_time);
             }
             AppAction::LoginAction(LoginAction::SetLoginSuccess(
-                SetLoginSuccessAction::Token { token, .. },
+                SetLoginSuccessAction::Token {
+                    token,
+                    token_expiry_time,
+                    ..
+                },
             )) => {
-                self.services.spotify_api.update_token(token.clone());
+                self.services
+                    .spotify_api
+                    .update_token(token.clone(), *token_expiry_time);
             }
-            AppAction::LoginAction(LoginAction::SetRefreshedToken { token, .. }) => {
-                self.services.spotify_api.update_token(token.clone());
+            AppAction::LoginAction(LoginAction::SetRefreshedToken {
+                token,
+                token_expiry_time,
+            }) => {
+                self.services
+                    .spotify_api
+                    .update_token(token.clone(), Some(*token_expiry_time));
             }
             _ => {}
         }
 
+        let api = &self.services.spotify_api;
         let mut state = self.state.borrow_mut();
-        state.update_state(message)
+
+        // The API goes offline and queues changes by itself when requests fail; it reports going offline,
+        // but other actions might be handled before that, so catch up with it first
+        let mut events = if api.is_offline() && !state.network.is_offline() {
+            state.update_state(AppAction::SetNetworkAvailable(false))
+        } else {
+            vec![]
+        };
+        let pending_writes = api.pending_writes();
+        if pending_writes != state.pending_writes {
+            events.append(&mut state.update_state(AppAction::SetPendingWrites(pending_writes)));
+        }
+        events.append(&mut state.update_state(message));
+
+        api.set_offline(state.network.is_offline());
+        events
     }
 }
@@ -21,6 +21,8 @@ pub enum AppAction {
     Raise,
     ShowNotification(String),
     ViewNowPlaying,
+    // Plays a track we only know the id of, once it's been fetched
+    OpenTrack(String),
     // cross-state actions
     QueueSelection,
     DequeueSelection,
@@ -32,30 +34,91 @@ pub enum AppAction {
     CancelSelection,
     CreatePlaylist(PlaylistDescription),
     UpdatePlaylistName(PlaylistSummary),
+    SetNetworkAvailable(bool),
+    SetWorkOffline(bool),
+    SetPendingWrites(usize),
 }
 
-impl AppAction {
-    #[allow(non_snake_case)]
-    pub fn OpenURI(uri: String) -> Option<Self> {
-        debug!("parsing {}", &uri);
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NetworkStatus {
+    Online,
+    // Spotify could not be reached, we'll go back online as soon as the network is back
+    Offline,
+    // Offline on the user's request, only they can turn it off
+    WorkingOffline,
+}
+
+impl NetworkStatus {
+    pub fn is_offline(self) -> bool {
+        self != Self::Online
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SpotifyLink {
+    Album(String),
+    Artist(String),
+    Playlist(String),
+    User(String),
+    Track(String),
+}
+
+impl SpotifyLink {
+    // Accepts both spotify:<kind>:<id> URIs and https://open.spotify.com/<kind>/<id> links
+    pub fn parse(uri: &str) -> Option<Self> {
+        let (kind, id) = Self::parse_spotify_uri(uri).or_else(|| Self::parse_web_link(uri))?;
+        let id = id.to_string();
+        match kind {
+            "album" => Some(Self::Album(id)),
+            "artist" => Some(Self::Artist(id)),
+            "playlist" => Some(Self::Playlist(id)),
+            "user" => Some(Self::User(id)),
+            "track" => Some(Self::Track(id)),
+            _ => None,
+        }
+    }
+
+    fn parse_spotify_uri(uri: &str) -> Option<(&str, &str)> {
         let mut parts = uri.split(':');
         if parts.next()? != "spotify" {
             return None;
         }
 
         // Might start with /// because of https://gitlab.gnome.org/GNOME/glib/-/issues/1886/
-        let action = parts
-            .next()?
-            .strip_prefix("///")
-            .filter(|p| !p.is_empty())?;
-        let data = parts.next()?;
-
-        match action {
-            "album" => Some(Self::ViewAlbum(data.to_string())),
-            "artist" => Some(Self::ViewArtist(data.to_string())),
-            "playlist" => Some(Self::ViewPlaylist(data.to_string())),
-            "user" => Some(Self::ViewUser(data.to_string())),
-            _ => None,
+        let kind = parts.next()?;
+        let kind = kind.strip_prefix("///").unwrap_or(kind);
+        let id = parts.next()?;
+        Some((kind, id)).filter(|(kind, id)| !kind.is_empty() && !id.is_empty())
+    }
+
+    fn parse_web_link(uri: &str) -> Option<(&str, &str)> {
+        let path = uri
+            .strip_prefix("https://open.spotify.com/")
+            .or_else(|| uri.strip_prefix("http://open.spotify.com/"))?;
+        let path = path.split(['?', '#']).next()?;
+        let mut parts = path.split('/').filter(|p| !p.is_empty()).peekable();
+
+        // Localized links look like https://open.spotify.com/intl-fr/album/<id>
+        if parts.peek()?.starts_with("intl-") {
+            parts.next();
+        }
+
+        let kind = parts.next()?;
+        let id = parts.next()?;
+        Some((kind, id))
+    }
+}
+
+impl AppAction {
+    #[allow(non_snake_case)]
+    pub fn OpenURI(uri: String) -> Option<Self> {
+        debug!("parsing {}", &uri);
+        match SpotifyLink::parse(&uri)? {
+            SpotifyLink::Album(id) => Some(Self::ViewAlbum(id)),
+            SpotifyLink::Artist(id) => Some(Self::ViewArtist(id)),
+            SpotifyLink::Playlist(id) => Some(Self::ViewPlaylist(id)),
+            SpotifyLink::User(id) => Some(Self::ViewUser(id)),
+            SpotifyLink::Track(id) => Some(Self::OpenTrack(id)),
         }
     }
 
@@ -96,11 +159,17 @@ pub enum AppEvent {
     NotificationShown(String),
     PlaylistCreatedNotificationShown(String),
     NowPlayingShown,
+    TrackOpened(String),
     SettingsEvent(SettingsEvent),
+    NetworkStatusChanged(NetworkStatus),
+    PendingWritesChanged(usize),
 }
 
 pub struct AppState {
     started: bool,
+    pub network: NetworkStatus,
+    // Library changes accepted locally but not yet sent to Spotify
+    pub pending_writes: usize,
     pub playback: PlaybackState,
     pub browser: BrowserState,
     pub selection: SelectionState,
@@ -112,6 +181,8 @@ impl AppState {
     pub fn new() -> Self {
         Self {
             started: false,
+            network: NetworkStatus::Online,
+            pending_writes: 0,
             playback: Default::default(),
             browser: BrowserState::new(),
             selection: Default::default(),
@@ -129,6 +200,7 @@ impl AppState {
             AppAction::ShowNotification(c) => vec![AppEvent::NotificationShown(c)],
             AppAction::ViewNowPlaying => vec![AppEvent::NowPlayingShown],
             AppAction::Raise => vec![AppEvent::Raised],
+            AppAction::OpenTrack(id) => vec![AppEvent::TrackOpened(id)],
             AppAction::QueueSelection => {
                 self.playback.queue(self.selection.take_selection());
                 vec![
@@ -229,6 +301,23 @@ impl AppState {
                 events.append(&mut more_events);
                 events
             }
+            AppAction::SetNetworkAvailable(available) => {
+                let network = match (self.network, available) {
+                    (NetworkStatus::Online, false) => NetworkStatus::Offline,
+                    (NetworkStatus::Offline, true) => NetworkStatus::Online,
+                    (network, _) => network,
+                };
+                self.set_network(network)
+            }
+            AppAction::SetWorkOffline(offline) => self.set_network(if offline {
+                NetworkStatus::WorkingOffline
+            } else {
+                NetworkStatus::Online
+            }),
+            AppAction::SetPendingWrites(count) => {
+                self.pending_writes = count;
+                vec![AppEvent::PendingWritesChanged(count)]
+            }
             AppAction::PlaybackAction(a) => forward_action(a, &mut self.playback),
             AppAction::BrowserAction(a) => forward_action(a, &mut self.browser),
             AppAction::SelectionAction(a) => forward_action(a, &mut self.selection),
@@ -237,6 +326,14 @@ impl AppState {
             _ => vec![],
         }
     }
+
+    fn set_network(&mut self, network: NetworkStatus) -> Vec<AppEvent> {
+        if self.network == network {
+            return vec![];
+        }
+        self.network = network;
+        vec![AppEvent::NetworkStatusChanged(network)]
+    }
 }
 
 fn forward_action<A, E>(
@@ -253,3 +350,68 @@ where
         .map(|e| e.into())
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_parse_spotify_uri() {
+        assert_eq!(
+            SpotifyLink::parse("spotify:album:abc"),
+            Some(SpotifyLink::Album("abc".to_string()))
+        );
+        assert_eq!(
+            SpotifyLink::parse("spotify:///playlist:abc"),
+            Some(SpotifyLink::Playlist("abc".to_string()))
+        );
+        assert_eq!(
+            SpotifyLink::parse("spotify:track:abc"),
+            Some(SpotifyLink::Track("abc".to_string()))
+        );
+        assert_eq!(SpotifyLink::parse("spotify:show:abc"), None);
+        assert_eq!(SpotifyLink::parse("spotify:album:"), None);
+        assert_eq!(SpotifyLink::parse("file:///album:abc"), None);
+    }
+
+    #[test]
+    fn test_parse_web_link() {
+        assert_eq!(
+            SpotifyLink::parse("https://open.spotify.com/track/abc?si=123"),
+            Some(SpotifyLink::Track("abc".to_string()))
+        );
+        assert_eq!(
+            SpotifyLink::parse("https://open.spotify.com/intl-fr/album/abc"),
+            Some(SpotifyLink::Album("abc".to_string()))
+        );
+        assert_eq!(
+            SpotifyLink::parse("https://open.spotify.com/user/abc/"),
+            Some(SpotifyLink::User("abc".to_string()))
+        );
+        assert_eq!(SpotifyLink::parse("https://open.spotify.com/album"), None);
+        assert_eq!(SpotifyLink::parse("https://example.com/album/abc"), None);
+    }
+
+    #[test]
+    fn test_network_status() {
+        let mut state = AppState::new();
+
+        let events = state.update_state(AppAction::SetNetworkAvailable(false));
+        assert!(matches!(
+            events[..],
+            [AppEvent::NetworkStatusChanged(NetworkStatus::Offline)]
+        ));
+
+        state.update_state(AppAction::SetNetworkAvailable(true));
+        assert_eq!(state.network, NetworkStatus::Online);
+
+        state.update_state(AppAction::SetWorkOffline(true));
+        let events = state.update_state(AppAction::SetNetworkAvailable(true));
+        assert!(events.is_empty());
+        assert_eq!(state.network, NetworkStatus::WorkingOffline);
+
+        state.update_state(AppAction::SetWorkOffline(false));
+        assert_eq!(state.network, NetworkStatus::Online);
+    }
+}
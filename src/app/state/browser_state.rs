@@ -16,6 +16,8 @@ pub enum BrowserAction {
     SetPlaylistsContent(Vec<PlaylistDescription>),
     AppendPlaylistsContent(Vec<PlaylistDescription>),
     RemoveTracksFromPlaylist(String, Vec<String>),
+    MovePlaylistTrack { id: String, from: usize, to: usize },
+    SetPlaylistSnapshot { id: String, snapshot_id: String },
     SetAlbumDetails(Box<AlbumFullDescription>),
     AppendAlbumTracks(String, Box<SongBatch>),
     SetPlaylistDetails(Box<PlaylistDescription>),
@@ -23,6 +25,10 @@ pub enum BrowserAction {
     AppendPlaylistTracks(String, Box<SongBatch>),
     Search(String),
     SetSearchResults(Box<SearchResults>),
+    AppendSearchAlbums(String, Vec<AlbumDescription>),
+    AppendSearchArtists(String, Vec<ArtistSummary>),
+    AppendSearchTracks(String, Box<SongBatch>),
+    AppendSearchPlaylists(String, Vec<PlaylistDescription>),
     SetArtistDetails(Box<ArtistDescription>),
     AppendArtistReleases(String, Vec<AlbumDescription>),
     NavigationPush(ScreenName),
@@ -55,6 +61,7 @@ pub enum BrowserEvent {
     PlaylistDetailsLoaded(String),
     PlaylistTracksAppended(String),
     PlaylistTracksRemoved(String),
+    PlaylistTracksMoved(String),
     SearchUpdated,
     SearchResultsUpdated,
     ArtistDetailsUpdated(String),
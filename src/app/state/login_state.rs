@@ -1,4 +1,3 @@
-use gettextrs::*;
 use std::borrow::Cow;
 use std::time::SystemTime;
 
@@ -15,7 +14,11 @@ pub enum TryLoginAction {
 #[derive(Clone, Debug)]
 pub enum SetLoginSuccessAction {
     Password(Credentials),
-    Token { username: String, token: String },
+    Token {
+        username: String,
+        token: String,
+        token_expiry_time: Option<SystemTime>,
+    },
 }
 
 #[derive(Clone, Debug)]
@@ -110,15 +113,12 @@ impl UpdatableState for LoginState {
                 token,
                 token_expiry_time,
             } => {
-                // translators: This notification is shown when, after some inactivity, the session is successfully restored. The user might have to repeat its last action.
-                vec![
-                    AppEvent::NotificationShown(gettext("Connection restored")),
-                    LoginEvent::RefreshTokenCompleted {
-                        token,
-                        token_expiry_time,
-                    }
-                    .into(),
-                ]
+                // Requests that failed because of the expired token are replayed, no need to tell anyone
+                vec![LoginEvent::RefreshTokenCompleted {
+                    token,
+                    token_expiry_time,
+                }
+                .into()]
             }
             LoginAction::Logout => {
                 self.user = None;
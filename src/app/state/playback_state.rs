@@ -1,9 +1,19 @@
 use std::borrow::Cow;
-use std::time::Instant;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
 
 use crate::app::models::*;
 use crate::app::state::{AppAction, AppEvent, UpdatableState};
-use crate::app::{BatchQuery, LazyRandomIndex, SongsSource};
+use crate::app::{BatchQuery, LazyRandomIndex, PlaybackSession, SongsSource};
+
+// Spotify takes at most 5 seeds for recommendations, we favor tracks over artists
+const AUTOPLAY_MAX_SEEDS: usize = 5;
+const AUTOPLAY_SEED_TRACKS: usize = 3;
+const AUTOPLAY_BATCH_SIZE: usize = 20;
+
+// Speeds the local player can play at
+pub const MIN_PLAYBACK_RATE: f32 = 0.5;
+pub const MAX_PLAYBACK_RATE: f32 = 2.0;
 
 #[derive(Debug)]
 pub struct PlaybackState {
@@ -11,12 +21,20 @@ pub struct PlaybackState {
     current_device: Device,
     index: LazyRandomIndex,
     songs: SongListModel,
+    // Songs queued by the user, played before the rest of the context
+    next_up: SongListModel,
+    // Set when the current song was taken from next_up, list_position is then where the context resumes from
+    from_next_up: Option<SongDescription>,
+    // Songs that were recommended when the queue ended
+    autoplayed: HashSet<String>,
     list_position: Option<usize>,
     seek_position: PositionMillis,
     source: Option<SongsSource>,
     repeat: RepeatMode,
     is_playing: bool,
+    is_buffering: bool,
     is_shuffled: bool,
+    sleep_timer: Option<SleepDeadline>,
 }
 
 impl PlaybackState {
@@ -24,8 +42,26 @@ impl PlaybackState {
         &self.songs
     }
 
+    pub fn next_up(&self) -> &SongListModel {
+        &self.next_up
+    }
+
+    // Looks for a song in both the queue and the context
+    pub fn get_song(&self, id: &str) -> Option<SongModel> {
+        self.next_up.get(id).or_else(|| self.songs.get(id))
+    }
+
+    fn has_current_song(&self) -> bool {
+        self.from_next_up.is_some() || self.list_position.is_some()
+    }
+
     pub fn is_playing(&self) -> bool {
-        self.is_playing && self.list_position.is_some()
+        self.is_playing && self.has_current_song()
+    }
+
+    // Whether the local player is waiting for the current track to load
+    pub fn is_buffering(&self) -> bool {
+        self.is_buffering && self.has_current_song() && matches!(self.current_device, Device::Local)
     }
 
     pub fn is_shuffled(&self) -> bool {
@@ -52,6 +88,50 @@ impl PlaybackState {
         }
     }
 
+    // Recommendations to append once the last song of the queue is playing
+    pub fn autoplay_query(&self) -> Option<BatchQuery> {
+        let position = self.list_position?;
+        let at_end = self.repeat == RepeatMode::None && self.next_index().is_none();
+        if !at_end || !matches!(self.current_device, Device::Local) {
+            return None;
+        }
+
+        let recent: Vec<SongDescription> = self
+            .from_next_up
+            .iter()
+            .cloned()
+            .chain((0..=position).rev().filter_map(|i| self.index(i)))
+            .take(AUTOPLAY_SEED_TRACKS)
+            .collect();
+        let seed_tracks: Vec<String> = recent.iter().map(|song| song.id.clone()).collect();
+        let mut seed_artists: Vec<String> = vec![];
+        for artist in recent.iter().flat_map(|song| song.artists.iter()) {
+            if seed_tracks.len() + seed_artists.len() >= AUTOPLAY_MAX_SEEDS {
+                break;
+            }
+            if !seed_artists.contains(&artist.id) {
+                seed_artists.push(artist.id.clone());
+            }
+        }
+
+        let offset = self.songs.len();
+        Some(BatchQuery {
+            source: SongsSource::Recommendations {
+                seed_tracks,
+                seed_artists,
+            },
+            batch: Batch {
+                offset,
+                batch_size: AUTOPLAY_BATCH_SIZE,
+                total: offset + AUTOPLAY_BATCH_SIZE,
+            },
+        })
+    }
+
+    pub fn is_autoplayed(&self, id: &str) -> bool {
+        self.autoplayed.contains(id)
+    }
+
     fn index(&self, i: usize) -> Option<SongDescription> {
         let song = if self.is_shuffled {
             self.songs.index(self.index.get(i)?)
@@ -61,30 +141,61 @@ impl PlaybackState {
         Some(song?.into_description())
     }
 
+    // The songs in the order they play in: queued ones come right after the current one
+    pub fn songs_in_order(&self) -> Vec<SongDescription> {
+        let mut songs = self.songs.collect();
+        let position = self
+            .list_position
+            .map(|position| position + 1)
+            .unwrap_or(0)
+            .min(songs.len());
+        let queued = self
+            .from_next_up
+            .iter()
+            .cloned()
+            .chain(self.next_up.collect());
+        songs.splice(position..position, queued);
+        songs
+    }
+
     pub fn current_source(&self) -> Option<&SongsSource> {
         self.source.as_ref()
     }
 
+    // The position of the current song in the context, unless it was queued
     pub fn current_song_index(&self) -> Option<usize> {
-        self.list_position
+        self.list_position.filter(|_| self.from_next_up.is_none())
     }
 
     pub fn current_song_id(&self) -> Option<String> {
-        Some(self.index(self.list_position?)?.id)
+        self.current_song().map(|song| song.id)
     }
 
     pub fn current_song(&self) -> Option<SongDescription> {
-        self.index(self.list_position?)
+        match &self.from_next_up {
+            Some(song) => Some(song.clone()),
+            None => self.index(self.list_position?),
+        }
     }
 
-    fn next_id(&self) -> Option<String> {
-        self.next_index()
-            .and_then(|i| Some(self.songs().index(i)?.description().id.clone()))
+    pub fn next_id(&self) -> Option<String> {
+        if self.repeat == RepeatMode::Song && self.from_next_up.is_some() {
+            return self.current_song_id();
+        }
+        let queued = self
+            .next_up
+            .index(0)
+            .filter(|_| self.repeat != RepeatMode::Song);
+        match queued {
+            Some(song) => Some(song.get_id()),
+            None => Some(self.index(self.next_index()?)?.id),
+        }
     }
 
     fn clear(&mut self, source: Option<SongsSource>) -> SongListModelPending {
         self.source = source;
         self.index = Default::default();
+        self.autoplayed.clear();
         self.list_position = None;
         self.songs.clear()
     }
@@ -106,14 +217,43 @@ impl PlaybackState {
         self.index.grow(self.songs.len());
     }
 
-    pub fn queue(&mut self, tracks: Vec<SongDescription>) {
-        self.source = None;
-        self.songs.append(tracks).commit();
+    // Recommendations arriving after playback moved on are dropped
+    fn autoplay(&mut self, mut songs: Vec<SongDescription>) -> bool {
+        if self.list_position.is_none() || self.next_index().is_some() {
+            return false;
+        }
+        songs.retain(|song| self.songs.get(&song.id).is_none());
+        if songs.is_empty() {
+            return false;
+        }
+        self.autoplayed
+            .extend(songs.iter().map(|song| song.id.clone()));
+        self.songs.append(songs).commit();
         self.index.grow(self.songs.len());
+        true
+    }
+
+    // Adds songs at the end of next_up
+    pub fn queue(&mut self, tracks: Vec<SongDescription>) {
+        self.next_up.append(tracks).commit();
+    }
+
+    // Adds songs at the start of next_up
+    pub fn queue_next(&mut self, tracks: Vec<SongDescription>) {
+        self.next_up.prepend(tracks).commit();
+    }
+
+    fn take_next_up(&mut self, id: &str) -> Option<SongDescription> {
+        let song = self.next_up.get(id)?.into_description();
+        self.next_up.remove(std::slice::from_ref(&song.id)).commit();
+        Some(song)
     }
 
     pub fn dequeue(&mut self, ids: &[String]) {
-        let current_id = self.current_song_id();
+        if ids.iter().any(|id| self.next_up.find_index(id).is_some()) {
+            self.next_up.remove(ids).commit();
+        }
+        let current_id = self.list_position.and_then(|p| self.index(p)).map(|s| s.id);
         self.songs.remove(ids).commit();
         self.list_position = current_id.and_then(|id| self.songs.find_index(&id));
         self.index.shrink(self.songs.len());
@@ -132,6 +272,10 @@ impl PlaybackState {
     }
 
     pub fn move_down(&mut self, id: &str) -> Option<usize> {
+        if let Some(index) = self.next_up.find_index(id) {
+            let is_last = index + 1 >= self.next_up.len();
+            return (!is_last && self.next_up.move_down(index).commit()).then_some(index);
+        }
         let index = self.songs.find_index(id)?;
         self.songs.move_down(index).commit();
         self.swap_pos(index + 1, index);
@@ -139,6 +283,9 @@ impl PlaybackState {
     }
 
     pub fn move_up(&mut self, id: &str) -> Option<usize> {
+        if let Some(index) = self.next_up.find_index(id) {
+            return (index > 0 && self.next_up.move_up(index).commit()).then_some(index);
+        }
         let index = self.songs.find_index(id).filter(|&index| index > 0)?;
         self.songs.move_up(index).commit();
         self.swap_pos(index - 1, index);
@@ -150,6 +297,11 @@ impl PlaybackState {
             return false;
         }
 
+        if let Some(song) = self.take_next_up(id) {
+            self.play_from_next_up(song);
+            return true;
+        }
+
         let found_index = self.songs.find_index(id);
 
         if let Some(index) = found_index {
@@ -166,12 +318,15 @@ impl PlaybackState {
     }
 
     fn stop(&mut self) {
+        self.from_next_up = None;
         self.list_position = None;
         self.is_playing = false;
+        self.is_buffering = false;
         self.seek_position.set(0, false);
     }
 
     fn play_index(&mut self, index: usize) -> Option<String> {
+        self.from_next_up = None;
         self.is_playing = true;
         self.list_position.replace(index);
         self.seek_position.set(0, true);
@@ -179,7 +334,27 @@ impl PlaybackState {
         self.current_song_id()
     }
 
+    fn play_from_next_up(&mut self, song: SongDescription) -> String {
+        let id = song.id.clone();
+        self.from_next_up = Some(song);
+        self.is_playing = true;
+        self.seek_position.set(0, true);
+        id
+    }
+
     fn play_next(&mut self) -> Option<String> {
+        if self.repeat == RepeatMode::Song && self.from_next_up.is_some() {
+            self.is_playing = true;
+            self.seek_position.set(0, true);
+            return self.current_song_id();
+        }
+        let queued = self
+            .next_up
+            .index(0)
+            .filter(|_| self.repeat != RepeatMode::Song);
+        if let Some(song) = queued.and_then(|song| self.take_next_up(&song.get_id())) {
+            return Some(self.play_from_next_up(song));
+        }
         self.next_index().and_then(move |i| {
             self.seek_position.set(0, true);
             self.play_index(i)
@@ -212,6 +387,10 @@ impl PlaybackState {
     }
 
     pub fn prev_index(&self) -> Option<usize> {
+        // Going back from a queued song returns to the context
+        if self.from_next_up.is_some() {
+            return self.list_position;
+        }
         let len = self.songs.len();
         self.list_position.and_then(|p| match self.repeat {
             RepeatMode::Song => Some(p),
@@ -222,7 +401,7 @@ impl PlaybackState {
     }
 
     fn toggle_play(&mut self) -> Option<bool> {
-        if self.list_position.is_some() {
+        if self.has_current_song() {
             self.is_playing = !self.is_playing;
 
             match self.is_playing {
@@ -242,10 +421,142 @@ impl PlaybackState {
         self.index.reset_picking_first(old);
     }
 
+    pub fn session(&self) -> PlaybackSession {
+        let (shuffle_indices, shuffle_generated) = self.index.permutation();
+        PlaybackSession {
+            source: self.source.clone(),
+            batches: self.songs.song_batches(),
+            list_position: self.list_position,
+            shuffle_indices: shuffle_indices.to_vec(),
+            shuffle_generated,
+            seek_position: self.seek_position.current(),
+            repeat: self.repeat,
+            is_shuffled: self.is_shuffled,
+            next_up: self.next_up.collect(),
+            from_next_up: self.from_next_up.clone(),
+        }
+    }
+
+    // Restores a saved session, paused at the position it was saved at
+    fn restore_session(&mut self, session: PlaybackSession) {
+        let PlaybackSession {
+            source,
+            batches,
+            list_position,
+            shuffle_indices,
+            shuffle_generated,
+            seek_position,
+            repeat,
+            is_shuffled,
+            next_up,
+            from_next_up,
+        } = session;
+
+        let mut pending = self.clear(source);
+        for batch in batches {
+            pending = pending.and(move |s| s.add(batch));
+        }
+        pending.commit();
+        self.next_up
+            .clear()
+            .and(move |s| s.append(next_up))
+            .commit();
+
+        let len = self.songs.len();
+        if shuffle_indices.len() != len || !self.index.restore(shuffle_indices, shuffle_generated) {
+            self.index.resize(len);
+        }
+
+        self.repeat = repeat;
+        self.is_shuffled = is_shuffled;
+        self.is_playing = false;
+        self.from_next_up = from_next_up;
+        self.list_position = list_position.filter(|&p| p < len);
+        let seek_position = if self.from_next_up.is_some() || self.list_position.is_some() {
+            seek_position
+        } else {
+            0
+        };
+        self.seek_position.set(seek_position, false);
+    }
+
     pub fn available_devices(&self) -> &Vec<ConnectDevice> {
         &self.available_devices
     }
 
+    pub fn position_ms(&self) -> u32 {
+        self.seek_position.current() as u32
+    }
+
+    pub fn rate(&self) -> f32 {
+        self.seek_position.rate
+    }
+
+    fn set_rate(&mut self, rate: f32) -> Option<PlaybackEvent> {
+        if rate == self.rate() {
+            None
+        } else {
+            self.seek_position.set_rate(rate);
+            Some(PlaybackEvent::RateChanged(rate))
+        }
+    }
+
+    // The next song might have changed, and been preloaded already
+    fn queue_extended(&self) -> Vec<PlaybackEvent> {
+        let mut events = vec![PlaybackEvent::PlaylistChanged];
+        if self.has_current_song() {
+            events.extend(self.next_id().map(PlaybackEvent::Preload));
+        }
+        events
+    }
+
+    fn sync_local_player(&mut self, state: LocalPlayerState) -> Vec<PlaybackEvent> {
+        let mut events = vec![];
+        let buffering = state == LocalPlayerState::Loading;
+        if self.is_buffering != buffering {
+            self.is_buffering = buffering;
+            events.push(PlaybackEvent::BufferingChanged(buffering));
+        }
+
+        let (playing, position) = match state {
+            LocalPlayerState::Loading => return events,
+            LocalPlayerState::Playing(position) => (true, Some(position)),
+            LocalPlayerState::Paused(position) => (false, Some(position)),
+            LocalPlayerState::Stopped => (false, None),
+        };
+        if !self.has_current_song() {
+            return events;
+        }
+        if self.is_playing != playing {
+            self.is_playing = playing;
+            events.push(PlaybackEvent::PlayingSynced(playing));
+        }
+        match position {
+            Some(position) => {
+                self.seek_position.set(position as u64, playing);
+                events.push(PlaybackEvent::SeekSynced(position));
+            }
+            None => self.seek_position.pause(),
+        }
+        events
+    }
+
+    // Time left before the sleep timer pauses the playback
+    pub fn sleep_timer_remaining(&self) -> Option<Duration> {
+        match self.sleep_timer? {
+            SleepDeadline::At(deadline) => Some(deadline.saturating_duration_since(Instant::now())),
+            SleepDeadline::EndOfTrack => {
+                let duration = self.current_song().map(|song| song.duration).unwrap_or(0);
+                let remaining = duration.saturating_sub(self.position_ms());
+                Some(Duration::from_millis(remaining.into()).div_f32(self.rate()))
+            }
+        }
+    }
+
+    pub fn sleeps_at_end_of_track(&self) -> bool {
+        matches!(self.sleep_timer, Some(SleepDeadline::EndOfTrack))
+    }
+
     pub fn current_device(&self) -> &Device {
         &self.current_device
     }
@@ -258,12 +569,17 @@ impl Default for PlaybackState {
             current_device: Device::Local,
             index: LazyRandomIndex::default(),
             songs: SongListModel::new(50),
+            next_up: SongListModel::new(50),
+            from_next_up: None,
+            autoplayed: HashSet::new(),
             list_position: None,
             seek_position: PositionMillis::new(1.0),
             source: None,
             repeat: RepeatMode::None,
             is_playing: false,
+            is_buffering: false,
             is_shuffled: false,
+            sleep_timer: None,
         }
     }
 }
@@ -284,13 +600,25 @@ pub enum PlaybackAction {
     LoadSongs(Vec<SongDescription>),
     LoadPagedSongs(SongsSource, SongBatch),
     SetVolume(f64),
+    // The player's volume changed on its own, there's nothing to send back to it
+    SyncVolume(f64),
     Next,
     Previous,
     Preload,
+    // Adds to the end of the queue, which plays before the rest of the context
     Queue(Vec<SongDescription>),
+    QueueNext(Vec<SongDescription>),
     Dequeue(String),
     SwitchDevice(Device),
+    // The device was switched by another Spotify client
+    SyncDevice(Device),
+    SetSleepTimer(Option<SleepTimer>),
+    SetRate(f32),
+    // Reported by the local player, which doesn't always do what it was asked to
+    SyncLocalPlayer(LocalPlayerState),
+    SkipUnavailable(String),
     SetAvailableDevices(Vec<ConnectDevice>),
+    RestoreSession(Box<PlaybackSession>),
 }
 
 impl From<PlaybackAction> for AppAction {
@@ -305,6 +633,26 @@ pub enum Device {
     Connect(ConnectDevice),
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LocalPlayerState {
+    Loading,
+    Playing(u32),
+    Paused(u32),
+    Stopped,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SleepTimer {
+    Minutes(u32),
+    EndOfTrack,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum SleepDeadline {
+    At(Instant),
+    EndOfTrack,
+}
+
 #[derive(Clone, Debug)]
 pub enum PlaybackEvent {
     PlaybackPaused,
@@ -312,7 +660,11 @@ pub enum PlaybackEvent {
     RepeatModeChanged(RepeatMode),
     TrackSeeked(u32),
     SeekSynced(u32),
+    // Unlike PlaybackPaused and PlaybackResumed, the player is already in that state
+    PlayingSynced(bool),
+    BufferingChanged(bool),
     VolumeSet(f64),
+    VolumeSynced(f64),
     TrackChanged(String),
     SourceChanged,
     Preload(String),
@@ -320,7 +672,10 @@ pub enum PlaybackEvent {
     PlaylistChanged,
     PlaybackStopped,
     SwitchedDevice(Device),
+    DeviceSynced(Device),
     AvailableDevicesChanged,
+    SleepTimerChanged,
+    RateChanged(f32),
 }
 
 impl From<PlaybackEvent> for AppEvent {
@@ -380,6 +735,22 @@ impl UpdatableState for PlaybackState {
                 self.set_shuffled(!self.is_shuffled);
                 vec![PlaybackEvent::ShuffleChanged(self.is_shuffled)]
             }
+            // Whether the track ended or was skipped, that's when this timer goes off:
+            // the next track is cued, but doesn't start
+            PlaybackAction::Next if self.sleeps_at_end_of_track() => {
+                self.sleep_timer = None;
+                let mut events = vec![PlaybackEvent::SleepTimerChanged];
+                if let Some(id) = self.play_next() {
+                    self.is_playing = false;
+                    self.seek_position.pause();
+                    events.push(PlaybackEvent::TrackChanged(id));
+                    events.push(PlaybackEvent::PlaybackPaused);
+                } else {
+                    self.stop();
+                    events.push(PlaybackEvent::PlaybackStopped);
+                }
+                events
+            }
             PlaybackAction::Next => {
                 if let Some(id) = self.play_next() {
                     vec![
@@ -422,6 +793,13 @@ impl UpdatableState for PlaybackState {
                     vec![]
                 }
             }
+            PlaybackAction::LoadPagedSongs(SongsSource::Recommendations { .. }, batch) => {
+                if self.autoplay(batch.songs) {
+                    self.queue_extended()
+                } else {
+                    vec![]
+                }
+            }
             PlaybackAction::LoadPagedSongs(source, batch)
                 if Some(&source) == self.source.as_ref() =>
             {
@@ -444,28 +822,103 @@ impl UpdatableState for PlaybackState {
             }
             PlaybackAction::Queue(tracks) => {
                 self.queue(tracks);
-                vec![PlaybackEvent::PlaylistChanged]
+                self.queue_extended()
+            }
+            PlaybackAction::QueueNext(tracks) => {
+                self.queue_next(tracks);
+                self.queue_extended()
             }
             PlaybackAction::Dequeue(id) => {
                 self.dequeue(&[id]);
                 vec![PlaybackEvent::PlaylistChanged]
             }
+            // Events of the local player are meaningless once we've switched to another device
+            PlaybackAction::SyncLocalPlayer(state)
+                if matches!(self.current_device, Device::Local) =>
+            {
+                self.sync_local_player(state)
+            }
+            PlaybackAction::SkipUnavailable(id) if matches!(self.current_device, Device::Local) => {
+                if self.current_song_id().as_deref() == Some(&id) {
+                    return self.update_with(Cow::Owned(PlaybackAction::Next));
+                }
+                // It was going to be preloaded, the one after it should be instead
+                self.dequeue(&[id]);
+                let mut events = vec![PlaybackEvent::PlaylistChanged];
+                events.extend(self.next_id().map(PlaybackEvent::Preload));
+                events
+            }
             PlaybackAction::Seek(pos) => {
-                self.seek_position.set(pos as u64 * 1000, true);
+                self.seek_position.set(pos as u64, true);
                 vec![PlaybackEvent::TrackSeeked(pos)]
             }
             PlaybackAction::SyncSeek(pos) => {
-                self.seek_position.set(pos as u64 * 1000, true);
+                self.seek_position.set(pos as u64, true);
                 vec![PlaybackEvent::SeekSynced(pos)]
             }
+            PlaybackAction::RestoreSession(session) => {
+                self.restore_session(*session);
+                let mut events = vec![
+                    PlaybackEvent::PlaylistChanged,
+                    PlaybackEvent::SourceChanged,
+                    PlaybackEvent::RepeatModeChanged(self.repeat),
+                    PlaybackEvent::ShuffleChanged(self.is_shuffled),
+                ];
+                if self.current_song_id().is_some() {
+                    events.push(PlaybackEvent::TrackSeeked(self.position_ms()));
+                }
+                events
+            }
             PlaybackAction::SetVolume(volume) => vec![PlaybackEvent::VolumeSet(volume)],
+            PlaybackAction::SyncVolume(volume) => vec![PlaybackEvent::VolumeSynced(volume)],
             PlaybackAction::SetAvailableDevices(list) => {
                 self.available_devices = list;
                 vec![PlaybackEvent::AvailableDevicesChanged]
             }
             PlaybackAction::SwitchDevice(new_device) => {
                 self.current_device = new_device.clone();
-                vec![PlaybackEvent::SwitchedDevice(new_device)]
+                let mut events = vec![PlaybackEvent::SwitchedDevice(new_device)];
+                if !matches!(self.current_device, Device::Local) {
+                    events.extend(self.set_rate(1.0));
+                }
+                events
+            }
+            // NaN would get through the clamping below
+            PlaybackAction::SetRate(rate) if !rate.is_finite() => vec![],
+            // Connect devices have no notion of playback speed
+            PlaybackAction::SetRate(rate) if matches!(self.current_device, Device::Local) => self
+                .set_rate(rate.clamp(MIN_PLAYBACK_RATE, MAX_PLAYBACK_RATE))
+                .into_iter()
+                .collect(),
+            PlaybackAction::SetSleepTimer(timer) => {
+                self.sleep_timer = timer.map(|timer| match timer {
+                    SleepTimer::Minutes(minutes) => SleepDeadline::At(
+                        Instant::now() + Duration::from_secs(60 * u64::from(minutes)),
+                    ),
+                    SleepTimer::EndOfTrack => SleepDeadline::EndOfTrack,
+                });
+                vec![PlaybackEvent::SleepTimerChanged]
+            }
+            PlaybackAction::SyncDevice(new_device) => {
+                let unchanged = match (&self.current_device, &new_device) {
+                    (Device::Local, Device::Local) => true,
+                    (Device::Connect(current), Device::Connect(new)) => current.id == new.id,
+                    _ => false,
+                };
+                if unchanged {
+                    return vec![];
+                }
+                self.current_device = new_device.clone();
+                let mut events = vec![PlaybackEvent::DeviceSynced(new_device)];
+                // Playback was taken away from us, it shouldn't resume here on its own
+                if matches!(self.current_device, Device::Local) && self.is_playing() {
+                    self.is_playing = false;
+                    events.push(PlaybackEvent::PlaybackPaused);
+                }
+                if !matches!(self.current_device, Device::Local) {
+                    events.extend(self.set_rate(1.0));
+                }
+                events
             }
             _ => vec![],
         }
@@ -510,6 +963,12 @@ impl PositionMillis {
     fn resume(&mut self) {
         self.last_resume_instant = Some(Instant::now());
     }
+
+    fn set_rate(&mut self, rate: f32) {
+        let playing = self.last_resume_instant.is_some();
+        self.set(self.current(), playing);
+        self.rate = rate;
+    }
 }
 
 #[cfg(test)]
@@ -566,7 +1025,7 @@ mod tests {
     #[test]
     fn test_play_one() {
         let mut state = PlaybackState::default();
-        state.queue(vec![song("foo")]);
+        state.set_queue(vec![song("foo")]);
 
         state.play("foo");
         assert!(state.is_playing());
@@ -582,20 +1041,86 @@ mod tests {
     #[test]
     fn test_queue() {
         let mut state = PlaybackState::default();
-        state.queue(vec![song("1"), song("2"), song("3")]);
+        state.set_queue(vec![song("1"), song("2"), song("3")]);
 
         assert_eq!(state.songs().len(), 3);
 
         state.play("2");
 
         state.queue(vec![song("4")]);
-        assert_eq!(state.songs().len(), 4);
+        assert_eq!(state.songs().len(), 3);
+        assert_eq!(state.next_up().len(), 1);
+        assert_eq!(state.next_id(), Some("4".to_string()));
+
+        state.queue_next(vec![song("5")]);
+        assert_eq!(state.next_id(), Some("5".to_string()));
+    }
+
+    #[test]
+    fn test_next_up_plays_before_context() {
+        let mut state = PlaybackState::default();
+        state.set_queue(vec![song("1"), song("2"), song("3")]);
+        state.play("1");
+        state.queue(vec![song("4"), song("5")]);
+
+        assert_eq!(state.play_next(), Some("4".to_string()));
+        assert_eq!(state.current_song_id(), Some("4".to_string()));
+        assert!(state.current_song_index().is_none());
+        assert_eq!(state.next_up().len(), 1);
+        assert_eq!(state.next_id(), Some("5".to_string()));
+
+        assert_eq!(state.play_next(), Some("5".to_string()));
+        assert_eq!(state.next_id(), Some("2".to_string()));
+
+        assert_eq!(state.play_next(), Some("2".to_string()));
+        assert_eq!(state.current_song_index(), Some(1));
+        assert_eq!(state.next_up().len(), 0);
+    }
+
+    #[test]
+    fn test_songs_in_order() {
+        let mut state = PlaybackState::default();
+        state.set_queue(vec![song("1"), song("2"), song("3")]);
+        state.play("1");
+        state.queue(vec![song("4"), song("5")]);
+
+        let ids = |state: &PlaybackState| -> Vec<String> {
+            state
+                .songs_in_order()
+                .into_iter()
+                .map(|song| song.id)
+                .collect()
+        };
+        assert_eq!(ids(&state), vec!["1", "4", "5", "2", "3"]);
+
+        state.play_next();
+        assert_eq!(ids(&state), vec!["1", "4", "5", "2", "3"]);
+
+        state.play_next();
+        state.play_next();
+        assert_eq!(ids(&state), vec!["1", "2", "3"]);
+    }
+
+    #[test]
+    fn test_play_from_next_up() {
+        let mut state = PlaybackState::default();
+        state.set_queue(vec![song("1"), song("2")]);
+        state.play("1");
+        state.queue(vec![song("3"), song("4")]);
+
+        assert!(state.play("4"));
+        assert_eq!(state.current_song_id(), Some("4".to_string()));
+        assert_eq!(state.next_up().len(), 1);
+
+        state.play_prev();
+        assert_eq!(state.current_song_id(), Some("1".to_string()));
+        assert_eq!(state.next_id(), Some("3".to_string()));
     }
 
     #[test]
     fn test_play_multiple() {
         let mut state = PlaybackState::default();
-        state.queue(vec![song("1"), song("2"), song("3")]);
+        state.set_queue(vec![song("1"), song("2"), song("3")]);
         assert_eq!(state.songs().len(), 3);
 
         state.play("2");
@@ -638,7 +1163,7 @@ mod tests {
     #[test]
     fn test_shuffle() {
         let mut state = PlaybackState::default();
-        state.queue(vec![song("1"), song("2"), song("3"), song("4")]);
+        state.set_queue(vec![song("1"), song("2"), song("3"), song("4")]);
 
         assert_eq!(state.songs().len(), 4);
 
@@ -670,32 +1195,25 @@ mod tests {
     #[test]
     fn test_shuffle_queue() {
         let mut state = PlaybackState::default();
-        state.queue(vec![song("1"), song("2"), song("3")]);
+        state.set_queue(vec![song("1"), song("2"), song("3")]);
 
         state.set_shuffled(true);
         assert!(state.is_shuffled());
 
         state.queue(vec![song("4")]);
+        assert_eq!(state.next_up().len(), 1);
 
         state.set_shuffled(false);
         assert!(!state.is_shuffled());
 
         let ids = state.song_ids();
-        assert_eq!(
-            ids,
-            vec![
-                "1".to_string(),
-                "2".to_string(),
-                "3".to_string(),
-                "4".to_string()
-            ]
-        );
+        assert_eq!(ids, vec!["1".to_string(), "2".to_string(), "3".to_string()]);
     }
 
     #[test]
     fn test_move() {
         let mut state = PlaybackState::default();
-        state.queue(vec![song("1"), song("2"), song("3")]);
+        state.set_queue(vec![song("1"), song("2"), song("3")]);
 
         state.play("2");
         assert!(state.is_playing());
@@ -726,7 +1244,7 @@ mod tests {
     #[test]
     fn test_dequeue_last() {
         let mut state = PlaybackState::default();
-        state.queue(vec![song("1"), song("2"), song("3")]);
+        state.set_queue(vec![song("1"), song("2"), song("3")]);
 
         state.play("3");
         assert!(state.is_playing());
@@ -738,7 +1256,7 @@ mod tests {
     #[test]
     fn test_dequeue_a_few_songs() {
         let mut state = PlaybackState::default();
-        state.queue(vec![
+        state.set_queue(vec![
             song("1"),
             song("2"),
             song("3"),
@@ -757,7 +1275,7 @@ mod tests {
     #[test]
     fn test_dequeue_all() {
         let mut state = PlaybackState::default();
-        state.queue(vec![song("3")]);
+        state.set_queue(vec![song("3")]);
 
         state.play("3");
         assert!(state.is_playing());
@@ -765,4 +1283,265 @@ mod tests {
         state.dequeue(&["3".to_string()]);
         assert_eq!(state.current_song_id(), None);
     }
+
+    #[test]
+    fn test_restore_session() {
+        let mut state = PlaybackState::default();
+        state.set_queue(vec![song("1"), song("2"), song("3"), song("4")]);
+        state.play("2");
+        state.set_shuffled(true);
+        state.play_next();
+        state.repeat = RepeatMode::Playlist;
+
+        let session = state.session();
+        let mut restored = PlaybackState::default();
+        restored.update_with(Cow::Owned(PlaybackAction::RestoreSession(Box::new(
+            session,
+        ))));
+
+        assert!(!restored.is_playing());
+        assert!(restored.is_shuffled());
+        assert_eq!(restored.repeat_mode(), RepeatMode::Playlist);
+        assert_eq!(restored.song_ids(), state.song_ids());
+        assert_eq!(restored.current_position(), state.current_position());
+        assert_eq!(restored.current_song_id(), state.current_song_id());
+        assert_eq!(restored.next_id(), state.next_id());
+    }
+
+    #[test]
+    fn test_restore_session_with_next_up() {
+        let mut state = PlaybackState::default();
+        state.set_queue(vec![song("1"), song("2"), song("3")]);
+        state.play("1");
+        state.queue(vec![song("4"), song("5")]);
+        state.play_next();
+        state.seek_position.set(500, false);
+
+        let session = state.session();
+        let mut restored = PlaybackState::default();
+        restored.update_with(Cow::Owned(PlaybackAction::RestoreSession(Box::new(
+            session,
+        ))));
+
+        assert_eq!(restored.current_song_id(), Some("4".to_string()));
+        assert_eq!(restored.position_ms(), 500);
+        assert_eq!(restored.next_id(), Some("5".to_string()));
+        assert_eq!(
+            restored.next_up().map_collect(|song| song.id),
+            vec!["5".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_sync_device() {
+        let mut state = PlaybackState::default();
+        state.set_queue(vec![song("1"), song("2")]);
+        state.play("1");
+
+        let device = ConnectDevice {
+            id: "receiver".to_string(),
+            label: "Spot".to_string(),
+            kind: ConnectDeviceKind::Computer,
+        };
+        let events = state.update_with(Cow::Owned(PlaybackAction::SyncDevice(Device::Connect(
+            device.clone(),
+        ))));
+        assert_eq!(events.len(), 1);
+        assert!(state.is_playing());
+
+        let events = state.update_with(Cow::Owned(PlaybackAction::SyncDevice(Device::Connect(
+            device,
+        ))));
+        assert!(events.is_empty());
+
+        let events = state.update_with(Cow::Owned(PlaybackAction::SyncDevice(Device::Local)));
+        assert!(matches!(
+            events.as_slice(),
+            [
+                PlaybackEvent::DeviceSynced(Device::Local),
+                PlaybackEvent::PlaybackPaused
+            ]
+        ));
+        assert!(!state.is_playing());
+    }
+
+    #[test]
+    fn test_sleep_timer() {
+        let mut state = PlaybackState::default();
+        state.set_queue(vec![song("1"), song("2")]);
+        state.play("1");
+        assert!(state.sleep_timer_remaining().is_none());
+
+        state.update_with(Cow::Owned(PlaybackAction::SetSleepTimer(Some(
+            SleepTimer::EndOfTrack,
+        ))));
+        let remaining = state.sleep_timer_remaining().unwrap();
+        assert!(remaining <= Duration::from_millis(1000));
+
+        state.update_with(Cow::Owned(PlaybackAction::SetSleepTimer(Some(
+            SleepTimer::Minutes(15),
+        ))));
+        let remaining = state.sleep_timer_remaining().unwrap();
+        assert!(remaining > Duration::from_secs(14 * 60));
+        assert!(remaining <= Duration::from_secs(15 * 60));
+
+        state.update_with(Cow::Owned(PlaybackAction::SetSleepTimer(None)));
+        assert!(state.sleep_timer_remaining().is_none());
+    }
+
+    #[test]
+    fn test_sleep_timer_end_of_track() {
+        let mut state = PlaybackState::default();
+        state.set_queue(vec![song("1"), song("2")]);
+        state.play("1");
+        state.update_with(Cow::Owned(PlaybackAction::SetSleepTimer(Some(
+            SleepTimer::EndOfTrack,
+        ))));
+
+        let events = state.update_with(Cow::Owned(PlaybackAction::Next));
+        assert!(matches!(
+            events.as_slice(),
+            [
+                PlaybackEvent::SleepTimerChanged,
+                PlaybackEvent::TrackChanged(_),
+                PlaybackEvent::PlaybackPaused
+            ]
+        ));
+        assert_eq!(state.current_song_id(), Some("2".to_string()));
+        assert!(!state.is_playing());
+        assert!(state.sleep_timer_remaining().is_none());
+
+        state.update_with(Cow::Owned(PlaybackAction::Next));
+        assert!(state.is_playing());
+    }
+
+    #[test]
+    fn test_rate() {
+        let mut state = PlaybackState::default();
+        assert_eq!(state.rate(), 1.0);
+
+        let events = state.update_with(Cow::Owned(PlaybackAction::SetRate(1.5)));
+        assert!(matches!(events.as_slice(), [PlaybackEvent::RateChanged(rate)] if *rate == 1.5));
+
+        let events = state.update_with(Cow::Owned(PlaybackAction::SetRate(1.5)));
+        assert!(events.is_empty());
+
+        state.update_with(Cow::Owned(PlaybackAction::SetRate(4.0)));
+        assert_eq!(state.rate(), MAX_PLAYBACK_RATE);
+
+        let events = state.update_with(Cow::Owned(PlaybackAction::SetRate(f32::NAN)));
+        assert!(events.is_empty());
+        assert_eq!(state.rate(), MAX_PLAYBACK_RATE);
+
+        let device = ConnectDevice {
+            id: "device".to_string(),
+            label: "Device".to_string(),
+            kind: ConnectDeviceKind::Phone,
+        };
+        let events = state.update_with(Cow::Owned(PlaybackAction::SwitchDevice(Device::Connect(
+            device,
+        ))));
+        assert!(matches!(events.last(), Some(PlaybackEvent::RateChanged(rate)) if *rate == 1.0));
+
+        let events = state.update_with(Cow::Owned(PlaybackAction::SetRate(1.5)));
+        assert!(events.is_empty());
+        assert_eq!(state.rate(), 1.0);
+    }
+
+    #[test]
+    fn test_sync_local_player() {
+        let mut state = PlaybackState::default();
+        state.set_queue(vec![song("1"), song("2")]);
+        state.play("1");
+
+        let events = state.update_with(Cow::Owned(PlaybackAction::SyncLocalPlayer(
+            LocalPlayerState::Loading,
+        )));
+        assert!(matches!(
+            events.as_slice(),
+            [PlaybackEvent::BufferingChanged(true)]
+        ));
+        assert!(state.is_buffering());
+
+        let events = state.update_with(Cow::Owned(PlaybackAction::SyncLocalPlayer(
+            LocalPlayerState::Paused(500),
+        )));
+        assert!(matches!(
+            events.as_slice(),
+            [
+                PlaybackEvent::BufferingChanged(false),
+                PlaybackEvent::PlayingSynced(false),
+                PlaybackEvent::SeekSynced(500)
+            ]
+        ));
+        assert!(!state.is_playing());
+        assert_eq!(state.position_ms(), 500);
+
+        state.update_with(Cow::Owned(PlaybackAction::SwitchDevice(Device::Connect(
+            ConnectDevice {
+                id: "device".to_string(),
+                label: "Device".to_string(),
+                kind: ConnectDeviceKind::Phone,
+            },
+        ))));
+        let events = state.update_with(Cow::Owned(PlaybackAction::SyncLocalPlayer(
+            LocalPlayerState::Stopped,
+        )));
+        assert!(events.is_empty());
+        assert_eq!(state.current_song_id(), Some("1".to_string()));
+    }
+
+    #[test]
+    fn test_skip_unavailable() {
+        let mut state = PlaybackState::default();
+        state.set_queue(vec![song("1"), song("2"), song("3")]);
+        state.play("1");
+
+        let events =
+            state.update_with(Cow::Owned(PlaybackAction::SkipUnavailable("2".to_string())));
+        assert!(matches!(
+            events.as_slice(),
+            [PlaybackEvent::PlaylistChanged, PlaybackEvent::Preload(id)] if id == "3"
+        ));
+        assert_eq!(state.song_ids(), vec!["1", "3"]);
+
+        let events =
+            state.update_with(Cow::Owned(PlaybackAction::SkipUnavailable("1".to_string())));
+        assert!(matches!(events.first(), Some(PlaybackEvent::TrackChanged(id)) if id == "3"));
+    }
+
+    #[test]
+    fn test_autoplay() {
+        let mut state = PlaybackState::default();
+        state.set_queue(vec![song("1"), song("2")]);
+        state.play("1");
+        assert!(state.autoplay_query().is_none());
+
+        state.play("2");
+        let query = state.autoplay_query().unwrap();
+        assert!(matches!(
+            &query.source,
+            SongsSource::Recommendations { seed_tracks, .. } if seed_tracks == &["2", "1"]
+        ));
+        assert_eq!(query.batch.offset, 2);
+
+        let batch = SongBatch {
+            songs: vec![song("1"), song("3")],
+            batch: query.batch,
+        };
+        let events = state.update_with(Cow::Owned(PlaybackAction::LoadPagedSongs(
+            query.source,
+            batch,
+        )));
+        assert!(matches!(
+            events.as_slice(),
+            [PlaybackEvent::PlaylistChanged, PlaybackEvent::Preload(id)] if id == "3"
+        ));
+        assert_eq!(state.song_ids(), vec!["1", "2", "3"]);
+        assert!(state.is_autoplayed("3"));
+        assert!(!state.is_autoplayed("1"));
+
+        state.repeat = RepeatMode::Playlist;
+        assert!(state.autoplay_query().is_none());
+    }
 }
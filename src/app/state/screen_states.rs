@@ -136,6 +136,16 @@ impl UpdatableState for PlaylistDetailsState {
                 self.songs.remove(&uris[..]).commit();
                 vec![BrowserEvent::PlaylistTracksRemoved(self.id.clone())]
             }
+            BrowserAction::MovePlaylistTrack { id, from, to } if id == &self.id => {
+                self.songs.move_item(*from, *to).commit();
+                vec![BrowserEvent::PlaylistTracksMoved(self.id.clone())]
+            }
+            BrowserAction::SetPlaylistSnapshot { id, snapshot_id } if id == &self.id => {
+                if let Some(p) = self.playlist.as_mut() {
+                    p.snapshot_id = Some(snapshot_id.clone());
+                }
+                vec![]
+            }
             _ => vec![],
         }
     }
@@ -327,6 +337,28 @@ pub struct SearchState {
     pub query: String,
     pub album_results: Vec<AlbumDescription>,
     pub artist_results: Vec<ArtistSummary>,
+    pub track_results: SongListModel,
+    pub playlist_results: Vec<PlaylistDescription>,
+    pub next_albums_page: Pagination<String>,
+    pub next_artists_page: Pagination<String>,
+    pub next_tracks_page: Pagination<String>,
+    pub next_playlists_page: Pagination<String>,
+}
+
+impl SearchState {
+    const BATCH_SIZE: usize = 10;
+
+    pub fn batch_size(&self) -> usize {
+        Self::BATCH_SIZE
+    }
+
+    fn reset_pages(&mut self) {
+        let query = &self.query;
+        self.next_albums_page = Pagination::new(query.clone(), Self::BATCH_SIZE);
+        self.next_artists_page = Pagination::new(query.clone(), Self::BATCH_SIZE);
+        self.next_tracks_page = Pagination::new(query.clone(), Self::BATCH_SIZE);
+        self.next_playlists_page = Pagination::new(query.clone(), Self::BATCH_SIZE);
+    }
 }
 
 impl Default for SearchState {
@@ -336,6 +368,12 @@ impl Default for SearchState {
             query: "".to_owned(),
             album_results: vec![],
             artist_results: vec![],
+            track_results: SongListModel::new(Self::BATCH_SIZE as u32),
+            playlist_results: vec![],
+            next_albums_page: Pagination::new("".to_owned(), Self::BATCH_SIZE),
+            next_artists_page: Pagination::new("".to_owned(), Self::BATCH_SIZE),
+            next_tracks_page: Pagination::new("".to_owned(), Self::BATCH_SIZE),
+            next_playlists_page: Pagination::new("".to_owned(), Self::BATCH_SIZE),
         }
     }
 }
@@ -348,11 +386,49 @@ impl UpdatableState for SearchState {
         match action.as_ref() {
             BrowserAction::Search(query) if query != &self.query => {
                 self.query = query.clone();
+                self.reset_pages();
                 vec![BrowserEvent::SearchUpdated]
             }
             BrowserAction::SetSearchResults(results) => {
-                self.album_results = results.albums.clone();
-                self.artist_results = results.artists.clone();
+                let SearchResults {
+                    albums,
+                    artists,
+                    tracks,
+                    playlists,
+                } = *results.clone();
+                self.reset_pages();
+                self.next_albums_page.reset_count(albums.len());
+                self.next_artists_page.reset_count(artists.len());
+                self.next_tracks_page.reset_count(tracks.songs.len());
+                self.next_playlists_page.reset_count(playlists.len());
+                self.album_results = albums;
+                self.artist_results = artists;
+                self.track_results
+                    .clear()
+                    .and(move |s| s.add(tracks))
+                    .commit();
+                self.playlist_results = playlists;
+                vec![BrowserEvent::SearchResultsUpdated]
+            }
+            BrowserAction::AppendSearchAlbums(query, albums) if query == &self.query => {
+                self.next_albums_page.set_loaded_count(albums.len());
+                self.album_results.extend(albums.iter().cloned());
+                vec![BrowserEvent::SearchResultsUpdated]
+            }
+            BrowserAction::AppendSearchArtists(query, artists) if query == &self.query => {
+                self.next_artists_page.set_loaded_count(artists.len());
+                self.artist_results.extend(artists.iter().cloned());
+                vec![BrowserEvent::SearchResultsUpdated]
+            }
+            BrowserAction::AppendSearchTracks(query, song_batch) if query == &self.query => {
+                self.next_tracks_page
+                    .set_loaded_count(song_batch.songs.len());
+                self.track_results.add(*song_batch.clone()).commit();
+                vec![BrowserEvent::SearchResultsUpdated]
+            }
+            BrowserAction::AppendSearchPlaylists(query, playlists) if query == &self.query => {
+                self.next_playlists_page.set_loaded_count(playlists.len());
+                self.playlist_results.extend(playlists.iter().cloned());
                 vec![BrowserEvent::SearchResultsUpdated]
             }
             _ => vec![],
@@ -463,4 +539,69 @@ mod tests {
         let next = &artist_state.next_page;
         assert_eq!(None, next.next_offset);
     }
+
+    fn search_song(id: usize) -> SongDescription {
+        SongDescription {
+            id: id.to_string(),
+            uri: "".to_string(),
+            title: "Title".to_string(),
+            artists: vec![],
+            album: AlbumRef {
+                id: "".to_string(),
+                name: "".to_string(),
+            },
+            duration: 1000,
+            art: None,
+            track_number: None,
+        }
+    }
+
+    fn search_batch(offset: usize, len: usize) -> SongBatch {
+        SongBatch {
+            songs: (offset..offset + len).map(search_song).collect(),
+            batch: Batch {
+                offset,
+                batch_size: 10,
+                total: 25,
+            },
+        }
+    }
+
+    #[test]
+    fn test_search_tracks_pages() {
+        let mut search_state = SearchState::default();
+        search_state.update_with(Cow::Owned(BrowserAction::Search("foo".to_owned())));
+        search_state.update_with(Cow::Owned(BrowserAction::SetSearchResults(Box::new(
+            SearchResults {
+                albums: vec![],
+                artists: vec![],
+                tracks: search_batch(0, 10),
+                playlists: vec![],
+            },
+        ))));
+
+        assert_eq!(Some(10), search_state.next_tracks_page.next_offset);
+        assert_eq!(None, search_state.next_albums_page.next_offset);
+
+        // Results for an outdated query are ignored
+        let events = search_state.update_with(Cow::Owned(BrowserAction::AppendSearchTracks(
+            "fo".to_owned(),
+            Box::new(search_batch(10, 10)),
+        )));
+        assert!(events.is_empty());
+        assert_eq!(search_state.track_results.partial_len(), 10);
+
+        search_state.update_with(Cow::Owned(BrowserAction::AppendSearchTracks(
+            "foo".to_owned(),
+            Box::new(search_batch(10, 10)),
+        )));
+        assert_eq!(Some(20), search_state.next_tracks_page.next_offset);
+
+        search_state.update_with(Cow::Owned(BrowserAction::AppendSearchTracks(
+            "foo".to_owned(),
+            Box::new(search_batch(20, 5)),
+        )));
+        assert_eq!(None, search_state.next_tracks_page.next_offset);
+        assert_eq!(search_state.track_results.partial_len(), 25);
+    }
 }
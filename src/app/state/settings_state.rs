@@ -17,6 +17,8 @@ impl From<SettingsAction> for AppAction {
 #[derive(Clone, Debug)]
 pub enum SettingsEvent {
     PlayerSettingsChanged,
+    CacheSettingsChanged,
+    AutoplayToggled,
 }
 
 impl From<SettingsEvent> for AppEvent {
@@ -41,12 +43,20 @@ impl UpdatableState for SettingsState {
                 let new_settings = SpotSettings::new_from_gsettings().unwrap_or_default();
                 let player_settings_changed =
                     new_settings.player_settings != old_settings.player_settings;
+                let cache_settings_changed = new_settings.cache != old_settings.cache;
+                let autoplay_changed = new_settings.autoplay != old_settings.autoplay;
                 self.settings = new_settings;
+                let mut events = vec![];
                 if player_settings_changed {
-                    vec![SettingsEvent::PlayerSettingsChanged.into()]
-                } else {
-                    vec![]
+                    events.push(SettingsEvent::PlayerSettingsChanged.into());
                 }
+                if cache_settings_changed {
+                    events.push(SettingsEvent::CacheSettingsChanged.into());
+                }
+                if autoplay_changed {
+                    events.push(SettingsEvent::AutoplayToggled.into());
+                }
+                events
             }
         }
     }
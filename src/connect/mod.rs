@@ -9,7 +9,22 @@ use crate::api::SpotifyApiClient;
 use crate::app::AppAction;
 
 mod player;
-pub use player::ConnectCommand;
+pub use player::{ConnectCommand, TransferredPlayback, TransferredSongs};
+
+// Devices push their changes to us through the session (see ConnectCommand::RemoteStateChanged),
+// polling is only a fallback for the updates we might miss.
+// We poll often right after sending a command, and less and less as time goes by.
+const MIN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+// Devices tend to send several updates in a row
+const SYNC_REQUEST_DELAY: Duration = Duration::from_millis(300);
+
+fn poll_interval(time_since_last_command: Option<Duration>) -> Duration {
+    time_since_last_command
+        .map(|elapsed| (elapsed / 5).clamp(MIN_POLL_INTERVAL, MAX_POLL_INTERVAL))
+        .unwrap_or(MAX_POLL_INTERVAL)
+}
 
 #[tokio::main]
 async fn connect_server(
@@ -17,13 +32,22 @@ async fn connect_server(
     action_sender: UnboundedSender<AppAction>,
     receiver: UnboundedReceiver<ConnectCommand>,
 ) {
-    let player = Arc::new(player::ConnectPlayer::new(api, action_sender));
+    let (sync_sender, mut sync_requests) = unbounded();
+    let player = Arc::new(player::ConnectPlayer::new(api, action_sender, sync_sender));
 
     let player_clone = Arc::clone(&player);
     task::spawn(async move {
-        let mut interval = time::interval(Duration::from_secs(5));
         loop {
-            interval.tick().await;
+            let interval = poll_interval(player_clone.time_since_last_command());
+            tokio::select! {
+                _ = time::sleep(interval) => {}
+                // The interval shrinks right after a command, it has to be computed again
+                _ = player_clone.wait_for_command() => continue,
+                Some(_) = sync_requests.next() => {
+                    time::sleep(SYNC_REQUEST_DELAY).await;
+                    while let Ok(Some(_)) = sync_requests.try_next() {}
+                }
+            }
             if player_clone.has_device() {
                 player_clone.sync_state().await;
             }
@@ -45,3 +69,23 @@ pub fn start_connect_server(
 
     sender
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_poll_interval() {
+        assert_eq!(poll_interval(None), MAX_POLL_INTERVAL);
+        assert_eq!(poll_interval(Some(Duration::ZERO)), MIN_POLL_INTERVAL);
+        assert_eq!(
+            poll_interval(Some(Duration::from_secs(20))),
+            Duration::from_secs(4)
+        );
+        assert_eq!(
+            poll_interval(Some(Duration::from_secs(3600))),
+            MAX_POLL_INTERVAL
+        );
+    }
+}
@@ -1,18 +1,45 @@
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
 use futures::channel::mpsc::UnboundedSender;
 use gettextrs::gettext;
+use tokio::sync::Notify;
 
 use crate::api::{SpotifyApiClient, SpotifyApiError, SpotifyResult};
 use crate::app::models::{ConnectPlayerState, RepeatMode, SongDescription};
 use crate::app::state::{Device, PlaybackAction};
 use crate::app::{AppAction, SongsSource};
 
+#[derive(Debug)]
+pub enum TransferredSongs {
+    InContext { source: SongsSource, offset: usize },
+    NoContext { songs: Vec<String>, offset: usize },
+}
+
+// What's needed to pick up the playback where the local player left it
+#[derive(Debug)]
+pub struct TransferredPlayback {
+    pub songs: TransferredSongs,
+    pub position_ms: u32,
+    pub is_playing: bool,
+    pub shuffle: bool,
+    pub repeat: RepeatMode,
+}
+
 #[derive(Debug)]
 pub enum ConnectCommand {
     SetDevice(String),
+    // Moves the playback to a device, from the local player or from another device
+    TransferPlayback(String, Option<TransferredPlayback>),
+    // Forgets about the device without touching its playback
+    UnsetDevice,
+    // One of the devices of the account notified the others of a change
+    RemoteStateChanged,
+    // Spot's own receiver stopped playing, maybe because another device took over
+    ReceiverStopped,
     PlayerLoadInContext {
         source: SongsSource,
         offset: usize,
@@ -37,12 +64,20 @@ pub struct ConnectPlayer {
     device_id: RwLock<Option<String>>,
     last_queue: RwLock<u64>,
     last_state: RwLock<ConnectPlayerState>,
+    last_command: RwLock<Option<Instant>>,
+    // Wakes up the poll loop, which polls more often right after a command
+    command_sent: Notify,
+    queue_outdated: AtomicBool,
+    // Set until we know whether the receiver is still the active device
+    receiver_stopped: AtomicBool,
+    sync_requests: UnboundedSender<()>,
 }
 
 impl ConnectPlayer {
     pub fn new(
         api: Arc<dyn SpotifyApiClient + Send + Sync>,
         action_sender: UnboundedSender<AppAction>,
+        sync_requests: UnboundedSender<()>,
     ) -> Self {
         Self {
             api: api.clone(),
@@ -50,6 +85,11 @@ impl ConnectPlayer {
             device_id: Default::default(),
             last_queue: Default::default(),
             last_state: Default::default(),
+            last_command: Default::default(),
+            command_sent: Notify::new(),
+            queue_outdated: AtomicBool::new(true),
+            receiver_stopped: AtomicBool::new(false),
+            sync_requests,
         }
     }
 
@@ -85,8 +125,17 @@ impl ConnectPlayer {
     }
 
     async fn apply_remote_state(&self, state: &ConnectPlayerState) {
-        if let Some(songs) = self.get_queue_if_changed().await {
-            self.send_actions([PlaybackAction::LoadSongs(songs).into()]);
+        // The queue is only fetched again when we have reasons to believe it changed
+        let song_changed = self
+            .last_state
+            .read()
+            .map(|last_state| last_state.current_song_id != state.current_song_id)
+            .unwrap_or(true);
+        let queue_outdated = self.queue_outdated.swap(false, Ordering::Relaxed);
+        if song_changed || queue_outdated {
+            if let Some(songs) = self.get_queue_if_changed().await {
+                self.send_actions([PlaybackAction::LoadSongs(songs).into()]);
+            }
         }
 
         let play_pause = if state.is_playing {
@@ -103,6 +152,17 @@ impl ConnectPlayer {
         ]);
     }
 
+    pub fn time_since_last_command(&self) -> Option<Duration> {
+        self.last_command
+            .read()
+            .ok()
+            .and_then(|it| it.map(|instant| instant.elapsed()))
+    }
+
+    pub async fn wait_for_command(&self) {
+        self.command_sent.notified().await
+    }
+
     pub fn has_device(&self) -> bool {
         self.device_id
             .read()
@@ -117,6 +177,14 @@ impl ConnectPlayer {
                 self.device_lost();
                 return;
         };
+        if self.receiver_stopped.load(Ordering::Relaxed) {
+            let device_id = self.device_id.read().ok().and_then(|id| id.clone());
+            if state.device_id != device_id {
+                self.receiver_stopped.store(false, Ordering::Relaxed);
+                self.send_actions([PlaybackAction::SyncDevice(Device::Local).into()]);
+                return;
+            }
+        }
         self.apply_remote_state(&state).await;
         if let Ok(mut last_state) = self.last_state.write() {
             *last_state = state;
@@ -145,7 +213,7 @@ impl ConnectPlayer {
         if is_diff_song {
             let context = source.spotify_uri().unwrap();
             self.api
-                .player_play_in_context(device_id, context, offset)
+                .player_play_in_context(device_id, context, offset, 0)
                 .await
         } else if is_paused {
             self.api.player_resume(device_id).await
@@ -178,6 +246,7 @@ impl ConnectPlayer {
                         .map(|s| format!("spotify:track:{}", s))
                         .collect(),
                     offset,
+                    0,
                 )
                 .await
         } else if is_paused {
@@ -187,6 +256,62 @@ impl ConnectPlayer {
         }
     }
 
+    async fn transfer_playback(
+        &self,
+        device_id: String,
+        previous_device_id: Option<String>,
+        playback: Option<TransferredPlayback>,
+    ) -> SpotifyResult<()> {
+        // Devices know how to hand over their playback to each other
+        if previous_device_id.is_some() {
+            let is_playing = self
+                .last_state
+                .read()
+                .map(|state| state.is_playing)
+                .unwrap_or(false);
+            return self.api.player_transfer(device_id, is_playing).await;
+        }
+        let Some(playback) = playback else {
+            return Ok(());
+        };
+        match playback.songs {
+            TransferredSongs::InContext { source, offset } => {
+                let context = source.spotify_uri().unwrap();
+                self.api
+                    .player_play_in_context(
+                        device_id.clone(),
+                        context,
+                        offset,
+                        playback.position_ms,
+                    )
+                    .await?
+            }
+            TransferredSongs::NoContext { songs, offset } => {
+                self.api
+                    .player_play_no_context(
+                        device_id.clone(),
+                        songs
+                            .into_iter()
+                            .map(|s| format!("spotify:track:{}", s))
+                            .collect(),
+                        offset,
+                        playback.position_ms,
+                    )
+                    .await?
+            }
+        };
+        self.api
+            .player_shuffle(device_id.clone(), playback.shuffle)
+            .await?;
+        self.api
+            .player_repeat(device_id.clone(), playback.repeat)
+            .await?;
+        if !playback.is_playing {
+            self.api.player_pause(device_id).await?;
+        }
+        Ok(())
+    }
+
     async fn handle_other_command(
         &self,
         device_id: String,
@@ -223,12 +348,43 @@ impl ConnectPlayer {
     }
 
     pub async fn handle_command(&self, command: ConnectCommand) -> Option<()> {
+        if !matches!(command, ConnectCommand::RemoteStateChanged) {
+            self.last_command.write().ok()?.replace(Instant::now());
+            self.command_sent.notify_one();
+        }
         let device_lost = match command {
             ConnectCommand::SetDevice(new_device_id) => {
                 self.device_id.write().ok()?.replace(new_device_id);
+                self.receiver_stopped.store(false, Ordering::Relaxed);
+                self.queue_outdated.store(true, Ordering::Relaxed);
                 self.sync_state().await;
                 false
             }
+            ConnectCommand::TransferPlayback(new_device_id, playback) => {
+                let previous_device_id =
+                    self.device_id.write().ok()?.replace(new_device_id.clone());
+                self.receiver_stopped.store(false, Ordering::Relaxed);
+                self.queue_outdated.store(true, Ordering::Relaxed);
+                let result = self
+                    .transfer_playback(new_device_id, previous_device_id, playback)
+                    .await;
+                self.sync_state().await;
+                matches!(result, Err(SpotifyApiError::BadStatus(404, _)))
+            }
+            ConnectCommand::RemoteStateChanged => {
+                self.queue_outdated.store(true, Ordering::Relaxed);
+                let _ = self.sync_requests.unbounded_send(());
+                false
+            }
+            ConnectCommand::ReceiverStopped => {
+                self.receiver_stopped.store(true, Ordering::Relaxed);
+                let _ = self.sync_requests.unbounded_send(());
+                false
+            }
+            ConnectCommand::UnsetDevice => {
+                let _ = self.device_id.write().ok()?.take();
+                false
+            }
             ConnectCommand::PlayerStop => {
                 let device_id = self.device_id.write().ok()?.take();
                 if let Some(old_id) = device_id {
@@ -1,14 +1,15 @@
 use futures::channel::mpsc::UnboundedSender;
 use std::rc::Rc;
+use std::time::Duration;
 
 use crate::app::{
     components::EventListener,
     models::{RepeatMode, SongDescription},
-    state::PlaybackEvent,
-    AppEvent, AppModel,
+    state::{LoginEvent, PlaybackEvent},
+    AppEvent, AppModel, SongsSource,
 };
 
-use super::types::{LoopStatus, PlaybackStatus, TrackMetadata};
+use super::types::{track_path, LoopStatus, PlaybackStatus, PlaylistMetadata, TrackMetadata};
 
 #[derive(Debug)]
 pub enum MprisStateUpdate {
@@ -18,7 +19,11 @@ pub enum MprisStateUpdate {
         current: Option<TrackMetadata>,
         has_next: bool,
     },
-    SetPositionMs(u128),
+    SetPositionMs {
+        position: u128,
+        // Whether the user seeked, as opposed to the position being corrected by the player
+        seeked: bool,
+    },
     SetLoopStatus {
         has_prev: bool,
         loop_status: LoopStatus,
@@ -26,6 +31,34 @@ pub enum MprisStateUpdate {
     },
     SetShuffled(bool),
     SetPlaying(PlaybackStatus),
+    SetTrackList {
+        tracks: Vec<TrackMetadata>,
+        current: Option<String>,
+    },
+    SetSleepTimer(Option<Duration>),
+    SetRate(f32),
+    SetPlaylists(Vec<PlaylistMetadata>),
+    SetActivePlaylist(Option<PlaylistMetadata>),
+}
+
+fn track_meta(song: SongDescription) -> TrackMetadata {
+    let SongDescription {
+        id,
+        title,
+        artists,
+        album,
+        duration,
+        art,
+        ..
+    } = song;
+    TrackMetadata {
+        id: track_path(&id),
+        length: 1000 * duration as u64,
+        title,
+        album: album.name,
+        artist: artists.into_iter().map(|a| a.name).collect(),
+        art,
+    }
 }
 
 pub struct AppPlaybackStateListener {
@@ -39,30 +72,46 @@ impl AppPlaybackStateListener {
     }
 
     fn make_track_meta(&self) -> Option<TrackMetadata> {
-        let SongDescription {
-            id,
-            title,
-            artists,
-            album,
-            duration,
-            art,
-            ..
-        } = self.app_model.get_state().playback.current_song()?;
-        Some(TrackMetadata {
-            id: format!("/dev/alextren/Spot/Track/{id}"),
-            length: 1000 * duration as u64,
-            title,
-            album: album.name,
-            artist: artists.into_iter().map(|a| a.name).collect(),
-            art,
-        })
+        let song = self.app_model.get_state().playback.current_song()?;
+        Some(track_meta(song))
+    }
+
+    fn make_playlists(&self) -> Vec<PlaylistMetadata> {
+        self.app_model
+            .get_state()
+            .logged_user
+            .playlists
+            .iter()
+            .filter_map(|playlist| PlaylistMetadata::new(&playlist.id, playlist.title.clone()))
+            .collect()
+    }
+
+    // Only playlists of the user's library are listed, so only those can be active
+    fn make_active_playlist(&self) -> Option<PlaylistMetadata> {
+        let state = self.app_model.get_state();
+        let id = match state.playback.current_source()? {
+            SongsSource::Playlist(id) => id,
+            _ => return None,
+        };
+        let playlist = state.logged_user.playlists.iter().find(|p| &p.id == id)?;
+        PlaylistMetadata::new(id, playlist.title.clone())
+    }
+
+    fn make_track_list(&self) -> Vec<TrackMetadata> {
+        self.app_model
+            .get_state()
+            .playback
+            .songs_in_order()
+            .into_iter()
+            .map(track_meta)
+            .collect()
     }
 
     fn has_prev_next(&self) -> (bool, bool) {
         let state = self.app_model.get_state();
         (
             state.playback.prev_index().is_some(),
-            state.playback.next_index().is_some(),
+            state.playback.next_id().is_some(),
         )
     }
 
@@ -75,12 +124,32 @@ impl AppPlaybackStateListener {
         }
     }
 
+    fn sleep_timer_update_for(&self, event: &PlaybackEvent) -> Option<MprisStateUpdate> {
+        let remaining = self.app_model.get_state().playback.sleep_timer_remaining();
+        match event {
+            PlaybackEvent::SleepTimerChanged => Some(MprisStateUpdate::SetSleepTimer(remaining)),
+            // A timer set for the end of the track moves along with the playback
+            PlaybackEvent::PlaybackPaused
+            | PlaybackEvent::PlaybackResumed
+            | PlaybackEvent::PlayingSynced(_)
+            | PlaybackEvent::TrackChanged(_)
+            | PlaybackEvent::TrackSeeked(_)
+            | PlaybackEvent::SeekSynced(_)
+            | PlaybackEvent::RateChanged(_)
+                if remaining.is_some() =>
+            {
+                Some(MprisStateUpdate::SetSleepTimer(remaining))
+            }
+            _ => None,
+        }
+    }
+
     fn update_for(&self, event: &PlaybackEvent) -> Option<MprisStateUpdate> {
         match event {
-            PlaybackEvent::PlaybackPaused => {
+            PlaybackEvent::PlaybackPaused | PlaybackEvent::PlayingSynced(false) => {
                 Some(MprisStateUpdate::SetPlaying(PlaybackStatus::Paused))
             }
-            PlaybackEvent::PlaybackResumed => {
+            PlaybackEvent::PlaybackResumed | PlaybackEvent::PlayingSynced(true) => {
                 Some(MprisStateUpdate::SetPlaying(PlaybackStatus::Playing))
             }
             PlaybackEvent::PlaybackStopped => {
@@ -107,24 +176,74 @@ impl AppPlaybackStateListener {
             PlaybackEvent::ShuffleChanged(shuffled) => {
                 Some(MprisStateUpdate::SetShuffled(*shuffled))
             }
-            PlaybackEvent::TrackSeeked(pos) | PlaybackEvent::SeekSynced(pos) => {
-                let pos = 1000 * (*pos as u128);
-                Some(MprisStateUpdate::SetPositionMs(pos))
+            PlaybackEvent::TrackSeeked(pos) => Some(MprisStateUpdate::SetPositionMs {
+                position: 1000 * (*pos as u128),
+                seeked: true,
+            }),
+            PlaybackEvent::SeekSynced(pos) => Some(MprisStateUpdate::SetPositionMs {
+                position: 1000 * (*pos as u128),
+                seeked: false,
+            }),
+            PlaybackEvent::VolumeSet(vol) | PlaybackEvent::VolumeSynced(vol) => {
+                Some(MprisStateUpdate::SetVolume(*vol))
+            }
+            PlaybackEvent::RateChanged(rate) => Some(MprisStateUpdate::SetRate(*rate)),
+            PlaybackEvent::PlaylistChanged | PlaybackEvent::SourceChanged => {
+                Some(MprisStateUpdate::SetTrackList {
+                    tracks: self.make_track_list(),
+                    current: self.make_track_meta().map(|t| t.id),
+                })
             }
-            PlaybackEvent::VolumeSet(vol) => Some(MprisStateUpdate::SetVolume(*vol)),
             _ => None,
         }
     }
+
+    // Queueing songs can give us a next one, without the current track changing
+    fn navigation_update_for(&self, event: &PlaybackEvent) -> Option<MprisStateUpdate> {
+        match event {
+            PlaybackEvent::PlaylistChanged => {
+                let (has_prev, has_next) = self.has_prev_next();
+                Some(MprisStateUpdate::SetLoopStatus {
+                    has_prev,
+                    has_next,
+                    loop_status: self.loop_status(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn playlists_update_for(&self, event: &AppEvent) -> Vec<MprisStateUpdate> {
+        match event {
+            AppEvent::LoginEvent(LoginEvent::UserPlaylistsLoaded) => vec![
+                MprisStateUpdate::SetPlaylists(self.make_playlists()),
+                MprisStateUpdate::SetActivePlaylist(self.make_active_playlist()),
+            ],
+            AppEvent::PlaybackEvent(PlaybackEvent::SourceChanged) => {
+                vec![MprisStateUpdate::SetActivePlaylist(
+                    self.make_active_playlist(),
+                )]
+            }
+            _ => vec![],
+        }
+    }
 }
 
 impl EventListener for AppPlaybackStateListener {
     fn on_event(&mut self, event: &AppEvent) {
+        let mut updates = self.playlists_update_for(event);
         if let AppEvent::PlaybackEvent(event) = event {
-            if let Some(update) = self.update_for(event) {
-                self.sender
-                    .unbounded_send(update)
-                    .expect("Could not send event to DBUS server");
-            }
+            updates.extend(
+                self.update_for(event)
+                    .into_iter()
+                    .chain(self.navigation_update_for(event))
+                    .chain(self.sleep_timer_update_for(event)),
+            );
+        }
+        for update in updates {
+            self.sender
+                .unbounded_send(update)
+                .expect("Could not send event to DBUS server");
         }
     }
 }
@@ -2,6 +2,7 @@ use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
 use futures::StreamExt;
 use std::rc::Rc;
 use std::thread;
+use std::time::Duration;
 use zbus::Connection;
 
 use crate::app::{AppAction, AppModel};
@@ -9,7 +10,17 @@ use crate::app::{AppAction, AppModel};
 mod mpris;
 pub use mpris::*;
 
+mod playlists;
+use playlists::SpotMprisPlaylists;
+
+mod sleep_timer;
+use sleep_timer::SpotSleepTimer;
+
 mod types;
+use types::{PlaylistMetadata, TrackMetadata};
+
+// Players correct their position every now and then, only bigger jumps are worth signaling
+const SEEKED_THRESHOLD_MICROS: u128 = 1_000_000;
 
 mod listener;
 use listener::*;
@@ -18,6 +29,9 @@ use listener::*;
 async fn dbus_server(
     mpris: SpotMpris,
     player: SpotMprisPlayer,
+    track_list: SpotMprisTrackList,
+    playlists: SpotMprisPlaylists,
+    sleep_timer: SpotSleepTimer,
     receiver: UnboundedReceiver<MprisStateUpdate>,
 ) -> zbus::Result<()> {
     let connection = Connection::session().await?;
@@ -29,58 +43,42 @@ async fn dbus_server(
         .object_server()
         .at("/org/mpris/MediaPlayer2", player)
         .await?;
+    connection
+        .object_server()
+        .at("/org/mpris/MediaPlayer2", track_list)
+        .await?;
+    connection
+        .object_server()
+        .at("/org/mpris/MediaPlayer2", playlists)
+        .await?;
+    connection
+        .object_server()
+        .at("/org/mpris/MediaPlayer2", sleep_timer)
+        .await?;
     connection
         .request_name("org.mpris.MediaPlayer2.Spot")
         .await?;
 
     receiver
         .for_each(|update| async {
-            if let Ok(player_ref) = connection
-                .object_server()
-                .interface::<_, SpotMprisPlayer>("/org/mpris/MediaPlayer2")
-                .await
-            {
-                let mut player = player_ref.get_mut().await;
-                let ctxt = player_ref.signal_context();
-                let res: zbus::Result<()> = match update {
-                    MprisStateUpdate::SetVolume(volume) => {
-                        player.state_mut().set_volume(volume);
-                        player.volume_changed(ctxt).await
-                    }
-                    MprisStateUpdate::SetCurrentTrack {
-                        has_prev,
-                        has_next,
-                        current,
-                    } => {
-                        player.state_mut().set_has_prev(has_prev);
-                        player.state_mut().set_has_next(has_next);
-                        player.state_mut().set_current_track(current);
-                        player.notify_current_track_changed(ctxt).await
-                    }
-                    MprisStateUpdate::SetPositionMs(position) => {
-                        player.state_mut().set_position(position);
-                        Ok(())
-                    }
-                    MprisStateUpdate::SetLoopStatus {
-                        has_prev,
-                        has_next,
-                        loop_status,
-                    } => {
-                        player.state_mut().set_has_prev(has_prev);
-                        player.state_mut().set_has_next(has_next);
-                        player.state_mut().set_loop_status(loop_status);
-                        player.notify_loop_status(ctxt).await
-                    }
-                    MprisStateUpdate::SetShuffled(shuffled) => {
-                        player.state_mut().set_shuffled(shuffled);
-                        player.shuffle_changed(ctxt).await
-                    }
-                    MprisStateUpdate::SetPlaying(status) => {
-                        player.state_mut().set_playing(status);
-                        player.playback_status_changed(ctxt).await
-                    }
-                };
-                res.expect("Signal emission failed");
+            let res = match update {
+                MprisStateUpdate::SetTrackList { tracks, current } => {
+                    update_track_list(&connection, tracks, current).await
+                }
+                MprisStateUpdate::SetSleepTimer(remaining) => {
+                    update_sleep_timer(&connection, remaining).await
+                }
+                MprisStateUpdate::SetPlaylists(playlists) => {
+                    update_playlists(&connection, playlists).await
+                }
+                MprisStateUpdate::SetActivePlaylist(active) => {
+                    update_active_playlist(&connection, active).await
+                }
+                update => update_player(&connection, update).await,
+            };
+            // A signal we couldn't emit isn't worth losing the whole MPRIS service over
+            if let Err(err) = res {
+                warn!("MPRIS signal emission failed: {}", err);
             }
         })
         .await;
@@ -88,16 +86,152 @@ async fn dbus_server(
     Ok(())
 }
 
+async fn update_player(connection: &Connection, update: MprisStateUpdate) -> zbus::Result<()> {
+    if let Ok(player_ref) = connection
+        .object_server()
+        .interface::<_, SpotMprisPlayer>("/org/mpris/MediaPlayer2")
+        .await
+    {
+        let mut player = player_ref.get_mut().await;
+        let ctxt = player_ref.signal_context();
+        match update {
+            MprisStateUpdate::SetVolume(volume) => {
+                player.state_mut().set_volume(volume);
+                player.volume_changed(ctxt).await
+            }
+            MprisStateUpdate::SetCurrentTrack {
+                has_prev,
+                has_next,
+                current,
+            } => {
+                player.state_mut().set_has_prev(has_prev);
+                player.state_mut().set_has_next(has_next);
+                player.state_mut().set_current_track(current);
+                player.notify_current_track_changed(ctxt).await
+            }
+            MprisStateUpdate::SetPositionMs { position, seeked } => {
+                let drift = player.state_mut().position().abs_diff(position);
+                player.state_mut().set_position(position);
+                if seeked || drift > SEEKED_THRESHOLD_MICROS {
+                    SpotMprisPlayer::seeked(ctxt, position as i64).await
+                } else {
+                    Ok(())
+                }
+            }
+            MprisStateUpdate::SetLoopStatus {
+                has_prev,
+                has_next,
+                loop_status,
+            } => {
+                player.state_mut().set_has_prev(has_prev);
+                player.state_mut().set_has_next(has_next);
+                player.state_mut().set_loop_status(loop_status);
+                player.notify_loop_status(ctxt).await
+            }
+            MprisStateUpdate::SetShuffled(shuffled) => {
+                player.state_mut().set_shuffled(shuffled);
+                player.shuffle_changed(ctxt).await
+            }
+            MprisStateUpdate::SetPlaying(status) => {
+                player.state_mut().set_playing(status);
+                player.playback_status_changed(ctxt).await
+            }
+            MprisStateUpdate::SetRate(rate) => {
+                player.state_mut().set_rate(rate);
+                player.rate_changed(ctxt).await
+            }
+            MprisStateUpdate::SetTrackList { .. }
+            | MprisStateUpdate::SetSleepTimer(_)
+            | MprisStateUpdate::SetPlaylists(_)
+            | MprisStateUpdate::SetActivePlaylist(_) => Ok(()),
+        }
+    } else {
+        Ok(())
+    }
+}
+
+async fn update_track_list(
+    connection: &Connection,
+    tracks: Vec<TrackMetadata>,
+    current: Option<String>,
+) -> zbus::Result<()> {
+    if let Ok(track_list_ref) = connection
+        .object_server()
+        .interface::<_, SpotMprisTrackList>("/org/mpris/MediaPlayer2")
+        .await
+    {
+        let mut track_list = track_list_ref.get_mut().await;
+        let ctxt = track_list_ref.signal_context();
+        track_list.set_tracks(ctxt, tracks, current).await
+    } else {
+        Ok(())
+    }
+}
+
+async fn update_playlists(
+    connection: &Connection,
+    playlists: Vec<PlaylistMetadata>,
+) -> zbus::Result<()> {
+    if let Ok(playlists_ref) = connection
+        .object_server()
+        .interface::<_, SpotMprisPlaylists>("/org/mpris/MediaPlayer2")
+        .await
+    {
+        let mut mpris_playlists = playlists_ref.get_mut().await;
+        let ctxt = playlists_ref.signal_context();
+        mpris_playlists.set_playlists(ctxt, playlists).await
+    } else {
+        Ok(())
+    }
+}
+
+async fn update_active_playlist(
+    connection: &Connection,
+    active: Option<PlaylistMetadata>,
+) -> zbus::Result<()> {
+    if let Ok(playlists_ref) = connection
+        .object_server()
+        .interface::<_, SpotMprisPlaylists>("/org/mpris/MediaPlayer2")
+        .await
+    {
+        let mut mpris_playlists = playlists_ref.get_mut().await;
+        let ctxt = playlists_ref.signal_context();
+        mpris_playlists.set_active(ctxt, active).await
+    } else {
+        Ok(())
+    }
+}
+
+async fn update_sleep_timer(
+    connection: &Connection,
+    remaining: Option<Duration>,
+) -> zbus::Result<()> {
+    if let Ok(sleep_timer_ref) = connection
+        .object_server()
+        .interface::<_, SpotSleepTimer>("/org/mpris/MediaPlayer2")
+        .await
+    {
+        let mut sleep_timer = sleep_timer_ref.get_mut().await;
+        let ctxt = sleep_timer_ref.signal_context();
+        sleep_timer.set_remaining(ctxt, remaining).await
+    } else {
+        Ok(())
+    }
+}
+
 pub fn start_dbus_server(
     app_model: Rc<AppModel>,
     sender: UnboundedSender<AppAction>,
 ) -> AppPlaybackStateListener {
     let mpris = SpotMpris::new(sender.clone());
-    let player = SpotMprisPlayer::new(sender);
+    let player = SpotMprisPlayer::new(app_model.get_spotify(), sender.clone());
+    let track_list = SpotMprisTrackList::new(app_model.get_spotify(), sender.clone());
+    let playlists = SpotMprisPlaylists::new(app_model.get_spotify(), sender.clone());
+    let sleep_timer = SpotSleepTimer::new(sender);
 
     let (sender, receiver) = unbounded();
 
-    thread::spawn(move || dbus_server(mpris, player, receiver));
+    thread::spawn(move || dbus_server(mpris, player, track_list, playlists, sleep_timer, receiver));
 
     AppPlaybackStateListener::new(app_model, sender)
 }
@@ -2,17 +2,41 @@
 #![allow(unused_variables)]
 
 use std::collections::HashMap;
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
+use std::sync::Arc;
 
 use futures::channel::mpsc::UnboundedSender;
 use zbus::fdo::{Error, Result};
 use zbus::{dbus_interface, Interface, SignalContext};
-use zvariant::{ObjectPath, Value};
+use zvariant::{ObjectPath, OwnedObjectPath, Value};
 
 use super::types::*;
-use crate::app::models::RepeatMode;
-use crate::app::state::PlaybackAction;
-use crate::app::AppAction;
+use crate::api::{SpotifyApiClient, SpotifyApiError, SpotifyResult};
+use crate::app::models::{Batch, RepeatMode};
+use crate::app::state::{PlaybackAction, SpotifyLink, MAX_PLAYBACK_RATE, MIN_PLAYBACK_RATE};
+use crate::app::{AppAction, BatchLoader, BatchQuery, SongsSource};
+
+// The actions to play a source from its first track
+pub(super) async fn play_source(
+    api: &Arc<dyn SpotifyApiClient + Send + Sync>,
+    source: SongsSource,
+) -> SpotifyResult<Vec<AppAction>> {
+    let query = BatchQuery {
+        source,
+        batch: Batch::first_of_size(50),
+    };
+    let batch = BatchLoader::new(Arc::clone(api)).fetch(&query).await?;
+    let first_id = batch
+        .songs
+        .first()
+        .ok_or(SpotifyApiError::NoContent)?
+        .id
+        .clone();
+    Ok(vec![
+        PlaybackAction::LoadPagedSongs(query.source, batch).into(),
+        PlaybackAction::Load(first_id).into(),
+    ])
+}
 
 #[derive(Clone)]
 pub struct SpotMpris {
@@ -49,7 +73,7 @@ impl SpotMpris {
 
     #[dbus_interface(property)]
     fn has_track_list(&self) -> bool {
-        false
+        true
     }
 
     #[dbus_interface(property)]
@@ -64,7 +88,7 @@ impl SpotMpris {
 
     #[dbus_interface(property)]
     fn supported_uri_schemes(&self) -> Vec<String> {
-        vec![]
+        vec!["spotify".to_string(), "https".to_string()]
     }
 
     #[dbus_interface(property)]
@@ -75,17 +99,33 @@ impl SpotMpris {
 
 pub struct SpotMprisPlayer {
     state: MprisState,
+    api: Arc<dyn SpotifyApiClient + Send + Sync>,
     sender: UnboundedSender<AppAction>,
 }
 
 impl SpotMprisPlayer {
-    pub fn new(sender: UnboundedSender<AppAction>) -> Self {
+    pub fn new(
+        api: Arc<dyn SpotifyApiClient + Send + Sync>,
+        sender: UnboundedSender<AppAction>,
+    ) -> Self {
         Self {
             state: MprisState::new(),
+            api,
             sender,
         }
     }
 
+    async fn play_link(&self, link: SpotifyLink) -> Option<SpotifyResult<Vec<AppAction>>> {
+        let actions = match link {
+            // Same as a link opened through the app: queued, so that the context resumes after it
+            SpotifyLink::Track(id) => Ok(vec![AppAction::OpenTrack(id)]),
+            SpotifyLink::Album(id) => play_source(&self.api, SongsSource::Album(id)).await,
+            SpotifyLink::Playlist(id) => play_source(&self.api, SongsSource::Playlist(id)).await,
+            SpotifyLink::Artist(_) | SpotifyLink::User(_) => return None,
+        };
+        Some(actions)
+    }
+
     pub fn state_mut(&mut self) -> &mut MprisState {
         &mut self.state
     }
@@ -135,8 +175,20 @@ impl SpotMprisPlayer {
             .map_err(|_| Error::Failed("Could not send action".to_string()))
     }
 
-    pub fn open_uri(&self, Uri: &str) -> Result<()> {
-        Err(Error::NotSupported("Not implemented".to_string()))
+    pub async fn open_uri(&self, Uri: &str) -> Result<()> {
+        let link = SpotifyLink::parse(Uri)
+            .ok_or_else(|| Error::InvalidArgs("Unsupported URI".to_string()))?;
+        let actions = self
+            .play_link(link)
+            .await
+            .ok_or_else(|| Error::NotSupported("This URI cannot be played".to_string()))?
+            .map_err(|e| Error::Failed(format!("Could not load URI: {e}")))?;
+        for action in actions {
+            self.sender
+                .unbounded_send(action)
+                .map_err(|_| Error::Failed("Could not send action".to_string()))?;
+        }
+        Ok(())
     }
 
     pub fn pause(&self) -> Result<()> {
@@ -220,7 +272,9 @@ impl SpotMprisPlayer {
     }
 
     pub fn stop(&self) -> Result<()> {
-        Err(Error::NotSupported("Not implemented".to_string()))
+        self.sender
+            .unbounded_send(PlaybackAction::Stop.into())
+            .map_err(|_| Error::Failed("Could not send action".to_string()))
     }
 
     #[dbus_interface(signal)]
@@ -258,7 +312,7 @@ impl SpotMprisPlayer {
 
     #[dbus_interface(property)]
     pub fn maximum_rate(&self) -> f64 {
-        1.0f64
+        MAX_PLAYBACK_RATE.into()
     }
 
     #[dbus_interface(property)]
@@ -278,7 +332,7 @@ impl SpotMprisPlayer {
 
     #[dbus_interface(property)]
     pub fn minimum_rate(&self) -> f64 {
-        1.0f64
+        MIN_PLAYBACK_RATE.into()
     }
 
     #[dbus_interface(property)]
@@ -311,11 +365,25 @@ impl SpotMprisPlayer {
 
     #[dbus_interface(property)]
     pub fn rate(&self) -> f64 {
-        1.0f64
+        self.state.rate()
     }
 
     #[dbus_interface(property)]
-    pub fn set_rate(&self, value: f64) {}
+    pub fn set_rate(&self, value: f64) -> zbus::Result<()> {
+        if !value.is_finite() {
+            return Err(Error::InvalidArgs(format!("Invalid rate {value}")).into());
+        }
+        // As per the spec, a rate of 0 is how some clients ask for a pause
+        let action = if value <= 0.0 {
+            PlaybackAction::Pause
+        } else {
+            PlaybackAction::SetRate(value as f32)
+        };
+        self.sender
+            .unbounded_send(action.into())
+            .map_err(|_| Error::Failed("Could not send action".to_string()))?;
+        Ok(())
+    }
 
     #[dbus_interface(property)]
     pub fn shuffle(&self) -> bool {
@@ -346,3 +414,143 @@ impl SpotMprisPlayer {
         Ok(())
     }
 }
+
+pub struct SpotMprisTrackList {
+    tracks: Vec<TrackMetadata>,
+    api: Arc<dyn SpotifyApiClient + Send + Sync>,
+    sender: UnboundedSender<AppAction>,
+}
+
+impl SpotMprisTrackList {
+    pub fn new(
+        api: Arc<dyn SpotifyApiClient + Send + Sync>,
+        sender: UnboundedSender<AppAction>,
+    ) -> Self {
+        Self {
+            tracks: vec![],
+            api,
+            sender,
+        }
+    }
+
+    pub async fn set_tracks(
+        &mut self,
+        ctxt: &SignalContext<'_>,
+        tracks: Vec<TrackMetadata>,
+        current: Option<String>,
+    ) -> zbus::Result<()> {
+        let change = TrackListChange::between(&self.tracks, &tracks);
+        self.tracks = tracks;
+        match change {
+            TrackListChange::Unchanged => Ok(()),
+            TrackListChange::Replaced => {
+                let current = current.unwrap_or_else(|| NO_TRACK_PATH.to_string());
+                Self::track_list_replaced(
+                    ctxt,
+                    self.tracks(),
+                    ObjectPath::try_from(current.as_str())?,
+                )
+                .await
+            }
+            TrackListChange::Updated { removed, added } => {
+                for id in removed {
+                    Self::track_removed(ctxt, ObjectPath::try_from(id.as_str())?).await?;
+                }
+                for (metadata, after) in added {
+                    Self::track_added(ctxt, metadata, ObjectPath::try_from(after.as_str())?)
+                        .await?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[dbus_interface(interface = "org.mpris.MediaPlayer2.TrackList")]
+impl SpotMprisTrackList {
+    pub fn get_tracks_metadata(&self, TrackIds: Vec<ObjectPath<'_>>) -> Vec<TrackMetadata> {
+        TrackIds
+            .iter()
+            .filter_map(|path| {
+                self.tracks
+                    .iter()
+                    .find(|track| track.id.as_str() == path.as_str())
+                    .cloned()
+            })
+            .collect()
+    }
+
+    // Spot can only append to the queue, so AfterTrack is not honored
+    pub async fn add_track(
+        &self,
+        Uri: &str,
+        AfterTrack: ObjectPath<'_>,
+        SetAsCurrent: bool,
+    ) -> Result<()> {
+        let id = Uri
+            .strip_prefix("spotify:track:")
+            .ok_or_else(|| Error::InvalidArgs("Unsupported URI".to_string()))?;
+        let song = self
+            .api
+            .get_track(id)
+            .await
+            .map_err(|e| Error::Failed(format!("Could not fetch track: {e}")))?;
+        let id = song.id.clone();
+
+        self.sender
+            .unbounded_send(PlaybackAction::Queue(vec![song]).into())
+            .map_err(|_| Error::Failed("Could not send action".to_string()))?;
+        if SetAsCurrent {
+            self.sender
+                .unbounded_send(PlaybackAction::Load(id).into())
+                .map_err(|_| Error::Failed("Could not send action".to_string()))?;
+        }
+        Ok(())
+    }
+
+    pub fn remove_track(&self, TrackId: ObjectPath<'_>) -> Result<()> {
+        let id = track_id_from_path(&TrackId)
+            .ok_or_else(|| Error::InvalidArgs("Unknown track".to_string()))?;
+        self.sender
+            .unbounded_send(PlaybackAction::Dequeue(id).into())
+            .map_err(|_| Error::Failed("Could not send action".to_string()))
+    }
+
+    pub fn go_to(&self, TrackId: ObjectPath<'_>) -> Result<()> {
+        let id = track_id_from_path(&TrackId)
+            .ok_or_else(|| Error::InvalidArgs("Unknown track".to_string()))?;
+        self.sender
+            .unbounded_send(PlaybackAction::Load(id).into())
+            .map_err(|_| Error::Failed("Could not send action".to_string()))
+    }
+
+    #[dbus_interface(signal)]
+    pub async fn track_list_replaced(
+        ctxt: &SignalContext<'_>,
+        Tracks: Vec<OwnedObjectPath>,
+        CurrentTrack: ObjectPath<'_>,
+    ) -> zbus::Result<()>;
+
+    #[dbus_interface(signal)]
+    pub async fn track_added(
+        ctxt: &SignalContext<'_>,
+        Metadata: TrackMetadata,
+        AfterTrack: ObjectPath<'_>,
+    ) -> zbus::Result<()>;
+
+    #[dbus_interface(signal)]
+    pub async fn track_removed(
+        ctxt: &SignalContext<'_>,
+        TrackId: ObjectPath<'_>,
+    ) -> zbus::Result<()>;
+
+    #[dbus_interface(property)]
+    pub fn tracks(&self) -> Vec<OwnedObjectPath> {
+        self.tracks.iter().filter_map(|t| t.path()).collect()
+    }
+
+    #[dbus_interface(property)]
+    pub fn can_edit_tracks(&self) -> bool {
+        true
+    }
+}
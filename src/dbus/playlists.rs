@@ -0,0 +1,138 @@
+#![allow(non_snake_case)]
+
+use std::sync::Arc;
+
+use futures::channel::mpsc::UnboundedSender;
+use zbus::fdo::{Error, Result};
+use zbus::{dbus_interface, SignalContext};
+use zvariant::ObjectPath;
+
+use super::mpris::play_source;
+use super::types::{playlist_id_from_path, MaybePlaylist, PlaylistMetadata};
+use crate::api::SpotifyApiClient;
+use crate::app::{AppAction, SongsSource};
+
+const USER_DEFINED_ORDER: &str = "UserDefined";
+const ALPHABETICAL_ORDER: &str = "Alphabetical";
+
+pub struct SpotMprisPlaylists {
+    playlists: Vec<PlaylistMetadata>,
+    active: Option<PlaylistMetadata>,
+    api: Arc<dyn SpotifyApiClient + Send + Sync>,
+    sender: UnboundedSender<AppAction>,
+}
+
+impl SpotMprisPlaylists {
+    pub fn new(
+        api: Arc<dyn SpotifyApiClient + Send + Sync>,
+        sender: UnboundedSender<AppAction>,
+    ) -> Self {
+        Self {
+            playlists: vec![],
+            active: None,
+            api,
+            sender,
+        }
+    }
+
+    pub async fn set_playlists(
+        &mut self,
+        ctxt: &SignalContext<'_>,
+        playlists: Vec<PlaylistMetadata>,
+    ) -> zbus::Result<()> {
+        let renamed: Vec<PlaylistMetadata> = playlists
+            .iter()
+            .filter(|playlist| {
+                self.playlists
+                    .iter()
+                    .any(|old| old.id == playlist.id && old.name != playlist.name)
+            })
+            .cloned()
+            .collect();
+        let count_changed = self.playlists.len() != playlists.len();
+        self.playlists = playlists;
+
+        for playlist in renamed {
+            Self::playlist_changed(ctxt, playlist).await?;
+        }
+        if count_changed {
+            self.playlist_count_changed(ctxt).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn set_active(
+        &mut self,
+        ctxt: &SignalContext<'_>,
+        active: Option<PlaylistMetadata>,
+    ) -> zbus::Result<()> {
+        if self.active != active {
+            self.active = active;
+            self.active_playlist_changed(ctxt).await?;
+        }
+        Ok(())
+    }
+}
+
+#[dbus_interface(interface = "org.mpris.MediaPlayer2.Playlists")]
+impl SpotMprisPlaylists {
+    pub async fn activate_playlist(&self, PlaylistId: ObjectPath<'_>) -> Result<()> {
+        let id = playlist_id_from_path(&PlaylistId)
+            .ok_or_else(|| Error::InvalidArgs("Unknown playlist".to_string()))?;
+        let actions = play_source(&self.api, SongsSource::Playlist(id))
+            .await
+            .map_err(|e| Error::Failed(format!("Could not load playlist: {e}")))?;
+        for action in actions {
+            self.sender
+                .unbounded_send(action)
+                .map_err(|_| Error::Failed("Could not send action".to_string()))?;
+        }
+        Ok(())
+    }
+
+    pub fn get_playlists(
+        &self,
+        Index: u32,
+        MaxCount: u32,
+        Order: &str,
+        ReverseOrder: bool,
+    ) -> Vec<PlaylistMetadata> {
+        let mut playlists = self.playlists.clone();
+        // Orderings we don't support fall back to the order of the user's library
+        if Order == ALPHABETICAL_ORDER {
+            playlists.sort_by_key(|playlist| playlist.name.to_lowercase());
+        }
+        if ReverseOrder {
+            playlists.reverse();
+        }
+        playlists
+            .into_iter()
+            .skip(Index as usize)
+            .take(MaxCount as usize)
+            .collect()
+    }
+
+    #[dbus_interface(signal)]
+    pub async fn playlist_changed(
+        ctxt: &SignalContext<'_>,
+        Playlist: PlaylistMetadata,
+    ) -> zbus::Result<()>;
+
+    #[dbus_interface(property)]
+    pub fn playlist_count(&self) -> u32 {
+        self.playlists.len() as u32
+    }
+
+    #[dbus_interface(property)]
+    pub fn orderings(&self) -> Vec<String> {
+        vec![
+            USER_DEFINED_ORDER.to_string(),
+            ALPHABETICAL_ORDER.to_string(),
+        ]
+    }
+
+    #[dbus_interface(property)]
+    pub fn active_playlist(&self) -> MaybePlaylist {
+        self.active.clone().into()
+    }
+}
@@ -0,0 +1,72 @@
+#![allow(non_snake_case)]
+
+use std::time::{Duration, Instant};
+
+use futures::channel::mpsc::UnboundedSender;
+use zbus::fdo::{Error, Result};
+use zbus::{dbus_interface, SignalContext};
+
+use crate::app::state::{PlaybackAction, SleepTimer};
+use crate::app::AppAction;
+
+// Not part of MPRIS, this lets scripts and shell extensions control the sleep timer
+pub struct SpotSleepTimer {
+    sender: UnboundedSender<AppAction>,
+    deadline: Option<Instant>,
+}
+
+impl SpotSleepTimer {
+    pub fn new(sender: UnboundedSender<AppAction>) -> Self {
+        Self {
+            sender,
+            deadline: None,
+        }
+    }
+
+    fn set_sleep_timer(&self, timer: Option<SleepTimer>) -> Result<()> {
+        self.sender
+            .unbounded_send(PlaybackAction::SetSleepTimer(timer).into())
+            .map_err(|_| Error::Failed("Could not send action".to_string()))
+    }
+
+    pub async fn set_remaining(
+        &mut self,
+        ctxt: &SignalContext<'_>,
+        remaining: Option<Duration>,
+    ) -> zbus::Result<()> {
+        self.deadline = remaining.map(|remaining| Instant::now() + remaining);
+        self.active_changed(ctxt).await?;
+        self.remaining_changed(ctxt).await
+    }
+}
+
+#[dbus_interface(interface = "dev.alextren.Spot.SleepTimer")]
+impl SpotSleepTimer {
+    pub fn start(&self, Minutes: u32) -> Result<()> {
+        if Minutes == 0 {
+            return Err(Error::InvalidArgs("Duration can't be zero".to_string()));
+        }
+        self.set_sleep_timer(Some(SleepTimer::Minutes(Minutes)))
+    }
+
+    pub fn start_until_end_of_track(&self) -> Result<()> {
+        self.set_sleep_timer(Some(SleepTimer::EndOfTrack))
+    }
+
+    pub fn cancel(&self) -> Result<()> {
+        self.set_sleep_timer(None)
+    }
+
+    #[dbus_interface(property)]
+    pub fn active(&self) -> bool {
+        self.deadline.is_some()
+    }
+
+    // In seconds, like the Minutes of Start this isn't meant to be precise
+    #[dbus_interface(property)]
+    pub fn remaining(&self) -> u64 {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()).as_secs())
+            .unwrap_or(0)
+    }
+}
@@ -1,7 +1,35 @@
+use serde::{Serialize, Serializer};
+use std::collections::HashSet;
 use std::convert::{Into, TryFrom};
 use std::time::Instant;
-use zvariant::Type;
-use zvariant::{Dict, Signature, Str, Value};
+use zvariant::{Dict, ObjectPath, OwnedObjectPath, Signature, Str, Value};
+use zvariant::{Type, Value as DeriveValue};
+
+const TRACK_PATH_PREFIX: &str = "/dev/alextren/Spot/Track/";
+const PLAYLIST_PATH_PREFIX: &str = "/dev/alextren/Spot/Playlist/";
+
+// As per spec, this special path is used to indicate the absence of a track
+pub const NO_TRACK_PATH: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+pub fn track_path(id: &str) -> String {
+    format!("{TRACK_PATH_PREFIX}{id}")
+}
+
+pub fn track_id_from_path(path: &ObjectPath<'_>) -> Option<String> {
+    path.as_str()
+        .strip_prefix(TRACK_PATH_PREFIX)
+        .map(|id| id.to_string())
+}
+
+pub fn playlist_path(id: &str) -> String {
+    format!("{PLAYLIST_PATH_PREFIX}{id}")
+}
+
+pub fn playlist_id_from_path(path: &ObjectPath<'_>) -> Option<String> {
+    path.as_str()
+        .strip_prefix(PLAYLIST_PATH_PREFIX)
+        .map(|id| id.to_string())
+}
 
 fn boxed_value<'a, V: Into<Value<'a>>>(v: V) -> Value<'a> {
     Value::new(v.into())
@@ -102,9 +130,15 @@ impl PositionMicros {
     fn resume(&mut self) {
         self.last_resume_instant = Some(Instant::now());
     }
+
+    fn set_rate(&mut self, rate: f32) {
+        let playing = self.last_resume_instant.is_some();
+        self.set(self.current(), playing);
+        self.rate = rate;
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TrackMetadata {
     pub id: String,
     pub length: u64,
@@ -120,8 +154,13 @@ impl Type for TrackMetadata {
     }
 }
 
-impl From<TrackMetadata> for Value<'_> {
-    fn from(meta: TrackMetadata) -> Self {
+impl TrackMetadata {
+    pub fn path(&self) -> Option<OwnedObjectPath> {
+        OwnedObjectPath::try_from(self.id.as_str()).ok()
+    }
+
+    fn into_dict(self) -> Dict<'static, 'static> {
+        let meta = self;
         let mut d = Dict::new(Str::signature(), Value::signature());
         d.append("mpris:trackid".into(), boxed_value(meta.id))
             .unwrap();
@@ -138,7 +177,133 @@ impl From<TrackMetadata> for Value<'_> {
         if let Some(art) = meta.art {
             d.append("mpris:artUrl".into(), boxed_value(art)).unwrap();
         }
-        Value::Dict(d)
+        d
+    }
+}
+
+impl From<TrackMetadata> for Value<'_> {
+    fn from(meta: TrackMetadata) -> Self {
+        Value::Dict(meta.into_dict())
+    }
+}
+
+impl Serialize for TrackMetadata {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.clone().into_dict().serialize(serializer)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Type, DeriveValue)]
+pub struct PlaylistMetadata {
+    pub id: OwnedObjectPath,
+    pub name: String,
+    // We don't have the artwork of user playlists at hand
+    pub icon: String,
+}
+
+impl PlaylistMetadata {
+    pub fn new(id: &str, name: String) -> Option<Self> {
+        Some(Self {
+            id: OwnedObjectPath::try_from(playlist_path(id)).ok()?,
+            name,
+            icon: String::new(),
+        })
+    }
+}
+
+// What the spec calls a Maybe_Playlist, the playlist is only meaningful if valid is true
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Type, DeriveValue)]
+pub struct MaybePlaylist {
+    pub valid: bool,
+    pub playlist: PlaylistMetadata,
+}
+
+impl From<Option<PlaylistMetadata>> for MaybePlaylist {
+    fn from(playlist: Option<PlaylistMetadata>) -> Self {
+        match playlist {
+            Some(playlist) => Self {
+                valid: true,
+                playlist,
+            },
+            None => Self {
+                valid: false,
+                playlist: PlaylistMetadata {
+                    id: ObjectPath::from_static_str_unchecked("/").into(),
+                    name: String::new(),
+                    icon: String::new(),
+                },
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TrackListChange {
+    Unchanged,
+    Replaced,
+    Updated {
+        removed: Vec<String>,
+        // each added track comes with the id of the track it was inserted after
+        added: Vec<(TrackMetadata, String)>,
+    },
+}
+
+impl TrackListChange {
+    // Signaling individual additions and removals only makes sense if the tracks that were kept
+    // are still in the same order, otherwise we consider the whole list replaced.
+    pub fn between(old: &[TrackMetadata], new: &[TrackMetadata]) -> Self {
+        let old_ids: HashSet<&str> = old.iter().map(|t| t.id.as_str()).collect();
+        let new_ids: HashSet<&str> = new.iter().map(|t| t.id.as_str()).collect();
+
+        // Ids can't tell apart the copies of a track queued twice
+        if old_ids.len() != old.len() || new_ids.len() != new.len() {
+            return Self::Replaced;
+        }
+
+        let kept_from_old: Vec<&str> = old
+            .iter()
+            .map(|t| t.id.as_str())
+            .filter(|id| new_ids.contains(id))
+            .collect();
+        let kept_from_new: Vec<&str> = new
+            .iter()
+            .map(|t| t.id.as_str())
+            .filter(|id| old_ids.contains(id))
+            .collect();
+
+        if kept_from_old != kept_from_new || kept_from_old.is_empty() {
+            return if old.is_empty() && new.is_empty() {
+                Self::Unchanged
+            } else {
+                Self::Replaced
+            };
+        }
+
+        if kept_from_old.len() == old.len() && kept_from_new.len() == new.len() {
+            return Self::Unchanged;
+        }
+
+        let removed = old
+            .iter()
+            .filter(|t| !new_ids.contains(t.id.as_str()))
+            .map(|t| t.id.clone())
+            .collect();
+        let added = new
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| !old_ids.contains(t.id.as_str()))
+            .map(|(i, t)| {
+                let after = match i {
+                    0 => NO_TRACK_PATH.to_string(),
+                    i => new[i - 1].id.clone(),
+                };
+                (t.clone(), after)
+            })
+            .collect();
+        Self::Updated { removed, added }
     }
 }
 
@@ -163,7 +328,7 @@ impl MprisState {
             metadata: None,
             has_prev: false,
             has_next: false,
-            volume: 1f64,
+            volume: crate::settings::saved_volume(),
         }
     }
 
@@ -217,6 +382,14 @@ impl MprisState {
         self.position.current()
     }
 
+    pub fn rate(&self) -> f64 {
+        self.position.rate.into()
+    }
+
+    pub fn set_rate(&mut self, rate: f32) {
+        self.position.set_rate(rate);
+    }
+
     pub fn set_position(&mut self, position: u128) {
         let playing = self.status == PlaybackStatus::Playing;
         self.position.set(position, playing);
@@ -245,3 +418,80 @@ impl MprisState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn track(id: &str) -> TrackMetadata {
+        TrackMetadata {
+            id: track_path(id),
+            length: 1000,
+            artist: vec![],
+            album: "".to_string(),
+            title: "Title".to_string(),
+            art: None,
+        }
+    }
+
+    #[test]
+    fn test_track_list_unchanged() {
+        let tracks = vec![track("1"), track("2")];
+        let change = TrackListChange::between(&tracks, &tracks);
+        assert_eq!(change, TrackListChange::Unchanged);
+
+        let change = TrackListChange::between(&[], &[]);
+        assert_eq!(change, TrackListChange::Unchanged);
+    }
+
+    #[test]
+    fn test_track_list_added_removed() {
+        let old = vec![track("1"), track("2"), track("3")];
+        let new = vec![track("1"), track("3"), track("4")];
+        let change = TrackListChange::between(&old, &new);
+        assert_eq!(
+            change,
+            TrackListChange::Updated {
+                removed: vec![track_path("2")],
+                added: vec![(track("4"), track_path("3"))],
+            }
+        );
+
+        let change = TrackListChange::between(&old[1..], &old);
+        assert_eq!(
+            change,
+            TrackListChange::Updated {
+                removed: vec![],
+                added: vec![(track("1"), NO_TRACK_PATH.to_string())],
+            }
+        );
+    }
+
+    #[test]
+    fn test_track_list_replaced() {
+        let old = vec![track("1"), track("2")];
+
+        let change = TrackListChange::between(&old, &[track("3")]);
+        assert_eq!(change, TrackListChange::Replaced);
+
+        let change = TrackListChange::between(&old, &[track("2"), track("1")]);
+        assert_eq!(change, TrackListChange::Replaced);
+
+        let change = TrackListChange::between(&[], &old);
+        assert_eq!(change, TrackListChange::Replaced);
+
+        let change = TrackListChange::between(&old, &[track("1"), track("2"), track("2")]);
+        assert_eq!(change, TrackListChange::Replaced);
+    }
+
+    #[test]
+    fn test_playlist_path() {
+        let playlist = PlaylistMetadata::new("abc", "Name".to_string()).unwrap();
+        assert_eq!(playlist_id_from_path(&playlist.id), Some("abc".to_string()));
+
+        let active = MaybePlaylist::from(None);
+        assert!(!active.valid);
+        assert_eq!(playlist_id_from_path(&active.playlist.id), None);
+    }
+}
@@ -0,0 +1,599 @@
+use librespot::core::session::Session;
+use librespot::core::spotify_id::SpotifyId;
+use librespot::playback::audio_backend::{Sink, SinkError, SinkResult};
+use librespot::playback::config::PlayerConfig;
+use librespot::playback::convert::Converter;
+use librespot::playback::decoder::AudioPacket;
+use librespot::playback::mixer::Mixer;
+use librespot::playback::player::{Player, PlayerEvent, PlayerEventChannel};
+use librespot::playback::SAMPLES_PER_SECOND;
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::f64::consts::FRAC_PI_2;
+use std::rc::{Rc, Weak};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::task::{self, JoinHandle};
+
+use super::{audio_sink, notify_delegate, player_config, LoadFailures, PlaybackRate};
+use super::{SpotifyPlayerDelegate, SpotifyPlayerSettings};
+
+const DECKS: usize = 2;
+
+// How far ahead of the output a player can decode (about 100ms)
+const MAX_BUFFERED_SAMPLES: usize = SAMPLES_PER_SECOND as usize / 10;
+const CHUNK_SAMPLES: usize = 4096;
+
+#[derive(Default)]
+struct Deck {
+    samples: VecDeque<f64>,
+    running: bool,
+}
+
+struct Fade {
+    mixed: usize,
+    length: usize,
+}
+
+impl Fade {
+    fn new(length: Duration) -> Self {
+        Self {
+            mixed: 0,
+            length: (length.as_secs_f64() * SAMPLES_PER_SECOND as f64) as usize,
+        }
+    }
+
+    fn progress(&self) -> f64 {
+        if self.length == 0 {
+            1.0
+        } else {
+            (self.mixed as f64 / self.length as f64).min(1.0)
+        }
+    }
+}
+
+#[derive(Default)]
+struct OutputState {
+    decks: [Deck; DECKS],
+    lead: usize,
+    // Only set while fading from the lead deck into the other one
+    fade: Option<Fade>,
+    closed: bool,
+}
+
+impl OutputState {
+    fn is_running(&self) -> bool {
+        self.decks.iter().any(|deck| deck.running)
+    }
+
+    fn accepts(&self, deck: usize) -> bool {
+        !self.closed && (deck == self.lead || self.fade.is_some())
+    }
+
+    fn mix_chunk(&mut self) -> Option<Vec<f64>> {
+        let OutputState {
+            decks, lead, fade, ..
+        } = self;
+        let [first, second] = decks;
+        let (lead, other) = if *lead == 0 {
+            (first, second)
+        } else {
+            (second, first)
+        };
+
+        let available = match fade {
+            None => lead.samples.len(),
+            Some(_) => lead.samples.len().max(other.samples.len()),
+        };
+        let len = available.min(CHUNK_SAMPLES);
+        if len == 0 {
+            return None;
+        }
+
+        let chunk = match fade {
+            None => lead.samples.drain(..len).collect(),
+            Some(fade) => (0..len)
+                .map(|_| {
+                    // Equal power curves, so that the loudness doesn't dip halfway through
+                    let angle = fade.progress() * FRAC_PI_2;
+                    let fading_out = lead.samples.pop_front().unwrap_or(0.0) * angle.cos();
+                    // The fade only starts once the next track actually plays
+                    let fading_in = other.samples.pop_front().map(|sample| {
+                        fade.mixed += 1;
+                        sample * angle.sin()
+                    });
+                    fading_out + fading_in.unwrap_or(0.0)
+                })
+                .collect(),
+        };
+        Some(chunk)
+    }
+}
+
+// Both players write to this instead of the audio backend, and a thread of its own
+// sends what they played (mixed together during a fade) to the actual sink.
+struct Output {
+    state: Mutex<OutputState>,
+    changed: Condvar,
+}
+
+impl Output {
+    fn start<F>(sink_builder: F) -> (Arc<Self>, thread::JoinHandle<()>)
+    where
+        F: FnOnce() -> Box<dyn Sink> + Send + 'static,
+    {
+        let output = Arc::new(Self {
+            state: Default::default(),
+            changed: Condvar::new(),
+        });
+        let output_clone = Arc::clone(&output);
+        let handle = thread::spawn(move || output_clone.run(sink_builder()));
+        (output, handle)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, OutputState> {
+        self.state.lock().unwrap()
+    }
+
+    fn update(&self, update: impl FnOnce(&mut OutputState)) {
+        update(&mut self.lock());
+        self.changed.notify_all();
+    }
+
+    fn run(&self, mut sink: Box<dyn Sink>) {
+        let mut converter = Converter::new(PlayerConfig::default().ditherer);
+        let mut sink_running = false;
+        let mut state = self.lock();
+        while !state.closed {
+            if let Some(chunk) = state.mix_chunk() {
+                self.changed.notify_all();
+                drop(state);
+                if !sink_running {
+                    sink_running = true;
+                    if let Err(e) = sink.start() {
+                        error!("could not start audio output: {}", e);
+                    }
+                }
+                if let Err(e) = sink.write(AudioPacket::Samples(chunk), &mut converter) {
+                    error!("could not write to audio output: {}", e);
+                }
+                state = self.lock();
+            } else if sink_running && !state.is_running() {
+                drop(state);
+                sink_running = false;
+                if let Err(e) = sink.stop() {
+                    error!("could not stop audio output: {}", e);
+                }
+                state = self.lock();
+            } else {
+                state = self.changed.wait(state).unwrap();
+            }
+        }
+        drop(state);
+        if sink_running {
+            let _ = sink.stop();
+        }
+    }
+
+    fn begin_fade(&self, length: Duration) {
+        self.update(|state| {
+            let other = 1 - state.lead;
+            state.decks[other].samples.clear();
+            state.fade = Some(Fade::new(length));
+        });
+    }
+
+    fn cancel_fade(&self) {
+        self.update(|state| {
+            let other = 1 - state.lead;
+            state.decks[other].samples.clear();
+            state.fade = None;
+        });
+    }
+
+    // Whatever the previous lead deck still had to play is near silent by now
+    fn swap(&self) {
+        self.update(|state| {
+            let previous = state.lead;
+            state.decks[previous].samples.clear();
+            state.lead = 1 - previous;
+            state.fade = None;
+        });
+    }
+
+    fn close(&self) {
+        self.update(|state| state.closed = true);
+    }
+}
+
+struct DeckSink {
+    output: Arc<Output>,
+    deck: usize,
+}
+
+impl Sink for DeckSink {
+    fn start(&mut self) -> SinkResult<()> {
+        self.output
+            .update(|state| state.decks[self.deck].running = true);
+        Ok(())
+    }
+
+    fn stop(&mut self) -> SinkResult<()> {
+        self.output
+            .update(|state| state.decks[self.deck].running = false);
+        Ok(())
+    }
+
+    fn write(&mut self, packet: AudioPacket, _converter: &mut Converter) -> SinkResult<()> {
+        let samples = packet
+            .samples()
+            .map_err(|e| SinkError::OnWrite(e.to_string()))?;
+        let mut state = self.output.lock();
+        loop {
+            if !state.accepts(self.deck) {
+                return Ok(());
+            }
+            if state.decks[self.deck].samples.len() < MAX_BUFFERED_SAMPLES {
+                break;
+            }
+            state = self.output.changed.wait(state).unwrap();
+        }
+        state.decks[self.deck].samples.extend(samples);
+        self.output.changed.notify_all();
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Progress {
+    position_ms: u32,
+    duration_ms: u32,
+    playing_since: Option<Instant>,
+}
+
+impl Progress {
+    // Positions are in track time, which goes faster than real time when sped up
+    fn position(&self, rate: f64) -> Duration {
+        let position = Duration::from_millis(self.position_ms.into());
+        match self.playing_since {
+            Some(since) => position + since.elapsed().mul_f64(rate),
+            None => position,
+        }
+    }
+
+    fn remaining(&self, rate: f64) -> Duration {
+        Duration::from_millis(self.duration_ms.into()).saturating_sub(self.position(rate))
+    }
+}
+
+struct Decks {
+    players: [Player; DECKS],
+    progress: [Option<Progress>; DECKS],
+    lead: usize,
+    output: Arc<Output>,
+    output_thread: Option<thread::JoinHandle<()>>,
+    duration: Duration,
+    rate: PlaybackRate,
+    // The track to play next, and whether to fade into it
+    next: Option<(SpotifyId, bool)>,
+    // Played by the other deck during a fade
+    fading_in: Option<SpotifyId>,
+    // Faded into, but the app might not know it yet
+    handed_over: Option<SpotifyId>,
+    timer: Option<JoinHandle<()>>,
+    delegate: Rc<dyn SpotifyPlayerDelegate>,
+}
+
+impl Decks {
+    fn other(&self) -> usize {
+        1 - self.lead
+    }
+
+    fn cancel_timer(&mut self) {
+        if let Some(timer) = self.timer.take() {
+            timer.abort();
+        }
+    }
+
+    fn cancel_fade(&mut self) {
+        self.cancel_timer();
+        if self.fading_in.take().is_some() {
+            self.output.cancel_fade();
+            self.players[self.other()].stop();
+        }
+    }
+
+    fn start_fade(&mut self) {
+        self.timer = None;
+        let (track, remaining) = match (self.next, self.progress[self.lead]) {
+            (Some((track, true)), Some(progress)) if self.fading_in.is_none() => {
+                (track, progress.remaining(self.rate.get()))
+            }
+            _ => return,
+        };
+        debug!("fading into the next track");
+        self.output.begin_fade(remaining.min(self.duration));
+        self.players[self.other()].load(track, true, 0);
+        self.fading_in = Some(track);
+    }
+
+    // Makes the deck we faded into the lead one
+    fn hand_over(&mut self) {
+        self.cancel_timer();
+        self.lead = self.other();
+        self.output.swap();
+        self.handed_over = self.fading_in.take();
+        self.next = None;
+        let position = self.progress[self.lead]
+            .map(|progress| progress.position(self.rate.get()).as_millis() as u32)
+            .unwrap_or_default();
+        self.delegate.notify_playback_state(position);
+    }
+
+    fn handle_event(decks: &Rc<RefCell<Self>>, deck: usize, event: PlayerEvent) {
+        let mut this = decks.borrow_mut();
+        match event {
+            PlayerEvent::Playing {
+                position_ms,
+                duration_ms,
+                ..
+            } => {
+                this.progress[deck] = Some(Progress {
+                    position_ms,
+                    duration_ms,
+                    playing_since: Some(Instant::now()),
+                });
+            }
+            PlayerEvent::Paused {
+                position_ms,
+                duration_ms,
+                ..
+            } => {
+                this.progress[deck] = Some(Progress {
+                    position_ms,
+                    duration_ms,
+                    playing_since: None,
+                });
+            }
+            _ => {}
+        }
+
+        // Events of the other deck are about a track the app doesn't consider playing yet
+        if deck != this.lead {
+            return;
+        }
+
+        let delegate = Rc::clone(&this.delegate);
+        match event {
+            PlayerEvent::EndOfTrack { .. } if this.fading_in.is_some() => {
+                delegate.end_of_track_reached();
+                this.hand_over();
+                return;
+            }
+            PlayerEvent::Playing { .. } => {
+                drop(this);
+                Self::schedule_fade(decks);
+            }
+            PlayerEvent::Paused { .. } | PlayerEvent::Stopped { .. } => this.cancel_timer(),
+            _ => {}
+        }
+        notify_delegate(event, delegate.as_ref());
+    }
+
+    fn schedule_fade(decks: &Rc<RefCell<Self>>) {
+        let mut this = decks.borrow_mut();
+        this.cancel_timer();
+        let progress = match this.progress[this.lead] {
+            Some(progress) if progress.playing_since.is_some() => progress,
+            _ => return,
+        };
+        if this.fading_in.is_some() || !matches!(this.next, Some((_, true))) {
+            return;
+        }
+
+        let rate = this.rate.get();
+        let delay = progress
+            .remaining(rate)
+            .saturating_sub(this.duration)
+            .div_f64(rate);
+        let decks = Rc::downgrade(decks);
+        this.timer = Some(task::spawn_local(async move {
+            tokio::time::sleep(delay).await;
+            if let Some(decks) = decks.upgrade() {
+                decks.borrow_mut().start_fade();
+            }
+        }));
+    }
+}
+
+impl Drop for Decks {
+    fn drop(&mut self) {
+        self.cancel_timer();
+        // Unblocks the players, which wait for the output to catch up
+        self.output.close();
+        if let Some(output_thread) = self.output_thread.take() {
+            let _ = output_thread.join();
+        }
+    }
+}
+
+async fn deck_events(mut channel: PlayerEventChannel, deck: usize, decks: Weak<RefCell<Decks>>) {
+    let mut load_failures = LoadFailures::default();
+    while let Some(event) = channel.recv().await {
+        match decks.upgrade() {
+            Some(decks) => Decks::handle_event(&decks, deck, load_failures.translate(event)),
+            None => break,
+        }
+    }
+}
+
+// Two players taking turns: the next track starts on the idle one shortly
+// before the current one ends, and both are mixed together in the meantime.
+pub struct CrossfadingPlayer {
+    decks: Rc<RefCell<Decks>>,
+    events: Vec<JoinHandle<()>>,
+}
+
+impl CrossfadingPlayer {
+    pub fn new(
+        settings: &SpotifyPlayerSettings,
+        session: Session,
+        mixer: &dyn Mixer,
+        rate: PlaybackRate,
+        delegate: Rc<dyn SpotifyPlayerDelegate>,
+    ) -> Self {
+        info!("crossfade: {:?}", &settings.crossfade);
+        let (output, output_thread) =
+            Output::start(audio_sink(settings.backend.clone(), rate.clone()));
+        let [(first, first_events), (second, second_events)] = [0, 1].map(|deck| {
+            let output = Arc::clone(&output);
+            Player::new(
+                player_config(settings),
+                session.clone(),
+                mixer.get_soft_volume(),
+                move || Box::new(DeckSink { output, deck }) as Box<dyn Sink>,
+            )
+        });
+
+        let decks = Rc::new(RefCell::new(Decks {
+            players: [first, second],
+            progress: [None; DECKS],
+            lead: 0,
+            output,
+            output_thread: Some(output_thread),
+            duration: settings.crossfade,
+            rate,
+            next: None,
+            fading_in: None,
+            handed_over: None,
+            timer: None,
+            delegate,
+        }));
+        let events = vec![
+            task::spawn_local(deck_events(first_events, 0, Rc::downgrade(&decks))),
+            task::spawn_local(deck_events(second_events, 1, Rc::downgrade(&decks))),
+        ];
+
+        Self { decks, events }
+    }
+
+    pub fn load(&self, track: SpotifyId, start_playing: bool, position_ms: u32) {
+        let mut decks = self.decks.borrow_mut();
+        if decks.handed_over.take() == Some(track) {
+            return;
+        }
+        // Skipped to the track we were fading into
+        if decks.fading_in == Some(track) {
+            decks.hand_over();
+            decks.handed_over = None;
+            let previous = decks.other();
+            decks.players[previous].stop();
+            return;
+        }
+        decks.cancel_fade();
+        decks.next = None;
+        let lead = decks.lead;
+        decks.players[lead].load(track, start_playing, position_ms);
+    }
+
+    pub fn preload(&self, track: SpotifyId, crossfade: bool) {
+        {
+            let mut decks = self.decks.borrow_mut();
+            let deck = if crossfade { decks.other() } else { decks.lead };
+            if decks.fading_in.is_none() {
+                decks.players[deck].preload(track);
+            }
+            decks.next = Some((track, crossfade));
+        }
+        // The fade might be due already
+        Decks::schedule_fade(&self.decks);
+    }
+
+    pub fn play(&self) {
+        let decks = self.decks.borrow();
+        decks.players[decks.lead].play();
+        if decks.fading_in.is_some() {
+            decks.players[decks.other()].play();
+        }
+    }
+
+    pub fn pause(&self) {
+        let decks = self.decks.borrow();
+        decks.players[decks.lead].pause();
+        if decks.fading_in.is_some() {
+            decks.players[decks.other()].pause();
+        }
+    }
+
+    pub fn stop(&self) {
+        let mut decks = self.decks.borrow_mut();
+        decks.cancel_fade();
+        decks.players[decks.lead].stop();
+    }
+
+    pub fn seek(&self, position_ms: u32) {
+        let mut decks = self.decks.borrow_mut();
+        decks.cancel_fade();
+        decks.players[decks.lead].seek(position_ms);
+    }
+}
+
+impl Drop for CrossfadingPlayer {
+    fn drop(&mut self) {
+        for events in self.events.iter() {
+            events.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::f64::consts::FRAC_PI_4;
+
+    fn state_with(lead: &[f64], other: &[f64], fade: Option<Fade>) -> OutputState {
+        let mut state = OutputState {
+            fade,
+            ..Default::default()
+        };
+        state.decks[0].samples.extend(lead);
+        state.decks[1].samples.extend(other);
+        state
+    }
+
+    #[test]
+    fn test_only_lead_plays_without_fade() {
+        let mut state = state_with(&[0.5, 0.5], &[1.0, 1.0], None);
+        assert_eq!(state.mix_chunk(), Some(vec![0.5, 0.5]));
+        assert_eq!(state.mix_chunk(), None);
+    }
+
+    #[test]
+    fn test_fade_waits_for_next_track() {
+        let fade = Fade {
+            mixed: 0,
+            length: 4,
+        };
+        let mut state = state_with(&[1.0, 1.0, 1.0, 1.0], &[], Some(fade));
+        assert_eq!(state.mix_chunk(), Some(vec![1.0, 1.0, 1.0, 1.0]));
+        assert_eq!(state.fade.as_ref().unwrap().mixed, 0);
+    }
+
+    #[test]
+    fn test_fade_mixes_both_decks() {
+        let fade = Fade {
+            mixed: 0,
+            length: 2,
+        };
+        let mut state = state_with(&[1.0, 1.0, 1.0], &[1.0, 1.0, 1.0], Some(fade));
+        let chunk = state.mix_chunk().unwrap();
+        assert_eq!(chunk.len(), 3);
+        assert!((chunk[0] - 1.0).abs() < 1e-9);
+        assert!((chunk[1] - FRAC_PI_4.sin() * 2.0).abs() < 1e-9);
+        assert!((chunk[2] - 1.0).abs() < 1e-9);
+        assert_eq!(state.fade.as_ref().unwrap().progress(), 1.0);
+    }
+}
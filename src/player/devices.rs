@@ -0,0 +1,221 @@
+use libpulse_binding::callbacks::ListResult;
+use libpulse_binding::context::{Context, FlagSet, State};
+use libpulse_binding::mainloop::standard::{IterateResult, Mainloop};
+use std::cell::{Cell, RefCell};
+use std::env;
+use std::fs::OpenOptions;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+use std::rc::Rc;
+
+use super::AudioBackend;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioDevice {
+    // What librespot expects: an ALSA PCM name, or a PulseAudio sink name
+    pub id: String,
+    pub description: String,
+}
+
+// Lists the PCMs ALSA knows about, skipping capture-only ones
+pub fn list_alsa_devices() -> Vec<AudioDevice> {
+    let Ok(hints) = alsa::device_name::HintIter::new_str(None, "pcm") else {
+        return vec![];
+    };
+    hints
+        .filter(|hint| hint.direction != Some(alsa::Direction::Capture))
+        .filter_map(|hint| {
+            let id = hint.name?;
+            // Descriptions span several lines, the first one is enough
+            let description = hint
+                .desc
+                .as_deref()
+                .and_then(|desc| desc.lines().next())
+                .unwrap_or(&id)
+                .to_string();
+            Some(AudioDevice { id, description })
+        })
+        .collect()
+}
+
+fn iterate(mainloop: &mut Mainloop) -> Option<()> {
+    match mainloop.iterate(true) {
+        IterateResult::Success(_) => Some(()),
+        IterateResult::Quit(_) | IterateResult::Err(_) => None,
+    }
+}
+
+// Lists the sinks of the PulseAudio server, which is also how PipeWire sinks are exposed
+pub fn list_pulseaudio_sinks() -> Vec<AudioDevice> {
+    fn list() -> Option<Vec<AudioDevice>> {
+        let mut mainloop = Mainloop::new()?;
+        let mut context = Context::new(&mainloop, "Spot")?;
+        context.connect(None, FlagSet::NOFLAGS, None).ok()?;
+        loop {
+            iterate(&mut mainloop)?;
+            match context.get_state() {
+                State::Ready => break,
+                State::Failed | State::Terminated => return None,
+                _ => {}
+            }
+        }
+
+        let sinks = Rc::new(RefCell::new(vec![]));
+        let done = Rc::new(Cell::new(false));
+        let _operation = context.introspect().get_sink_info_list({
+            let sinks = Rc::clone(&sinks);
+            let done = Rc::clone(&done);
+            move |result| match result {
+                ListResult::Item(info) => {
+                    if let Some(id) = info.name.as_ref().map(|name| name.to_string()) {
+                        let description = info
+                            .description
+                            .as_ref()
+                            .map(|desc| desc.to_string())
+                            .unwrap_or_else(|| id.clone());
+                        sinks.borrow_mut().push(AudioDevice { id, description });
+                    }
+                }
+                ListResult::End | ListResult::Error => done.set(true),
+            }
+        });
+        while !done.get() {
+            iterate(&mut mainloop)?;
+        }
+        context.disconnect();
+
+        let sinks = sinks.borrow().clone();
+        Some(sinks)
+    }
+
+    list().unwrap_or_default()
+}
+
+fn alsa_device_available(device: &str) -> bool {
+    match alsa::PCM::new(device, alsa::Direction::Playback, true) {
+        Ok(_) => true,
+        // Most likely used by our own player already
+        Err(e) => {
+            std::io::Error::from_raw_os_error(e.errno() as i32).kind()
+                == std::io::ErrorKind::ResourceBusy
+        }
+    }
+}
+
+fn gstreamer_pipeline_valid(pipeline: &str) -> bool {
+    gstreamer::init().is_ok() && gstreamer::parse_bin_from_description(pipeline, true).is_ok()
+}
+
+// Opening a named pipe for writing would block until someone reads from it, hence O_NONBLOCK
+fn pipe_writable(path: &str) -> bool {
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path);
+    match file {
+        Ok(_) => true,
+        // A named pipe nobody reads from yet, librespot will simply wait for a reader
+        Err(e) => e.raw_os_error() == Some(libc::ENXIO),
+    }
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+fn find_program(program: &str) -> bool {
+    if program.contains('/') {
+        return is_executable(Path::new(program));
+    }
+    env::var_os("PATH")
+        .map(|paths| env::split_paths(&paths).any(|dir| is_executable(&dir.join(program))))
+        .unwrap_or(false)
+}
+
+// librespot splits the command the same way, and exits when it is "?" (its way of asking for help)
+fn subprocess_command_valid(command: &str) -> bool {
+    match shell_words::split(command).as_deref() {
+        Ok([program, ..]) => program != "?" && find_program(program),
+        _ => false,
+    }
+}
+
+// Mixers belong to the card rather than to a PCM, so `plughw:1,0` is controlled through `hw:1`
+pub fn alsa_mixer_device(pcm: &str) -> String {
+    let Some((_, args)) = pcm.split_once(':') else {
+        return pcm.to_string();
+    };
+    let card = args
+        .split(',')
+        .find_map(|arg| arg.strip_prefix("CARD="))
+        .or_else(|| args.split(',').next().filter(|arg| !arg.contains('=')));
+    match card {
+        Some(card) if !card.is_empty() => format!("hw:{card}"),
+        _ => pcm.to_string(),
+    }
+}
+
+// librespot panics (or exits!) when it can't open the output, so we make sure it's there first.
+// This might talk to the sound server, so it's best kept off threads that shouldn't block.
+pub fn is_available(backend: &AudioBackend) -> bool {
+    match backend {
+        AudioBackend::GStreamer(pipeline) => gstreamer_pipeline_valid(pipeline),
+        AudioBackend::PulseAudio(None) => true,
+        AudioBackend::PulseAudio(Some(sink)) => list_pulseaudio_sinks()
+            .iter()
+            .any(|device| &device.id == sink),
+        AudioBackend::Alsa(device) => alsa_device_available(device),
+        AudioBackend::Pipe(path) => path.is_empty() || pipe_writable(path),
+        AudioBackend::Subprocess(command) => subprocess_command_valid(command),
+    }
+}
+
+pub fn available_or_default(backend: AudioBackend) -> AudioBackend {
+    if is_available(&backend) {
+        backend
+    } else {
+        warn!(
+            "Audio output {:?} is not available, using the default one",
+            backend
+        );
+        AudioBackend::PulseAudio(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alsa_mixer_device() {
+        assert_eq!(alsa_mixer_device("plughw:1,0"), "hw:1");
+        assert_eq!(alsa_mixer_device("hw:1"), "hw:1");
+        assert_eq!(alsa_mixer_device("plughw:CARD=PCH,DEV=0"), "hw:PCH");
+        assert_eq!(alsa_mixer_device("front:CARD=Generic,DEV=0"), "hw:Generic");
+        assert_eq!(alsa_mixer_device("default"), "default");
+    }
+
+    #[test]
+    fn test_subprocess_command_valid() {
+        assert!(subprocess_command_valid("sh -c 'cat > /dev/null'"));
+        assert!(subprocess_command_valid("/bin/sh"));
+        assert!(!subprocess_command_valid(""));
+        assert!(!subprocess_command_valid("?"));
+        assert!(!subprocess_command_valid("sh -c 'unterminated"));
+        assert!(!subprocess_command_valid(
+            "surely-not-a-program-on-the-path"
+        ));
+    }
+
+    #[test]
+    fn test_pipe_writable() {
+        assert!(is_available(&AudioBackend::Pipe(String::new())));
+        assert!(is_available(&AudioBackend::Pipe("/dev/null".to_string())));
+        assert!(!is_available(&AudioBackend::Pipe(
+            "/surely/not/a/directory/pipe".to_string()
+        )));
+    }
+}
@@ -1,4 +1,5 @@
 use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use gettextrs::gettext;
 use librespot::core::spotify_id::SpotifyId;
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -6,36 +7,75 @@ use std::time::SystemTime;
 use tokio::task;
 
 use crate::app::credentials::Credentials;
-use crate::app::state::{LoginAction, PlaybackAction, SetLoginSuccessAction};
+use crate::app::models::{ConnectDevice, ConnectDeviceKind};
+use crate::app::state::{
+    Device, LocalPlayerState, LoginAction, PlaybackAction, SetLoginSuccessAction,
+};
 use crate::app::AppAction;
+use crate::connect::ConnectCommand;
 
 mod player;
 pub use player::*;
 
+mod devices;
+pub use devices::*;
+
+mod receiver;
+pub use receiver::*;
+
+mod crossfade;
+pub use crossfade::*;
+
+mod time_stretch;
+pub use time_stretch::*;
+
 #[derive(Debug, Clone)]
 pub enum Command {
-    PasswordLogin { username: String, password: String },
-    TokenLogin { username: String, token: String },
+    PasswordLogin {
+        username: String,
+        password: String,
+    },
+    TokenLogin {
+        username: String,
+        token: String,
+    },
     Logout,
-    PlayerLoad { track: SpotifyId, resume: bool },
+    PlayerLoad {
+        track: SpotifyId,
+        resume: bool,
+        position_ms: u32,
+    },
     PlayerResume,
     PlayerPause,
     PlayerStop,
     PlayerSeek(u32),
     PlayerSetVolume(f64),
-    PlayerPreload(SpotifyId),
+    PlayerSetRate(f64),
+    PlayerPreload {
+        track: SpotifyId,
+        crossfade: bool,
+    },
     RefreshToken,
     ReloadSettings,
+    // Sent by the player itself, when it notices that its session dropped
+    Reconnect,
 }
 
 struct AppPlayerDelegate {
     sender: RefCell<UnboundedSender<AppAction>>,
+    connect_sender: UnboundedSender<ConnectCommand>,
 }
 
 impl AppPlayerDelegate {
-    fn new(sender: UnboundedSender<AppAction>) -> Self {
+    fn new(
+        sender: UnboundedSender<AppAction>,
+        connect_sender: UnboundedSender<ConnectCommand>,
+    ) -> Self {
         let sender = RefCell::new(sender);
-        Self { sender }
+        Self {
+            sender,
+            connect_sender,
+        }
     }
 }
 
@@ -56,12 +96,21 @@ impl SpotifyPlayerDelegate for AppPlayerDelegate {
             .unwrap();
     }
 
-    fn token_login_successful(&self, username: String, token: String) {
+    fn token_login_successful(
+        &self,
+        username: String,
+        token: String,
+        token_expiry_time: Option<SystemTime>,
+    ) {
         self.sender
             .borrow_mut()
             .unbounded_send(
-                LoginAction::SetLoginSuccess(SetLoginSuccessAction::Token { username, token })
-                    .into(),
+                LoginAction::SetLoginSuccess(SetLoginSuccessAction::Token {
+                    username,
+                    token,
+                    token_expiry_time,
+                })
+                .into(),
             )
             .unwrap();
     }
@@ -92,7 +141,60 @@ impl SpotifyPlayerDelegate for AppPlayerDelegate {
     fn notify_playback_state(&self, position: u32) {
         self.sender
             .borrow_mut()
-            .unbounded_send(PlaybackAction::SyncSeek(position).into())
+            .unbounded_send(
+                PlaybackAction::SyncLocalPlayer(LocalPlayerState::Playing(position)).into(),
+            )
+            .unwrap();
+    }
+
+    fn reconnecting(&self) {
+        self.sender
+            .borrow_mut()
+            .unbounded_send(AppAction::ShowNotification(gettext(
+                "Connection lost, reconnecting…",
+            )))
+            .unwrap();
+    }
+
+    fn notify_loading(&self) {
+        self.sender
+            .borrow_mut()
+            .unbounded_send(PlaybackAction::SyncLocalPlayer(LocalPlayerState::Loading).into())
+            .unwrap();
+    }
+
+    fn notify_paused(&self, position: u32) {
+        self.sender
+            .borrow_mut()
+            .unbounded_send(
+                PlaybackAction::SyncLocalPlayer(LocalPlayerState::Paused(position)).into(),
+            )
+            .unwrap();
+    }
+
+    fn notify_stopped(&self) {
+        self.sender
+            .borrow_mut()
+            .unbounded_send(PlaybackAction::SyncLocalPlayer(LocalPlayerState::Stopped).into())
+            .unwrap();
+    }
+
+    fn notify_volume(&self, volume: f64) {
+        self.sender
+            .borrow_mut()
+            .unbounded_send(PlaybackAction::SyncVolume(volume).into())
+            .unwrap();
+    }
+
+    fn track_unavailable(&self, id: String) {
+        let sender = self.sender.borrow();
+        sender
+            .unbounded_send(AppAction::ShowNotification(gettext(
+                "A track isn't available and was skipped",
+            )))
+            .unwrap();
+        sender
+            .unbounded_send(PlaybackAction::SkipUnavailable(id).into())
             .unwrap();
     }
 
@@ -102,19 +204,47 @@ impl SpotifyPlayerDelegate for AppPlayerDelegate {
             .unbounded_send(PlaybackAction::Preload.into())
             .unwrap();
     }
+
+    fn receiver_activated(&self, device_id: String, name: String) {
+        let device = ConnectDevice {
+            id: device_id,
+            label: name,
+            kind: ConnectDeviceKind::Computer,
+        };
+        self.sender
+            .borrow_mut()
+            .unbounded_send(PlaybackAction::SyncDevice(Device::Connect(device)).into())
+            .unwrap();
+    }
+
+    fn receiver_stopped(&self) {
+        let _ = self
+            .connect_sender
+            .unbounded_send(ConnectCommand::ReceiverStopped);
+    }
+
+    fn remote_state_changed(&self) {
+        let _ = self
+            .connect_sender
+            .unbounded_send(ConnectCommand::RemoteStateChanged);
+    }
 }
 
 #[tokio::main]
 async fn player_main(
     player_settings: SpotifyPlayerSettings,
     appaction_sender: UnboundedSender<AppAction>,
-    receiver: UnboundedReceiver<Command>,
+    connect_sender: UnboundedSender<ConnectCommand>,
+    (sender, receiver): (UnboundedSender<Command>, UnboundedReceiver<Command>),
 ) {
     task::LocalSet::new()
         .run_until(async move {
             task::spawn_local(async move {
-                let delegate = Rc::new(AppPlayerDelegate::new(appaction_sender.clone()));
-                let player = SpotifyPlayer::new(player_settings, delegate);
+                let delegate = Rc::new(AppPlayerDelegate::new(
+                    appaction_sender.clone(),
+                    connect_sender,
+                ));
+                let player = SpotifyPlayer::new(player_settings, sender, delegate);
                 player.start(receiver).await.unwrap();
             })
             .await
@@ -126,8 +256,12 @@ async fn player_main(
 pub fn start_player_service(
     player_settings: SpotifyPlayerSettings,
     appaction_sender: UnboundedSender<AppAction>,
+    connect_sender: UnboundedSender<ConnectCommand>,
 ) -> UnboundedSender<Command> {
     let (sender, receiver) = unbounded::<Command>();
-    std::thread::spawn(move || player_main(player_settings, appaction_sender, receiver));
+    let commands = (sender.clone(), receiver);
+    std::thread::spawn(move || {
+        player_main(player_settings, appaction_sender, connect_sender, commands)
+    });
     sender
 }
@@ -1,4 +1,4 @@
-use futures::channel::mpsc::UnboundedReceiver;
+use futures::channel::mpsc::{UnboundedReceiver, UnboundedSender};
 use futures::stream::StreamExt;
 
 use librespot::core::authentication::Credentials;
@@ -7,12 +7,18 @@ use librespot::core::config::SessionConfig;
 use librespot::core::keymaster;
 use librespot::core::session::{Session, SessionError};
 
+use librespot::playback::mixer::alsamixer::AlsaMixer;
 use librespot::playback::mixer::softmixer::SoftMixer;
-use librespot::playback::mixer::{Mixer, MixerConfig};
+use librespot::playback::mixer::{Mixer, MixerConfig, VolumeGetter};
 use librespot::protocol::authentication::AuthenticationType;
+use librespot::protocol::spirc::{Frame, MessageType};
+use protobuf::Message;
 
-use librespot::playback::audio_backend;
-use librespot::playback::config::{AudioFormat, Bitrate, PlayerConfig, VolumeCtrl};
+use librespot::core::spotify_id::SpotifyId;
+use librespot::playback::audio_backend::{self, Sink};
+use librespot::playback::config::{
+    AudioFormat, Bitrate, NormalisationType, PlayerConfig, VolumeCtrl,
+};
 use librespot::playback::player::{Player, PlayerEvent, PlayerEventChannel};
 
 use std::cell::RefCell;
@@ -20,17 +26,20 @@ use std::env;
 use std::error::Error;
 use std::fmt;
 use std::rc::Rc;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::task::JoinHandle;
 
-use super::Command;
+use super::{available_or_default, is_available, Command, ConnectReceiver, CrossfadingPlayer};
+use super::{PlaybackRate, TimeStretchingSink};
 use crate::app::credentials;
-use crate::settings::SpotSettings;
+use crate::settings::{saved_volume, SpotSettings};
 
 #[derive(Debug)]
 pub enum SpotifyError {
     LoginFailed,
     TokenFailed,
     PlayerNotReady,
+    AudioOutputUnavailable,
     TechnicalError,
 }
 
@@ -42,6 +51,9 @@ impl fmt::Display for SpotifyError {
             Self::LoginFailed => write!(f, "Login failed!"),
             Self::TokenFailed => write!(f, "Token retrieval failed!"),
             Self::PlayerNotReady => write!(f, "Player is not responding."),
+            Self::AudioOutputUnavailable => {
+                write!(f, "The selected audio output is not available.")
+            }
             Self::TechnicalError => {
                 write!(f, "A technical error occured. Check your connectivity.")
             }
@@ -52,26 +64,91 @@ impl fmt::Display for SpotifyError {
 pub trait SpotifyPlayerDelegate {
     fn end_of_track_reached(&self);
     fn password_login_successful(&self, credentials: credentials::Credentials);
-    fn token_login_successful(&self, username: String, token: String);
+    fn token_login_successful(
+        &self,
+        username: String,
+        token: String,
+        token_expiry_time: Option<SystemTime>,
+    );
     fn refresh_successful(&self, token: String, token_expiry_time: SystemTime);
     fn report_error(&self, error: SpotifyError);
     fn notify_playback_state(&self, position: u32);
+    fn reconnecting(&self);
+    fn notify_loading(&self);
+    fn notify_paused(&self, position: u32);
+    fn notify_stopped(&self);
+    fn notify_volume(&self, volume: f64);
+    fn track_unavailable(&self, id: String);
     fn preload_next_track(&self);
+    fn receiver_activated(&self, device_id: String, name: String);
+    fn receiver_stopped(&self);
+    fn remote_state_changed(&self);
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum AudioBackend {
     GStreamer(String),
-    PulseAudio,
+    // The sink to use, the server's default one if None
+    PulseAudio(Option<String>),
     Alsa(String),
+    // Raw samples written to a file or a named pipe, or to stdout if the path is empty
+    Pipe(String),
+    // Raw samples written to the standard input of a command
+    Subprocess(String),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MixerBackend {
+    Software,
+    Alsa { device: String, control: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VolumeCurve {
+    Linear,
+    Log(f64),
+    Fixed,
+}
+
+impl VolumeCurve {
+    fn volume_ctrl(self) -> VolumeCtrl {
+        match self {
+            Self::Linear => VolumeCtrl::Linear,
+            Self::Log(db_range) => VolumeCtrl::Log(db_range),
+            Self::Fixed => VolumeCtrl::Fixed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MixerSettings {
+    pub backend: MixerBackend,
+    pub volume_curve: VolumeCurve,
+}
+
+impl Default for MixerSettings {
+    fn default() -> Self {
+        Self {
+            backend: MixerBackend::Software,
+            // This value feels reasonable to me. Feel free to change it
+            volume_curve: VolumeCurve::Log(VolumeCtrl::DEFAULT_DB_RANGE / 2.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct SpotifyPlayerSettings {
     pub bitrate: Bitrate,
     pub backend: AudioBackend,
+    pub mixer: MixerSettings,
     pub gapless: bool,
+    pub normalisation: bool,
+    pub normalisation_type: NormalisationType,
+    pub normalisation_pregain_db: f64,
+    pub normalisation_threshold_dbfs: f64,
     pub ap_port: Option<u16>,
+    pub connect_receiver: bool,
+    pub crossfade: Duration,
 }
 
 impl Default for SpotifyPlayerSettings {
@@ -79,27 +156,140 @@ impl Default for SpotifyPlayerSettings {
         Self {
             bitrate: Bitrate::Bitrate160,
             gapless: true,
-            backend: AudioBackend::PulseAudio,
+            backend: AudioBackend::PulseAudio(None),
+            mixer: Default::default(),
+            normalisation: false,
+            normalisation_type: NormalisationType::Auto,
+            normalisation_pregain_db: 0.0,
+            normalisation_threshold_dbfs: -2.0,
             ap_port: None,
+            connect_receiver: false,
+            crossfade: Duration::ZERO,
+        }
+    }
+}
+
+// Crossfading takes a second player, which we'd rather not run when it's disabled
+enum LocalPlayer {
+    Single(Player),
+    Crossfading(CrossfadingPlayer),
+}
+
+impl LocalPlayer {
+    fn play(&self) {
+        match self {
+            Self::Single(player) => player.play(),
+            Self::Crossfading(player) => player.play(),
+        }
+    }
+
+    fn pause(&self) {
+        match self {
+            Self::Single(player) => player.pause(),
+            Self::Crossfading(player) => player.pause(),
+        }
+    }
+
+    fn stop(&self) {
+        match self {
+            Self::Single(player) => player.stop(),
+            Self::Crossfading(player) => player.stop(),
+        }
+    }
+
+    fn seek(&self, position_ms: u32) {
+        match self {
+            Self::Single(player) => player.seek(position_ms),
+            Self::Crossfading(player) => player.seek(position_ms),
+        }
+    }
+
+    fn load(&mut self, track: SpotifyId, start_playing: bool, position_ms: u32) {
+        match self {
+            Self::Single(player) => {
+                player.load(track, start_playing, position_ms);
+            }
+            Self::Crossfading(player) => player.load(track, start_playing, position_ms),
+        }
+    }
+
+    fn preload(&mut self, track: SpotifyId, crossfade: bool) {
+        match self {
+            Self::Single(player) => player.preload(track),
+            Self::Crossfading(player) => player.preload(track, crossfade),
         }
     }
 }
 
+// What the player was last asked to play, so that it can pick it up again after reconnecting
+#[derive(Clone, Copy)]
+struct LoadedTrack {
+    track: SpotifyId,
+    position_ms: u32,
+    playing_since: Option<Instant>,
+}
+
+impl LoadedTrack {
+    fn new(track: SpotifyId, position_ms: u32, playing: bool) -> Self {
+        Self {
+            track,
+            position_ms,
+            playing_since: playing.then(Instant::now),
+        }
+    }
+
+    fn position_ms(&self, rate: f64) -> u32 {
+        let elapsed = self
+            .playing_since
+            .map(|since| since.elapsed().as_secs_f64() * 1000.0 * rate)
+            .unwrap_or_default();
+        self.position_ms + elapsed as u32
+    }
+
+    fn set_playing(&mut self, playing: bool, rate: f64) {
+        *self = Self::new(self.track, self.position_ms(rate), playing);
+    }
+
+    fn seek(&mut self, position_ms: u32) {
+        *self = Self::new(self.track, position_ms, self.playing_since.is_some());
+    }
+}
+
 pub struct SpotifyPlayer {
     settings: SpotifyPlayerSettings,
-    player: Option<Player>,
-    mixer: Option<Box<dyn Mixer>>,
+    player: Option<LocalPlayer>,
+    loaded: Option<LoadedTrack>,
+    mixer: Option<(MixerSettings, Box<dyn Mixer>)>,
     session: Option<Session>,
+    // Reusable credentials of the current session, to open a new one when it drops
+    credentials: Option<Credentials>,
+    receiver: Option<ConnectReceiver>,
+    // Our own command queue, for the receiver to reach the local player
+    commands: UnboundedSender<Command>,
+    remote_state_watcher: Option<JoinHandle<()>>,
+    session_watcher: Option<JoinHandle<()>>,
+    rate: PlaybackRate,
     delegate: Rc<dyn SpotifyPlayerDelegate>,
 }
 
 impl SpotifyPlayer {
-    pub fn new(settings: SpotifyPlayerSettings, delegate: Rc<dyn SpotifyPlayerDelegate>) -> Self {
+    pub fn new(
+        settings: SpotifyPlayerSettings,
+        commands: UnboundedSender<Command>,
+        delegate: Rc<dyn SpotifyPlayerDelegate>,
+    ) -> Self {
         Self {
             settings,
             mixer: None,
             player: None,
+            loaded: None,
             session: None,
+            credentials: None,
+            receiver: None,
+            commands,
+            remote_state_watcher: None,
+            session_watcher: None,
+            rate: PlaybackRate::default(),
             delegate,
         }
     }
@@ -107,16 +297,27 @@ impl SpotifyPlayer {
     async fn handle(&mut self, action: Command) -> Result<(), SpotifyError> {
         match action {
             Command::PlayerSetVolume(volume) => {
-                if let Some(mixer) = self.mixer.as_mut() {
+                if let Some((_, mixer)) = self.mixer.as_mut() {
                     mixer.set_volume((VolumeCtrl::MAX_VOLUME as f64 * volume) as u16);
                 }
                 Ok(())
             }
+            Command::PlayerSetRate(rate) => {
+                if let Some(loaded) = self.loaded.as_mut() {
+                    loaded.set_playing(loaded.playing_since.is_some(), self.rate.get());
+                }
+                self.rate.set(rate);
+                Ok(())
+            }
             Command::PlayerResume => {
+                self.release_receiver_output();
                 self.player
                     .as_ref()
                     .ok_or(SpotifyError::PlayerNotReady)?
                     .play();
+                if let Some(loaded) = self.loaded.as_mut() {
+                    loaded.set_playing(true, self.rate.get());
+                }
                 Ok(())
             }
             Command::PlayerPause => {
@@ -124,6 +325,9 @@ impl SpotifyPlayer {
                     .as_ref()
                     .ok_or(SpotifyError::PlayerNotReady)?
                     .pause();
+                if let Some(loaded) = self.loaded.as_mut() {
+                    loaded.set_playing(false, self.rate.get());
+                }
                 Ok(())
             }
             Command::PlayerStop => {
@@ -131,6 +335,7 @@ impl SpotifyPlayer {
                     .as_ref()
                     .ok_or(SpotifyError::PlayerNotReady)?
                     .stop();
+                self.loaded = None;
                 Ok(())
             }
             Command::PlayerSeek(position) => {
@@ -138,20 +343,29 @@ impl SpotifyPlayer {
                     .as_ref()
                     .ok_or(SpotifyError::PlayerNotReady)?
                     .seek(position);
+                if let Some(loaded) = self.loaded.as_mut() {
+                    loaded.seek(position);
+                }
                 Ok(())
             }
-            Command::PlayerLoad { track, resume } => {
+            Command::PlayerLoad {
+                track,
+                resume,
+                position_ms,
+            } => {
+                self.release_receiver_output();
                 self.player
                     .as_mut()
                     .ok_or(SpotifyError::PlayerNotReady)?
-                    .load(track, resume, 0);
+                    .load(track, resume, position_ms);
+                self.loaded = Some(LoadedTrack::new(track, position_ms, resume));
                 Ok(())
             }
-            Command::PlayerPreload(track) => {
+            Command::PlayerPreload { track, crossfade } => {
                 self.player
                     .as_mut()
                     .ok_or(SpotifyError::PlayerNotReady)?
-                    .preload(track);
+                    .preload(track, crossfade);
                 Ok(())
             }
             Command::RefreshToken => {
@@ -161,16 +375,26 @@ impl SpotifyPlayer {
                 Ok(())
             }
             Command::Logout => {
+                let _ = self.receiver.take();
+                if let Some(watcher) = self.remote_state_watcher.take() {
+                    watcher.abort();
+                }
+                if let Some(watcher) = self.session_watcher.take() {
+                    watcher.abort();
+                }
+                let _ = self.credentials.take();
                 self.session
                     .take()
                     .ok_or(SpotifyError::PlayerNotReady)?
                     .shutdown();
                 let _ = self.player.take();
+                self.loaded = None;
                 Ok(())
             }
             Command::PasswordLogin { username, password } => {
                 let credentials = Credentials::with_password(username, password.clone());
-                let new_session = create_session(&credentials, self.settings.ap_port).await?;
+                let (new_session, reusable_credentials) =
+                    create_session(&credentials, self.settings.ap_port).await?;
                 let (token, token_expiry_time) =
                     get_access_token_and_expiry_time(&new_session).await?;
                 let credentials = credentials::Credentials {
@@ -182,10 +406,8 @@ impl SpotifyPlayer {
                 };
                 self.delegate.password_login_successful(credentials);
 
-                let (new_player, channel) = self.create_player(new_session.clone());
-                tokio::task::spawn_local(player_setup_delegate(channel, Rc::clone(&self.delegate)));
-                self.player.replace(new_player);
-                self.session.replace(new_session);
+                self.credentials.replace(reusable_credentials);
+                self.start_session(new_session).await;
 
                 Ok(())
             }
@@ -195,72 +417,233 @@ impl SpotifyPlayer {
                     auth_type: AuthenticationType::AUTHENTICATION_SPOTIFY_TOKEN,
                     auth_data: token.clone().into_bytes(),
                 };
-                let new_session = create_session(&credentials, self.settings.ap_port).await?;
-                self.delegate
-                    .token_login_successful(new_session.username(), token);
+                let (new_session, reusable_credentials) =
+                    create_session(&credentials, self.settings.ap_port).await?;
+                // A token we know the expiry time of can be refreshed before it stops working
+                let (token, token_expiry_time) =
+                    match get_access_token_and_expiry_time(&new_session).await {
+                        Ok((token, token_expiry_time)) => (token, Some(token_expiry_time)),
+                        Err(_) => (token, None),
+                    };
+                self.delegate.token_login_successful(
+                    new_session.username(),
+                    token,
+                    token_expiry_time,
+                );
 
-                let (new_player, channel) = self.create_player(new_session.clone());
-                tokio::task::spawn_local(player_setup_delegate(channel, Rc::clone(&self.delegate)));
-                self.player.replace(new_player);
-                self.session.replace(new_session);
+                self.credentials.replace(reusable_credentials);
+                self.start_session(new_session).await;
 
                 Ok(())
             }
+            // Done before handling the command, if the session is indeed gone
+            Command::Reconnect => Ok(()),
             Command::ReloadSettings => {
                 let settings = SpotSettings::new_from_gsettings().unwrap_or_default();
+                // Keep playing with the previous settings rather than crashing
+                let backend = settings.player_settings.backend.clone();
+                let available = tokio::task::spawn_blocking(move || is_available(&backend))
+                    .await
+                    .unwrap_or(false);
+                if !available {
+                    return Err(SpotifyError::AudioOutputUnavailable);
+                }
                 self.settings = settings.player_settings;
 
-                let session = self.session.take().ok_or(SpotifyError::PlayerNotReady)?;
-                let (new_player, channel) = self.create_player(session);
-                tokio::task::spawn_local(player_setup_delegate(channel, Rc::clone(&self.delegate)));
+                let session = self.session.clone().ok_or(SpotifyError::PlayerNotReady)?;
+                let new_player = self.create_player(session);
                 self.player.replace(new_player);
+                self.setup_receiver();
+                self.watch_remote_state();
 
                 Ok(())
             }
         }
     }
 
-    fn create_player(&mut self, session: Session) -> (Player, PlayerEventChannel) {
+    async fn start_session(&mut self, session: Session) {
+        // Players (and crossfading decks) trust the output they're given, so it's checked once here
         let backend = self.settings.backend.clone();
+        self.settings.backend = tokio::task::spawn_blocking(move || available_or_default(backend))
+            .await
+            .unwrap_or(AudioBackend::PulseAudio(None));
+
+        let new_player = self.create_player(session.clone());
+        self.player.replace(new_player);
+        self.session.replace(session);
+        self.setup_receiver();
+        self.watch_remote_state();
+        self.watch_session();
+    }
+
+    // Nothing else tells us when the session drops, and the receiver goes down with it
+    fn watch_session(&mut self) {
+        if let Some(watcher) = self.session_watcher.take() {
+            watcher.abort();
+        }
+        if let Some(session) = self.session.clone() {
+            let watcher = session_watcher(session, self.commands.clone());
+            self.session_watcher = Some(tokio::task::spawn_local(watcher));
+        }
+    }
 
-        let player_config = PlayerConfig {
-            gapless: self.settings.gapless,
-            bitrate: self.settings.bitrate,
-            ..Default::default()
+    // The connection to the access point can drop (when the computer sleeps for instance),
+    // which leaves us with a session that can't be used anymore
+    fn has_lost_session(&self, action: &Command) -> bool {
+        let needs_session = !matches!(
+            action,
+            Command::PasswordLogin { .. } | Command::TokenLogin { .. } | Command::Logout
+        );
+        let is_invalid = self
+            .session
+            .as_ref()
+            .map(|session| session.is_invalid())
+            .unwrap_or(false);
+        needs_session && is_invalid
+    }
+
+    async fn reconnect(&mut self) -> Result<(), SpotifyError> {
+        let credentials = self
+            .credentials
+            .clone()
+            .ok_or(SpotifyError::PlayerNotReady)?;
+        self.delegate.reconnecting();
+
+        let mut attempt = 0;
+        let session = loop {
+            match create_session(&credentials, self.settings.ap_port).await {
+                Ok((session, _)) => break session,
+                Err(SpotifyError::TechnicalError) if attempt < MAX_RECONNECT_ATTEMPTS => {
+                    let delay = reconnect_delay(attempt);
+                    warn!("Could not reconnect, trying again in {:?}", delay);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
         };
-        info!("bitrate: {:?}", &player_config.bitrate);
+        info!("reconnected");
+        self.start_session(session).await;
 
-        let soft_volume = self
+        if let (Some(player), Some(loaded)) = (self.player.as_mut(), self.loaded.as_mut()) {
+            let position_ms = loaded.position_ms(self.rate.get());
+            let playing = loaded.playing_since.is_some();
+            player.load(loaded.track, playing, position_ms);
+            *loaded = LoadedTrack::new(loaded.track, position_ms, playing);
+        }
+        Ok(())
+    }
+
+    fn create_player(&mut self, session: Session) -> LocalPlayer {
+        let settings = self.settings.clone();
+        let delegate = Rc::clone(&self.delegate);
+        let rate = self.rate.clone();
+        let mixer = self.setup_mixer();
+        if settings.crossfade.is_zero() {
+            let (player, channel) = new_player(&settings, session, mixer.get_soft_volume(), rate);
+            tokio::task::spawn_local(player_setup_delegate(channel, delegate));
+            LocalPlayer::Single(player)
+        } else {
+            LocalPlayer::Crossfading(CrossfadingPlayer::new(
+                &settings, session, mixer, rate, delegate,
+            ))
+        }
+    }
+
+    // Restarted along with the player, so that it picks up the new audio settings
+    fn setup_receiver(&mut self) {
+        let _ = self.receiver.take();
+        let session = match &self.session {
+            Some(session) if self.settings.connect_receiver => session.clone(),
+            _ => return,
+        };
+        let volume = self
             .mixer
-            .get_or_insert_with(|| {
-                let mix = Box::new(SoftMixer::open(MixerConfig {
-                    // This value feels reasonable to me. Feel free to change it
-                    volume_ctrl: VolumeCtrl::Log(VolumeCtrl::DEFAULT_DB_RANGE / 2.0),
+            .as_ref()
+            .map(|(_, mixer)| mixer.volume())
+            .unwrap_or_else(|| (VolumeCtrl::MAX_VOLUME as f64 * saved_volume()) as u16);
+        let mixer = Self::create_mixer(&self.settings.mixer);
+        mixer.set_volume(volume);
+        // Other clients expect their tracks to play at normal speed
+        let player = new_player(
+            &self.settings,
+            session.clone(),
+            mixer.get_soft_volume(),
+            PlaybackRate::default(),
+        );
+        self.receiver.replace(ConnectReceiver::start(
+            session,
+            player,
+            mixer,
+            self.commands.clone(),
+            Rc::clone(&self.delegate),
+        ));
+    }
+
+    fn release_receiver_output(&self) {
+        if let Some(receiver) = self.receiver.as_ref() {
+            receiver.release_output();
+        }
+    }
+
+    // Spirc subscribes to the same updates, so while the receiver runs we rely on polling instead
+    fn watch_remote_state(&mut self) {
+        if let Some(watcher) = self.remote_state_watcher.take() {
+            watcher.abort();
+        }
+        if let Some(session) = self.session.clone() {
+            let watcher = remote_state_watcher(session, Rc::clone(&self.delegate));
+            self.remote_state_watcher = Some(tokio::task::spawn_local(watcher));
+        }
+    }
+
+    // The mixer outlives players, unless its settings changed
+    fn setup_mixer(&mut self) -> &dyn Mixer {
+        let settings = &self.settings.mixer;
+        let outdated = match &self.mixer {
+            Some((current, _)) => current != settings,
+            None => true,
+        };
+        if outdated {
+            let volume = match self.mixer.take() {
+                Some((_, mixer)) => mixer.volume(),
+                None => (VolumeCtrl::MAX_VOLUME as f64 * saved_volume()) as u16,
+            };
+            let mixer = Self::create_mixer(settings);
+            mixer.set_volume(volume);
+            self.mixer = Some((settings.clone(), mixer));
+        }
+        self.mixer
+            .as_ref()
+            .map(|(_, mixer)| mixer.as_ref())
+            .unwrap()
+    }
+
+    fn create_mixer(settings: &MixerSettings) -> Box<dyn Mixer> {
+        let volume_ctrl = settings.volume_curve.volume_ctrl();
+        match &settings.backend {
+            MixerBackend::Alsa { device, control } if alsa_mixer_exists(device, control) => {
+                info!("using alsa mixer ({}, {})", device, control);
+                Box::new(AlsaMixer::open(MixerConfig {
+                    device: device.clone(),
+                    control: control.clone(),
+                    volume_ctrl,
                     ..Default::default()
-                }));
-                // TODO: Should read volume from somewhere instead of hard coding.
-                // Sets volume to 100%
-                mix.set_volume(VolumeCtrl::MAX_VOLUME);
-                mix
-            })
-            .get_soft_volume();
-        Player::new(player_config, session, soft_volume, move || match backend {
-            AudioBackend::GStreamer(pipeline) => {
-                let backend = audio_backend::find(Some("gstreamer".to_string())).unwrap();
-                backend(Some(pipeline), AudioFormat::default())
+                }))
             }
-            AudioBackend::PulseAudio => {
-                info!("using pulseaudio");
-                env::set_var("PULSE_PROP_application.name", "Spot");
-                let backend = audio_backend::find(Some("pulseaudio".to_string())).unwrap();
-                backend(None, AudioFormat::default())
-            }
-            AudioBackend::Alsa(device) => {
-                info!("using alsa ({})", &device);
-                let backend = audio_backend::find(Some("alsa".to_string())).unwrap();
-                backend(Some(device), AudioFormat::default())
+            backend => {
+                if let MixerBackend::Alsa { device, control } = backend {
+                    warn!(
+                        "alsa mixer control {} not found on {}, using software mixer",
+                        control, device
+                    );
+                }
+                Box::new(SoftMixer::open(MixerConfig {
+                    volume_ctrl,
+                    ..Default::default()
+                }))
             }
-        })
+        }
     }
 
     pub async fn start(self, receiver: UnboundedReceiver<Command>) -> Result<(), ()> {
@@ -268,6 +651,12 @@ impl SpotifyPlayer {
         receiver
             .for_each(|action| async {
                 let mut _self = _self.borrow_mut();
+                if _self.has_lost_session(&action) {
+                    if let Err(err) = _self.reconnect().await {
+                        _self.delegate.report_error(err);
+                        return;
+                    }
+                }
                 match _self.handle(action).await {
                     Ok(_) => {}
                     Err(err) => _self.delegate.report_error(err),
@@ -278,6 +667,84 @@ impl SpotifyPlayer {
     }
 }
 
+fn new_player(
+    settings: &SpotifyPlayerSettings,
+    session: Session,
+    soft_volume: Box<dyn VolumeGetter + Send>,
+    rate: PlaybackRate,
+) -> (Player, PlayerEventChannel) {
+    let player_config = player_config(settings);
+    info!("bitrate: {:?}", &player_config.bitrate);
+    info!("normalisation: {:?}", &player_config.normalisation);
+
+    Player::new(
+        player_config,
+        session,
+        soft_volume,
+        audio_sink(settings.backend.clone(), rate),
+    )
+}
+
+pub(super) fn player_config(settings: &SpotifyPlayerSettings) -> PlayerConfig {
+    PlayerConfig {
+        gapless: settings.gapless,
+        bitrate: settings.bitrate,
+        normalisation: settings.normalisation,
+        normalisation_type: settings.normalisation_type,
+        normalisation_pregain_db: settings.normalisation_pregain_db,
+        normalisation_threshold_dbfs: settings.normalisation_threshold_dbfs,
+        ..Default::default()
+    }
+}
+
+pub(super) fn audio_sink(
+    backend: AudioBackend,
+    rate: PlaybackRate,
+) -> impl FnOnce() -> Box<dyn Sink> + Send + 'static {
+    move || {
+        let sink = match backend {
+            AudioBackend::GStreamer(pipeline) => {
+                let backend = audio_backend::find(Some("gstreamer".to_string())).unwrap();
+                backend(Some(pipeline), AudioFormat::default())
+            }
+            AudioBackend::PulseAudio(sink) => {
+                info!("using pulseaudio ({:?})", &sink);
+                env::set_var("PULSE_PROP_application.name", "Spot");
+                let backend = audio_backend::find(Some("pulseaudio".to_string())).unwrap();
+                backend(sink, AudioFormat::default())
+            }
+            AudioBackend::Alsa(device) => {
+                info!("using alsa ({})", &device);
+                let backend = audio_backend::find(Some("alsa".to_string())).unwrap();
+                backend(Some(device), AudioFormat::default())
+            }
+            AudioBackend::Pipe(path) => {
+                info!("using pipe ({})", &path);
+                let backend = audio_backend::find(Some("pipe".to_string())).unwrap();
+                backend(Some(path).filter(|p| !p.is_empty()), AudioFormat::default())
+            }
+            AudioBackend::Subprocess(command) => {
+                info!("using subprocess ({})", &command);
+                let backend = audio_backend::find(Some("subprocess".to_string())).unwrap();
+                backend(Some(command), AudioFormat::default())
+            }
+        };
+        Box::new(TimeStretchingSink::new(sink, rate))
+    }
+}
+
+// librespot's AlsaMixer panics when the control can't be found, so we check first
+fn alsa_mixer_exists(device: &str, control: &str) -> bool {
+    alsa::mixer::Mixer::new(device, false)
+        .ok()
+        .and_then(|mixer| {
+            mixer
+                .find_selem(&alsa::mixer::SelemId::new(control, 0))
+                .map(|_| ())
+        })
+        .is_some()
+}
+
 const CLIENT_ID: &str = "782ae96ea60f4cdf986a766049607005";
 
 const SCOPES: &str = "user-read-private,\
@@ -296,6 +763,25 @@ playlist-modify-public";
 
 const KNOWN_AP_PORTS: [Option<u16>; 4] = [None, Some(80), Some(443), Some(4070)];
 
+// Waiting a second, then two, four... up to about a minute overall
+const MAX_RECONNECT_ATTEMPTS: u32 = 6;
+
+fn reconnect_delay(attempt: u32) -> Duration {
+    Duration::from_secs(1 << attempt.min(MAX_RECONNECT_ATTEMPTS))
+}
+
+const SESSION_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+// Reconnecting happens before handling the command, a single one is enough:
+// the new session gets a watcher of its own
+async fn session_watcher(session: Session, commands: UnboundedSender<Command>) {
+    while !session.is_invalid() {
+        tokio::time::sleep(SESSION_CHECK_INTERVAL).await;
+    }
+    warn!("Session dropped");
+    let _ = commands.unbounded_send(Command::Reconnect);
+}
+
 async fn get_access_token_and_expiry_time(
     session: &Session,
 ) -> Result<(String, SystemTime), SpotifyError> {
@@ -309,7 +795,7 @@ async fn get_access_token_and_expiry_time(
 async fn create_session_with_port(
     credentials: &Credentials,
     ap_port: Option<u16>,
-) -> Result<Session, SpotifyError> {
+) -> Result<(Session, Credentials), SpotifyError> {
     let session_config = SessionConfig {
         ap_port,
         ..Default::default()
@@ -324,7 +810,7 @@ async fn create_session_with_port(
     .map_err(|e| dbg!(e))
     .ok();
     match Session::connect(session_config, credentials.clone(), cache, true).await {
-        Ok(r) => Ok(r.0),
+        Ok(r) => Ok(r),
         Err(SessionError::IoError(_)) => Err(SpotifyError::TechnicalError),
         Err(SessionError::AuthenticationError(err)) => {
             warn!("Login failure: {}", err);
@@ -336,7 +822,7 @@ async fn create_session_with_port(
 async fn create_session(
     credentials: &Credentials,
     ap_port: Option<u16>,
-) -> Result<Session, SpotifyError> {
+) -> Result<(Session, Credentials), SpotifyError> {
     match ap_port {
         Some(_) => create_session_with_port(credentials, ap_port).await,
         None => {
@@ -356,23 +842,185 @@ async fn create_session(
     }
 }
 
+// Connect devices of the account broadcast their state changes to each other
+// through the session, which saves us from polling the Web API for them.
+// The session hands each update to every subscriber of the uri, so while the receiver runs
+// this reads the very frames its Spirc gets.
+async fn remote_state_watcher(session: Session, delegate: Rc<dyn SpotifyPlayerDelegate>) {
+    let username: String = form_urlencoded::byte_serialize(session.username().as_bytes()).collect();
+    let uri = format!("hm://remote/user/{}/", username);
+    let mut updates = match session.mercury().subscribe(uri).await {
+        Ok(updates) => updates,
+        Err(_) => {
+            warn!("Could not subscribe to connect state updates");
+            return;
+        }
+    };
+    while let Some(update) = updates.recv().await {
+        let frame = update
+            .payload
+            .first()
+            .and_then(|data| Frame::parse_from_bytes(data).ok());
+        let is_state_change = frame
+            .map(|frame| {
+                matches!(
+                    frame.get_typ(),
+                    MessageType::kMessageTypeNotify | MessageType::kMessageTypeGoodbye
+                )
+            })
+            .unwrap_or(false);
+        if is_state_change {
+            delegate.remote_state_changed();
+        }
+    }
+}
+
+pub(super) fn notify_delegate(event: PlayerEvent, delegate: &dyn SpotifyPlayerDelegate) {
+    match event {
+        PlayerEvent::EndOfTrack { .. } => {
+            delegate.end_of_track_reached();
+        }
+        PlayerEvent::Playing { position_ms, .. } => {
+            delegate.notify_playback_state(position_ms);
+        }
+        PlayerEvent::Loading { .. } => {
+            delegate.notify_loading();
+        }
+        PlayerEvent::Paused { position_ms, .. } => {
+            delegate.notify_paused(position_ms);
+        }
+        PlayerEvent::Stopped { .. } => {
+            delegate.notify_stopped();
+        }
+        PlayerEvent::VolumeSet { volume } => {
+            delegate.notify_volume(volume as f64 / u16::MAX as f64);
+        }
+        PlayerEvent::Unavailable { track_id, .. } => {
+            warn!("Track {:?} is unavailable", track_id);
+            if let Ok(id) = track_id.to_base62() {
+                delegate.track_unavailable(id);
+            }
+        }
+        PlayerEvent::TimeToPreloadNextTrack { .. } => {
+            debug!("Requestiong next track to be preloaded...");
+            delegate.preload_next_track();
+        }
+        _ => {}
+    }
+}
+
+// librespot reports a track that couldn't be loaded as ending right away, rather than as unavailable
+#[derive(Default)]
+pub(super) struct LoadFailures {
+    loading: Option<SpotifyId>,
+}
+
+impl LoadFailures {
+    pub(super) fn translate(&mut self, event: PlayerEvent) -> PlayerEvent {
+        match event {
+            PlayerEvent::Loading { track_id, .. } => {
+                self.loading = Some(track_id);
+                event
+            }
+            PlayerEvent::EndOfTrack {
+                track_id,
+                play_request_id,
+            } if self.loading.take() == Some(track_id) => PlayerEvent::Unavailable {
+                track_id,
+                play_request_id,
+            },
+            PlayerEvent::Playing { .. }
+            | PlayerEvent::Paused { .. }
+            | PlayerEvent::Stopped { .. } => {
+                self.loading = None;
+                event
+            }
+            _ => event,
+        }
+    }
+}
+
 async fn player_setup_delegate(
     mut channel: PlayerEventChannel,
     delegate: Rc<dyn SpotifyPlayerDelegate>,
 ) {
+    let mut load_failures = LoadFailures::default();
     while let Some(event) = channel.recv().await {
-        match event {
-            PlayerEvent::EndOfTrack { .. } => {
-                delegate.end_of_track_reached();
-            }
-            PlayerEvent::Playing { position_ms, .. } => {
-                delegate.notify_playback_state(position_ms);
-            }
-            PlayerEvent::TimeToPreloadNextTrack { .. } => {
-                debug!("Requestiong next track to be preloaded...");
-                delegate.preload_next_track();
-            }
-            _ => {}
-        }
+        notify_delegate(load_failures.translate(event), delegate.as_ref());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(id: &str) -> SpotifyId {
+        SpotifyId::from_base62(id).unwrap()
+    }
+
+    #[test]
+    fn test_reconnect_delay() {
+        assert_eq!(reconnect_delay(0), Duration::from_secs(1));
+        assert_eq!(reconnect_delay(3), Duration::from_secs(8));
+        assert_eq!(
+            reconnect_delay(MAX_RECONNECT_ATTEMPTS),
+            Duration::from_secs(64)
+        );
+        assert_eq!(reconnect_delay(100), Duration::from_secs(64));
+    }
+
+    #[test]
+    fn test_loaded_track_position() {
+        let mut loaded = LoadedTrack::new(track("4uLU6hMCjMI75M1A2tKUQC"), 1000, false);
+        assert_eq!(loaded.position_ms(1.0), 1000);
+
+        loaded.playing_since = Some(Instant::now() - Duration::from_secs(2));
+        let position = loaded.position_ms(1.0);
+        assert!((3000..3100).contains(&position));
+        let position = loaded.position_ms(1.5);
+        assert!((4000..4100).contains(&position));
+
+        loaded.set_playing(false, 1.0);
+        assert!(loaded.playing_since.is_none());
+        assert!((3000..3100).contains(&loaded.position_ms(1.0)));
+
+        loaded.seek(500);
+        assert_eq!(loaded.position_ms(1.0), 500);
+    }
+
+    #[test]
+    fn test_load_failures() {
+        let mut load_failures = LoadFailures::default();
+        let failing = track("4uLU6hMCjMI75M1A2tKUQC");
+        let playing = track("6rqhFgbbKwnb9MLmUQDhG6");
+
+        let event = load_failures.translate(PlayerEvent::Loading {
+            play_request_id: 0,
+            track_id: failing,
+            position_ms: 0,
+        });
+        assert!(matches!(event, PlayerEvent::Loading { .. }));
+        let event = load_failures.translate(PlayerEvent::EndOfTrack {
+            play_request_id: 0,
+            track_id: failing,
+        });
+        assert!(matches!(event, PlayerEvent::Unavailable { track_id, .. } if track_id == failing));
+
+        load_failures.translate(PlayerEvent::Loading {
+            play_request_id: 1,
+            track_id: playing,
+            position_ms: 0,
+        });
+        load_failures.translate(PlayerEvent::Playing {
+            play_request_id: 1,
+            track_id: playing,
+            position_ms: 0,
+            duration_ms: 1000,
+        });
+        let event = load_failures.translate(PlayerEvent::EndOfTrack {
+            play_request_id: 1,
+            track_id: playing,
+        });
+        assert!(matches!(event, PlayerEvent::EndOfTrack { .. }));
     }
 }
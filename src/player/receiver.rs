@@ -0,0 +1,114 @@
+use futures::channel::mpsc::UnboundedSender;
+use std::cell::Cell;
+use std::rc::Rc;
+use tokio::task::{self, JoinHandle};
+
+use librespot::connect::spirc::Spirc;
+use librespot::core::config::{ConnectConfig, DeviceType};
+use librespot::core::session::Session;
+use librespot::playback::mixer::Mixer;
+use librespot::playback::player::{Player, PlayerEvent, PlayerEventChannel};
+
+use super::{Command, SpotifyPlayerDelegate};
+
+// Makes the session visible to the other Spotify clients of the logged in account (through spirc),
+// so that playback can be handed off to Spot from a phone for instance.
+// Spirc drives its own player, which is only used while Spot is controlled remotely.
+pub struct ConnectReceiver {
+    spirc: Spirc,
+    // Whether spirc's player has (or is about to have) the audio output
+    active: Rc<Cell<bool>>,
+    events: JoinHandle<()>,
+}
+
+impl ConnectReceiver {
+    pub fn start(
+        session: Session,
+        (player, channel): (Player, PlayerEventChannel),
+        mixer: Box<dyn Mixer>,
+        commands: UnboundedSender<Command>,
+        delegate: Rc<dyn SpotifyPlayerDelegate>,
+    ) -> Self {
+        let name = receiver_name();
+        let device_id = session.device_id().to_string();
+        info!("starting connect receiver {} ({})", &name, &device_id);
+
+        let config = ConnectConfig {
+            name: name.clone(),
+            device_type: DeviceType::Computer,
+            initial_volume: Some(mixer.volume()),
+            has_volume_ctrl: true,
+            autoplay: false,
+        };
+        let (spirc, spirc_task) = Spirc::new(config, session, player, mixer);
+        task::spawn_local(spirc_task);
+
+        let active = Rc::new(Cell::new(false));
+        let events = task::spawn_local(receiver_events(
+            channel,
+            device_id,
+            name,
+            Rc::clone(&active),
+            commands,
+            delegate,
+        ));
+
+        Self {
+            spirc,
+            active,
+            events,
+        }
+    }
+
+    // Lets go of the audio output, before the local player needs it
+    pub fn release_output(&self) {
+        if self.active.get() {
+            self.spirc.pause();
+        }
+    }
+}
+
+impl Drop for ConnectReceiver {
+    fn drop(&mut self) {
+        self.spirc.shutdown();
+        self.events.abort();
+    }
+}
+
+fn receiver_name() -> String {
+    format!("Spot ({})", glib::host_name())
+}
+
+// Spirc only loads tracks when told to by another client, which is how we know we were picked.
+// It stops its player when another device takes over, but also when it runs out of tracks:
+// only the Connect state tells whether we are still the active device.
+async fn receiver_events(
+    mut channel: PlayerEventChannel,
+    device_id: String,
+    name: String,
+    active: Rc<Cell<bool>>,
+    commands: UnboundedSender<Command>,
+    delegate: Rc<dyn SpotifyPlayerDelegate>,
+) {
+    while let Some(event) = channel.recv().await {
+        match event {
+            PlayerEvent::Loading { .. } | PlayerEvent::Playing { .. } => {
+                // Both players can't have the audio output at once, ours has to let go first
+                if !active.replace(true) {
+                    let _ = commands.unbounded_send(Command::PlayerStop);
+                }
+                delegate.receiver_activated(device_id.clone(), name.clone());
+            }
+            PlayerEvent::Paused { .. } => {
+                active.set(false);
+            }
+            PlayerEvent::Stopped { .. } => {
+                active.set(false);
+                delegate.receiver_stopped();
+            }
+            _ => {}
+        }
+        // Spirc tells the other devices about what it does, but not us
+        delegate.remote_state_changed();
+    }
+}
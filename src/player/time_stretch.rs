@@ -0,0 +1,233 @@
+use librespot::playback::audio_backend::{Sink, SinkResult};
+use librespot::playback::convert::Converter;
+use librespot::playback::decoder::AudioPacket;
+use librespot::playback::NUM_CHANNELS;
+
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+const CHANNELS: usize = NUM_CHANNELS as usize;
+
+// In frames: segments of about 23ms, overlapping by half, each shifted by up to 6ms
+// so that it lines up with the end of the previous one
+const WINDOW: usize = 1024;
+const HOP: usize = WINDOW / 2;
+const TOLERANCE: usize = 256;
+
+// Shared with the audio thread, which reads it for every packet
+#[derive(Clone, Debug)]
+pub struct PlaybackRate(Arc<AtomicU64>);
+
+impl PlaybackRate {
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, rate: f64) {
+        self.0.store(rate.to_bits(), Ordering::Relaxed);
+    }
+}
+
+impl Default for PlaybackRate {
+    fn default() -> Self {
+        Self(Arc::new(AtomicU64::new(1.0f64.to_bits())))
+    }
+}
+
+// Changes the speed of the audio without changing its pitch (WSOLA)
+pub struct TimeStretcher {
+    // Interleaved samples not played yet
+    input: Vec<f64>,
+    // Where the next segment starts unless a nearby spot lines up better, in frames
+    position: f64,
+    // Where the previous segment would have gone on, which the next one should resemble
+    continuation: Option<usize>,
+    overlap: Vec<f64>,
+    window: Vec<f64>,
+}
+
+impl Default for TimeStretcher {
+    fn default() -> Self {
+        let window = (0..WINDOW)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f64 / WINDOW as f64).cos())
+            .collect();
+        Self {
+            input: vec![],
+            position: 0.0,
+            continuation: None,
+            overlap: vec![0.0; HOP * CHANNELS],
+            window,
+        }
+    }
+}
+
+impl TimeStretcher {
+    pub fn reset(&mut self) {
+        self.input.clear();
+        self.position = 0.0;
+        self.continuation = None;
+        self.overlap.iter_mut().for_each(|sample| *sample = 0.0);
+    }
+
+    pub fn process(&mut self, samples: &[f64], rate: f64) -> Vec<f64> {
+        self.input.extend_from_slice(samples);
+        let mut output = vec![];
+        loop {
+            let frames = self.input.len() / CHANNELS;
+            let nominal = self.position.round() as usize;
+            if nominal + TOLERANCE + WINDOW > frames {
+                break;
+            }
+            let start = match self.continuation {
+                Some(continuation) => self.best_match(
+                    continuation,
+                    nominal.saturating_sub(TOLERANCE),
+                    nominal + TOLERANCE,
+                ),
+                None => nominal,
+            };
+
+            let segment = &self.input[start * CHANNELS..(start + WINDOW) * CHANNELS];
+            let (head, tail) = segment.split_at(HOP * CHANNELS);
+            output.extend(
+                head.iter()
+                    .zip(self.overlap.iter())
+                    .enumerate()
+                    .map(|(i, (sample, overlap))| overlap + sample * self.window[i / CHANNELS]),
+            );
+            for (i, (overlap, sample)) in self.overlap.iter_mut().zip(tail).enumerate() {
+                *overlap = sample * self.window[HOP + i / CHANNELS];
+            }
+
+            self.continuation = Some(start + HOP);
+            self.position += HOP as f64 * rate;
+            self.discard_played(start + HOP);
+        }
+        output
+    }
+
+    fn discard_played(&mut self, continuation: usize) {
+        let played = (self.position as usize)
+            .saturating_sub(TOLERANCE)
+            .min(continuation);
+        if played > 0 {
+            self.input.drain(..played * CHANNELS);
+            self.position -= played as f64;
+            self.continuation = Some(continuation - played);
+        }
+    }
+
+    fn mono(&self, frame: usize) -> f64 {
+        let i = frame * CHANNELS;
+        self.input[i..i + CHANNELS].iter().sum::<f64>() / CHANNELS as f64
+    }
+
+    // The start of the segment most similar to what follows the previous one
+    fn best_match(&self, continuation: usize, from: usize, to: usize) -> usize {
+        let mut best = (f64::MIN, from);
+        for start in from..=to {
+            let (correlation, energy) = (0..HOP).fold((0.0, 0.0), |(c, e), i| {
+                let sample = self.mono(start + i);
+                (
+                    c + sample * self.mono(continuation + i),
+                    e + sample * sample,
+                )
+            });
+            let score = correlation / energy.sqrt().max(f64::EPSILON);
+            if score > best.0 {
+                best = (score, start);
+            }
+        }
+        best.1
+    }
+}
+
+pub struct TimeStretchingSink {
+    sink: Box<dyn Sink>,
+    rate: PlaybackRate,
+    stretcher: TimeStretcher,
+}
+
+impl TimeStretchingSink {
+    pub fn new(sink: Box<dyn Sink>, rate: PlaybackRate) -> Self {
+        Self {
+            sink,
+            rate,
+            stretcher: Default::default(),
+        }
+    }
+}
+
+impl Sink for TimeStretchingSink {
+    fn start(&mut self) -> SinkResult<()> {
+        self.sink.start()
+    }
+
+    fn stop(&mut self) -> SinkResult<()> {
+        self.stretcher.reset();
+        self.sink.stop()
+    }
+
+    fn write(&mut self, packet: AudioPacket, converter: &mut Converter) -> SinkResult<()> {
+        let rate = self.rate.get();
+        match packet {
+            AudioPacket::Samples(samples) if (rate - 1.0).abs() > f64::EPSILON => {
+                let stretched = self.stretcher.process(&samples, rate);
+                if stretched.is_empty() {
+                    Ok(())
+                } else {
+                    self.sink.write(AudioPacket::Samples(stretched), converter)
+                }
+            }
+            packet => {
+                self.stretcher.reset();
+                self.sink.write(packet, converter)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn tone(frames: usize) -> Vec<f64> {
+        (0..frames)
+            .flat_map(|i| {
+                let sample = (i as f64 * 0.05).sin();
+                [sample, sample]
+            })
+            .collect()
+    }
+
+    fn stretched_frames(rate: f64) -> usize {
+        let mut stretcher = TimeStretcher::default();
+        let input = tone(44100);
+        let output: Vec<f64> = input
+            .chunks(4096)
+            .flat_map(|chunk| stretcher.process(chunk, rate))
+            .collect();
+        output.len() / CHANNELS
+    }
+
+    #[test]
+    fn test_length_follows_rate() {
+        let margin = 2 * (WINDOW + TOLERANCE);
+        let faster = stretched_frames(2.0);
+        assert!(faster <= 22050 && faster + margin >= 22050, "{}", faster);
+        let slower = stretched_frames(0.5);
+        assert!(slower <= 88200 && slower + margin >= 88200, "{}", slower);
+    }
+
+    #[test]
+    fn test_steady_tone_keeps_its_level() {
+        let mut stretcher = TimeStretcher::default();
+        let output = stretcher.process(&tone(44100), 1.5);
+        let peak = output[WINDOW * CHANNELS..]
+            .iter()
+            .fold(0.0f64, |peak, sample| peak.max(sample.abs()));
+        assert!((peak - 1.0).abs() < 0.05, "{}", peak);
+    }
+}
@@ -1,7 +1,11 @@
-use crate::player::{AudioBackend, SpotifyPlayerSettings};
+use crate::player::{
+    alsa_mixer_device, AudioBackend, MixerBackend, MixerSettings, SpotifyPlayerSettings,
+    VolumeCurve,
+};
 use gio::prelude::SettingsExt;
 use libadwaita::ColorScheme;
-use librespot::playback::config::Bitrate;
+use librespot::playback::config::{Bitrate, NormalisationType};
+use std::time::Duration;
 
 const SETTINGS: &str = "dev.alextren.Spot";
 
@@ -45,16 +49,54 @@ impl SpotifyPlayerSettings {
             _ => None,
         }?;
         let backend = match settings.enum_("audio-backend") {
-            0 => Some(AudioBackend::PulseAudio),
+            0 => Some(AudioBackend::PulseAudio(
+                Some(settings.string("pulseaudio-sink").as_str().to_string())
+                    .filter(|sink| !sink.is_empty()),
+            )),
             1 => Some(AudioBackend::Alsa(
                 settings.string("alsa-device").as_str().to_string(),
             )),
             2 => Some(AudioBackend::GStreamer(
-                "audioconvert dithering=none ! audioresample ! pipewiresink".to_string(),
+                settings.string("gstreamer-pipeline").as_str().to_string(),
+            )),
+            3 => Some(AudioBackend::Pipe(
+                settings.string("pipe-path").as_str().to_string(),
+            )),
+            4 => Some(AudioBackend::Subprocess(
+                settings.string("subprocess-command").as_str().to_string(),
             )),
             _ => None,
         }?;
+        let mixer_backend = match &backend {
+            AudioBackend::Alsa(device) if settings.boolean("alsa-hardware-mixer") => {
+                MixerBackend::Alsa {
+                    device: alsa_mixer_device(device),
+                    control: settings.string("alsa-mixer-control").as_str().to_string(),
+                }
+            }
+            _ => MixerBackend::Software,
+        };
+        let volume_curve = match settings.enum_("volume-curve") {
+            0 => Some(VolumeCurve::Linear),
+            1 => Some(VolumeCurve::Log(settings.double("volume-db-range"))),
+            2 => Some(VolumeCurve::Fixed),
+            _ => None,
+        }?;
+        let mixer = MixerSettings {
+            backend: mixer_backend,
+            volume_curve,
+        };
         let gapless = settings.boolean("gapless-playback");
+        let crossfade = Duration::from_secs(settings.uint("crossfade-duration").into());
+        let normalisation = settings.boolean("volume-normalisation");
+        let normalisation_type = match settings.enum_("normalisation-type") {
+            0 => Some(NormalisationType::Auto),
+            1 => Some(NormalisationType::Track),
+            2 => Some(NormalisationType::Album),
+            _ => None,
+        }?;
+        let normalisation_pregain_db = settings.double("normalisation-pregain");
+        let normalisation_threshold_dbfs = settings.double("normalisation-threshold");
 
         let ap_port_val = settings.uint("ap-port");
         if ap_port_val > 65535 {
@@ -68,21 +110,69 @@ impl SpotifyPlayerSettings {
             0 => None,
             x => Some(x as u16),
         };
+        let connect_receiver = settings.boolean("connect-receiver");
 
         Some(Self {
             bitrate,
             backend,
+            mixer,
             gapless,
+            normalisation,
+            normalisation_type,
+            normalisation_pregain_db,
+            normalisation_threshold_dbfs,
             ap_port,
+            connect_receiver,
+            crossfade,
         })
     }
 }
 
+// Maximum sizes of the caches, in megabytes
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CacheSettings {
+    pub network_max_size: u32,
+    pub image_max_size: u32,
+}
+
+impl CacheSettings {
+    pub fn new_from_gsettings() -> Self {
+        let settings = gio::Settings::new(SETTINGS);
+        Self {
+            network_max_size: settings.uint("network-cache-max-size"),
+            image_max_size: settings.uint("image-cache-max-size"),
+        }
+    }
+}
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self {
+            network_max_size: 200,
+            image_max_size: 500,
+        }
+    }
+}
+
+// The volume isn't part of the player settings, it changes too often for that
+pub fn saved_volume() -> f64 {
+    let settings = gio::Settings::new(SETTINGS);
+    settings.double("volume")
+}
+
+pub fn save_volume(volume: f64) -> Option<()> {
+    let settings = gio::Settings::new(SETTINGS);
+    settings.set_double("volume", volume.clamp(0.0, 1.0)).ok()
+}
+
 #[derive(Debug, Clone)]
 pub struct SpotSettings {
     pub theme_preference: ColorScheme,
     pub player_settings: SpotifyPlayerSettings,
     pub window: WindowGeometry,
+    pub cache: CacheSettings,
+    // Whether to play recommended tracks once the queue ends
+    pub autoplay: bool,
 }
 
 impl SpotSettings {
@@ -98,6 +188,8 @@ impl SpotSettings {
             theme_preference,
             player_settings: SpotifyPlayerSettings::new_from_gsettings()?,
             window: WindowGeometry::new_from_gsettings(),
+            cache: CacheSettings::new_from_gsettings(),
+            autoplay: settings.boolean("autoplay"),
         })
     }
 }
@@ -108,6 +200,8 @@ impl Default for SpotSettings {
             theme_preference: ColorScheme::PreferDark,
             player_settings: Default::default(),
             window: Default::default(),
+            cache: Default::default(),
+            autoplay: false,
         }
     }
 }